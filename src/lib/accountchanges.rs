@@ -0,0 +1,183 @@
+//! The changes to the accounts made by a block, computed once per block for the indexes of the node
+//! (`Leaderboard`, `AccountHistory`), so that the senders of the transactions are only recovered once.
+
+use std::collections::HashMap;
+
+use ethereum_types::{Address, H256};
+
+use crate::lib::{Block, Wei, WorldState};
+
+/// A change of the balance of an account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BalanceUpdate {
+    pub address: Address,
+    /// `None` if the account is new.
+    pub previous: Option<Wei>,
+    /// `None` if the account was deleted.
+    pub balance: Option<Wei>,
+}
+
+/// The accounts involved in the transactions of a block and the balances it changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountChanges {
+    /// Number of the block.
+    pub number: u64,
+    /// The hash of each transaction of the block and the accounts involved: its sender and its
+    /// recipient, unless it's the sender.
+    pub transactions: Vec<(H256, Vec<Address>)>,
+    /// The balances that changed in the post-state of the block.
+    pub balances: Vec<BalanceUpdate>,
+}
+
+impl AccountChanges {
+    /// The transactions of the block, without balance changes (see `with_balances`).
+    pub fn of_block(block: &Block) -> Self {
+        let transactions = block
+            .transactions
+            .iter()
+            .map(|transaction| {
+                let sender = transaction.sender();
+                let recipient = transaction.to().filter(|to| Some(*to) != sender);
+                let accounts = sender.into_iter().chain(recipient).collect();
+                (transaction.hash(), accounts)
+            })
+            .collect();
+        Self {
+            number: block.header.number,
+            transactions,
+            balances: vec![],
+        }
+    }
+
+    /// The balance changes of `state`, the post-state of the block with the given number, made outside
+    /// of transactions.
+    pub fn of_state(number: u64, state: &WorldState, previous: &HashMap<Address, Wei>) -> Self {
+        Self {
+            number,
+            ..Self::default()
+        }
+        .with_balances(state, previous)
+    }
+
+    /// Adds the balances of `state` that differ from `previous`, the balances of all accounts of the
+    /// last recorded state.
+    pub fn with_balances(mut self, state: &WorldState, previous: &HashMap<Address, Wei>) -> Self {
+        let mut new_accounts = 0;
+        for (address, account) in &state.accounts {
+            let previous = previous.get(address).cloned();
+            if previous.is_none() {
+                new_accounts += 1;
+            }
+            if previous != Some(account.balance) {
+                self.balances.push(BalanceUpdate {
+                    address: *address,
+                    previous,
+                    balance: Some(account.balance),
+                });
+            }
+        }
+
+        // all accounts of the state were compared, so none was deleted if the sizes match
+        if previous.len() + new_accounts != state.accounts.len() {
+            for (address, balance) in previous {
+                if !state.accounts.contains_key(address) {
+                    self.balances.push(BalanceUpdate {
+                        address: *address,
+                        previous: Some(*balance),
+                        balance: None,
+                    });
+                }
+            }
+        }
+        self
+    }
+}
+
+/// A block with the given number and a transfer of 100 Wei signed with `key` to each recipient, for
+/// the tests of the indexes.
+#[cfg(test)]
+pub fn block_with_transfers(
+    number: u64,
+    key: &secp256k1::SecretKey,
+    recipients: &[Address],
+) -> Block {
+    use crate::lib::{LegacyTransaction, Transaction, DEV_CHAIN_ID};
+
+    let mut block = Block::default();
+    block.header.number = number;
+    for (nonce, recipient) in recipients.iter().enumerate() {
+        let mut transaction = Transaction::Legacy(LegacyTransaction {
+            nonce: nonce.into(),
+            gas_limit: 21000.into(),
+            to: Some(*recipient),
+            value: 100.into(),
+            ..LegacyTransaction::default()
+        });
+        transaction.sign(key, Some(DEV_CHAIN_ID));
+        block.transactions.push(transaction);
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{dev_accounts, AccountState};
+
+    #[test]
+    fn test_account_changes() {
+        let (sender, key) = dev_accounts().into_iter().next().unwrap();
+        let block = block_with_transfers(3, &key, &[Address::from(1), sender]);
+        let changes = AccountChanges::of_block(&block);
+        assert_eq!(changes.number, 3);
+        assert_eq!(
+            changes.transactions,
+            vec![
+                (block.transactions[0].hash(), vec![sender, Address::from(1)]),
+                (block.transactions[1].hash(), vec![sender]),
+            ]
+        );
+
+        let mut state = WorldState::new();
+        state
+            .accounts
+            .insert(Address::from(1), AccountState::new(5.into()));
+        state
+            .accounts
+            .insert(Address::from(2), AccountState::new(7.into()));
+        let previous: HashMap<Address, Wei> =
+            vec![(Address::from(1), 5.into())].into_iter().collect();
+        let changes = AccountChanges::of_state(3, &state, &previous);
+        assert_eq!(
+            changes.balances,
+            vec![BalanceUpdate {
+                address: Address::from(2),
+                previous: None,
+                balance: Some(7.into()),
+            }]
+        );
+
+        // a new and a deleted account, with the same number of accounts as before
+        let previous: HashMap<Address, Wei> =
+            vec![(Address::from(1), 5.into()), (Address::from(3), 1.into())]
+                .into_iter()
+                .collect();
+        let mut balances = AccountChanges::of_state(3, &state, &previous).balances;
+        balances.sort_by_key(|change| change.address);
+        assert_eq!(
+            balances,
+            vec![
+                BalanceUpdate {
+                    address: Address::from(2),
+                    previous: None,
+                    balance: Some(7.into()),
+                },
+                BalanceUpdate {
+                    address: Address::from(3),
+                    previous: Some(1.into()),
+                    balance: None,
+                },
+            ]
+        );
+    }
+}
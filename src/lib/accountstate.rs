@@ -1,8 +1,9 @@
 use ethereum_types::{H256, U256};
-use rlp::{Encodable, RlpStream};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use tiny_keccak::keccak256;
 
 use crate::lib::utils::Wei;
+use crate::lib::EMPTY_TRIE_ROOT;
 
 /// σ[a]
 #[derive(Debug, PartialEq, Clone)]
@@ -33,7 +34,7 @@ impl Default for AccountState {
         AccountState {
             nonce: 0.into(),
             balance: 0.into(),
-            storage_root: keccak256(&[]).into(),
+            storage_root: *EMPTY_TRIE_ROOT,
             code_hash: keccak256(&[]).into(),
         }
     }
@@ -67,6 +68,7 @@ impl AccountState {
 /// p(a) ≡ KEC(a), RLP(σ[a]n, σ[a]b, σ[a]s, σ[a]c))
 impl Encodable for AccountState {
     fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
         s.append(&self.nonce);
         s.append(&self.balance);
         s.append(&self.storage_root);
@@ -74,8 +76,19 @@ impl Encodable for AccountState {
     }
 }
 
-// TODO!: world-state collapse function L_S
-// This function, LS, is used alongside the trie function to provide a short identity (hash) of the world state
+impl Decodable for AccountState {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            nonce: rlp.val_at(0)?,
+            balance: rlp.val_at(1)?,
+            storage_root: rlp.val_at(2)?,
+            code_hash: rlp.val_at(3)?,
+        })
+    }
+}
 
 // TODO!: v is the account validity function...
 
@@ -94,6 +107,25 @@ mod tests {
         assert!(!acc.is_empty());
     }
 
+    #[test]
+    fn test_account_state_rlp() {
+        let account = AccountState {
+            nonce: 1234.into(),
+            balance: Wei::from_dec_str("100000000000000000000").unwrap(),
+            ..AccountState::default()
+        };
+        let rlp: Vec<u8> = rlp::encode(&account);
+        assert_eq!(
+            crate::lib::to_hex(&rlp),
+            concat!(
+                "0xf84f8204d289056bc75e2d63100000",
+                "a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+                "a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+            )
+        );
+        assert_eq!(rlp::decode::<AccountState>(&rlp).unwrap(), account);
+    }
+
     #[test]
     fn print_sizes() {
         // run with `cargo test -- --nocapture`
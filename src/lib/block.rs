@@ -1,15 +1,102 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
 use std::time::SystemTime;
 
-use ethereum_types::{Address, Bloom, H256, U256};
-use rlp::{encode, Encodable, RlpStream, EMPTY_LIST_RLP};
+use ethereum_types::{Address, Bloom, H256, H64, U256};
+use lazy_static::lazy_static;
+use rlp::{encode, Decodable, DecoderError, Encodable, Rlp, RlpStream, EMPTY_LIST_RLP};
 use tiny_keccak::keccak256;
 
-use crate::lib::Transaction;
+use crate::lib::{
+    ordered_trie_root, ChainConfig, Genesis, LegacyTransaction, Receipt, StateDb, Transaction,
+    TransactionError, WorldState, EMPTY_TRIE_ROOT, ONE_ETHER,
+};
+
+lazy_static! {
+    /// R<sub>block</sub> - the block reward (Section 11.3), 2 Ether as of Constantinople.
+    pub static ref BLOCK_REWARD: U256 = *ONE_ETHER * U256::from(2);
+}
+
+/// The base fee of the first block after the London fork (EIP-1559), 1 Gwei.
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+
+/// Reasons for a block to be invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockError {
+    /// The parent block is unknown.
+    UnknownParent(H256),
+    /// The parent is not the latest block; forks aren't supported.
+    NotOnLatestBlock(H256),
+    /// H<sub>g</sub> > H<sub>l</sub>
+    GasUsedExceedsGasLimit,
+    /// Equation 47
+    InvalidGasLimit,
+    /// Equation 48
+    InvalidTimestamp,
+    /// H<sub>i</sub> ≠ P(H)<sub>H<sub>i</sub></sub> + 1
+    InvalidNumber,
+    /// H<sub>f</sub> doesn't follow from the parent (EIP-1559), or is present before the London fork.
+    InvalidBaseFee {
+        expected: Option<U256>,
+        got: Option<U256>,
+    },
+    /// ∥H<sub>x</sub>∥ > 32
+    ExtraDataTooLong,
+    /// H<sub>o</sub> ≠ KEC(RLP(B<sub>U</sub>))
+    InvalidOmmersHash,
+    /// H<sub>t</sub> doesn't match the transactions.
+    InvalidTransactionsRoot,
+    /// The transaction at the given index is invalid.
+    InvalidTransaction(usize, TransactionError),
+    /// H<sub>g</sub> doesn't match the gas used by the transactions.
+    InvalidGasUsed { expected: U256, got: U256 },
+    /// H<sub>e</sub> doesn't match the receipts.
+    InvalidReceiptsRoot,
+    /// H<sub>b</sub> doesn't match the receipts.
+    InvalidLogsBloom,
+    /// H<sub>r</sub> doesn't match the state after execution.
+    InvalidStateRoot { expected: H256, got: H256 },
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BlockError::*;
+        match self {
+            UnknownParent(hash) => write!(f, "unknown parent block {:?}", hash),
+            NotOnLatestBlock(hash) => write!(f, "parent {:?} is not the latest block", hash),
+            GasUsedExceedsGasLimit => write!(f, "gas used exceeds gas limit"),
+            InvalidGasLimit => write!(f, "invalid gas limit"),
+            InvalidTimestamp => write!(f, "timestamp not after parent's"),
+            InvalidNumber => write!(f, "number is not parent's number + 1"),
+            InvalidBaseFee { expected, got } => write!(
+                f,
+                "invalid base fee (expected {:?}, got {:?})",
+                expected, got
+            ),
+            ExtraDataTooLong => write!(f, "extra data longer than 32 bytes"),
+            InvalidOmmersHash => write!(f, "invalid ommers hash"),
+            InvalidTransactionsRoot => write!(f, "invalid transactions root"),
+            InvalidTransaction(index, error) => {
+                write!(f, "invalid transaction at index {}: {}", index, error)
+            }
+            InvalidGasUsed { expected, got } => {
+                write!(f, "invalid gas used (expected {}, got {})", expected, got)
+            }
+            InvalidReceiptsRoot => write!(f, "invalid receipts root"),
+            InvalidLogsBloom => write!(f, "invalid logs bloom"),
+            InvalidStateRoot { expected, got } => write!(
+                f,
+                "invalid state root (expected {:?}, got {:?})",
+                expected, got
+            ),
+        }
+    }
+}
 
 /// H
 #[allow(dead_code)]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct BlockHeader {
     /// The Keccak 256-bit hash of the parent block’s header, in its entirety; formally H<sub>p</sub>.
     pub parent_hash: H256,
@@ -45,13 +132,16 @@ pub struct BlockHeader {
     pub timestamp: u64,
     /// An arbitrary byte array containing data relevant to this block.
     /// This must be 32 bytes or fewer; formally H<sub>x</sub>.
-    pub extra_data: [u8; 32],
+    pub extra_data: Vec<u8>,
     /// A 256-bit hash which, combined with the nonce, proves that a sufficient amount of computation has
     /// been carried out on this block; formally H<sub>m</sub>.
     pub mix_hash: H256,
     /// A 64-bit value which, combined with the mix-hash, proves that a sufficient amount of computation has
     /// been carried out on this block; formally H<sub>n</sub>.
-    pub nonce: u64,
+    pub nonce: H64,
+    /// The price per unit of gas every transaction in this block pays and which is burnt (EIP-1559).
+    /// Only present after the London fork; formally H<sub>f</sub>.
+    pub base_fee_per_gas: Option<U256>,
 }
 
 impl BlockHeader {
@@ -80,6 +170,69 @@ impl BlockHeader {
         }
     }
 
+    /// H<sub>f</sub> of a child block of `parent` (EIP-1559): the parent's base fee, increased when the
+    /// parent used more than the gas target (H<sub>l</sub> ÷ ρ, the elasticity multiplier) and decreased
+    /// when it used less, by at most 1/ξ (the base fee max change denominator).
+    /// `None` before the London fork.
+    pub fn expected_base_fee(parent: &BlockHeader, config: &ChainConfig) -> Option<U256> {
+        let number = parent.number + 1;
+        if !ChainConfig::is_active(config.london_block, number) {
+            return None;
+        }
+        let parent_base_fee = match parent.base_fee_per_gas {
+            Some(base_fee) if config.london_block != Some(number) => base_fee,
+            _ => return Some(INITIAL_BASE_FEE.into()),
+        };
+
+        let gas_target = parent.gas_limit / config.elasticity_multiplier;
+        let denominator = U256::from(config.base_fee_max_change_denominator);
+        if gas_target.is_zero() || parent.gas_used == gas_target {
+            Some(parent_base_fee)
+        } else if parent.gas_used > gas_target {
+            let delta = parent_base_fee * (parent.gas_used - gas_target) / gas_target / denominator;
+            Some(parent_base_fee + delta.max(1.into()))
+        } else {
+            let delta = parent_base_fee * (gas_target - parent.gas_used) / gas_target / denominator;
+            Some(parent_base_fee - delta)
+        }
+    }
+
+    /// The header of a new, empty child block of `parent`, valid except for the fields that depend on
+    /// the transactions. The timestamp is moved after the parent's if necessary.
+    pub fn child_of(
+        parent: &BlockHeader,
+        beneficiary: Address,
+        timestamp: u64,
+        config: &ChainConfig,
+    ) -> Self {
+        let number = parent.number + 1;
+        BlockHeader {
+            parent_hash: parent.hash(),
+            ommers_hash: keccak256(&EMPTY_LIST_RLP).into(),
+            beneficiary,
+            state_root: parent.state_root,
+            transactions_root: *EMPTY_TRIE_ROOT,
+            receipts_root: *EMPTY_TRIE_ROOT,
+            difficulty: parent.difficulty,
+            number,
+            // the gas target of the fork block is the gas limit of its parent (EIP-1559)
+            gas_limit: if config.london_block == Some(number) {
+                parent.gas_limit * config.elasticity_multiplier
+            } else {
+                parent.gas_limit
+            },
+            timestamp: timestamp.max(parent.timestamp + 1),
+            nonce: H64::from(42),
+            base_fee_per_gas: Self::expected_base_fee(parent, config),
+            ..BlockHeader::default()
+        }
+    }
+
+    /// H<sub>f</sub>, or zero before the London fork.
+    pub fn base_fee(&self) -> U256 {
+        self.base_fee_per_gas.unwrap_or_default()
+    }
+
     /// The canonical gas limit H<sub>l</sub> of a block of header H must fulfil this relation (Equation 47).
     pub fn validate_gas_limit(gas_limit: U256, parent_gas_limit: U256) -> bool {
         gas_limit < parent_gas_limit + parent_gas_limit / 1024
@@ -108,19 +261,43 @@ impl BlockHeader {
     }
 
     /// Block header validity function V(H) (Equation 50)
-    pub fn validate(&self, parent: &BlockHeader) -> bool {
-        // TODO!!: log each step if false
-        let mut valid = Self::validate_nonce(self.nonce, self.difficulty);
+    pub fn validate(&self, parent: &BlockHeader, config: &ChainConfig) -> Result<(), BlockError> {
+        // TODO!: Self::validate_nonce(self.nonce, self.difficulty) - needs the PoW part (Ethash).
+        // The difficulty isn't checked either, teth keeps it constant for now.
+
+        if self.gas_used > self.gas_limit {
+            return Err(BlockError::GasUsedExceedsGasLimit);
+        }
+        // The gas target of the fork block is the gas limit of its parent (EIP-1559).
+        let parent_gas_limit = if config.london_block == Some(self.number) {
+            parent.gas_limit * config.elasticity_multiplier
+        } else {
+            parent.gas_limit
+        };
+        if !Self::validate_gas_limit(self.gas_limit, parent_gas_limit) {
+            return Err(BlockError::InvalidGasLimit);
+        }
+        // validate_gas_used is checked after execution, see `BlockChain::import_block`.
+        if !Self::validate_timestamp(self.timestamp, parent.timestamp) {
+            return Err(BlockError::InvalidTimestamp);
+        }
+        if self.number != parent.number + 1 {
+            return Err(BlockError::InvalidNumber);
+        }
 
-        valid &= self.gas_used <= self.gas_limit;
-        valid &= Self::validate_gas_limit(self.gas_limit, parent.gas_limit);
-        // TODO!!: validate_gas_used? it's in a different equation...
-        valid &= Self::validate_timestamp(self.timestamp, parent.timestamp);
-        valid &= self.number == parent.number + 1;
+        if self.extra_data.len() > 32 {
+            return Err(BlockError::ExtraDataTooLong);
+        }
 
-        // no need to check: ∥Hx∥ ≤ 32 (extra_data is a [u8; 32])
+        let expected_base_fee = Self::expected_base_fee(parent, config);
+        if self.base_fee_per_gas != expected_base_fee {
+            return Err(BlockError::InvalidBaseFee {
+                expected: expected_base_fee,
+                got: self.base_fee_per_gas,
+            });
+        }
 
-        valid
+        Ok(())
     }
 
     pub fn to_rlp(&self) -> Vec<u8> {
@@ -132,14 +309,18 @@ impl BlockHeader {
         keccak256(&self.to_rlp()).into()
     }
 
-    // TODO!: impl decode...
-    // pub fn from_rlp(data: &[u8]) -> Self {
-    //     decode(data).expect("could not decode")
-    // }
+    pub fn from_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        rlp::decode(data)
+    }
 }
 
 impl Encodable for BlockHeader {
     fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(if self.base_fee_per_gas.is_some() {
+            16
+        } else {
+            15
+        });
         s.append(&self.parent_hash);
         s.append(&self.ommers_hash);
         s.append(&self.beneficiary);
@@ -152,10 +333,43 @@ impl Encodable for BlockHeader {
         s.append(&self.gas_limit);
         s.append(&self.gas_used);
         s.append(&self.timestamp);
-        let extra_data: &[u8] = &self.extra_data;
-        s.append(&extra_data);
+        s.append(&self.extra_data);
         s.append(&self.mix_hash);
         s.append(&self.nonce);
+        if let Some(base_fee_per_gas) = &self.base_fee_per_gas {
+            s.append(base_fee_per_gas);
+        }
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let item_count = rlp.item_count()?;
+        if item_count != 15 && item_count != 16 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            parent_hash: rlp.val_at(0)?,
+            ommers_hash: rlp.val_at(1)?,
+            beneficiary: rlp.val_at(2)?,
+            state_root: rlp.val_at(3)?,
+            transactions_root: rlp.val_at(4)?,
+            receipts_root: rlp.val_at(5)?,
+            logs_bloom: rlp.val_at(6)?,
+            difficulty: rlp.val_at(7)?,
+            number: rlp.val_at(8)?,
+            gas_limit: rlp.val_at(9)?,
+            gas_used: rlp.val_at(10)?,
+            timestamp: rlp.val_at(11)?,
+            extra_data: rlp.val_at(12)?,
+            mix_hash: rlp.val_at(13)?,
+            nonce: rlp.val_at(14)?,
+            base_fee_per_gas: if item_count == 16 {
+                Some(rlp.val_at(15)?)
+            } else {
+                None
+            },
+        })
     }
 }
 
@@ -168,7 +382,7 @@ impl Encodable for BlockHeader {
 /// Formally, we can refer to a block B:  
 /// B ≡ (B<sub>H</sub>, B<sub>T</sub>, B<sub>U</sub>)
 #[allow(dead_code)]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct Block {
     /// B<sub>H</sub>
     pub header: BlockHeader,
@@ -198,49 +412,48 @@ impl Block {
     /// The proof-of-concept series include a development premine, making the state root hash some
     /// value _stateRoot_. Also _time_ will be set to the initial timestamp of the genesis block. The
     /// latest documentation should be consulted for those values.
+    ///
+    /// Here, _stateRoot_ is the empty trie and _time_ is 0; see `Genesis::block` for genesis blocks
+    /// with a premine.
     pub fn genesis_block() -> Self {
         Block {
             header: BlockHeader {
                 parent_hash: H256::zero(),
                 ommers_hash: keccak256(&EMPTY_LIST_RLP).into(),
                 beneficiary: Address::zero(),
-                state_root: H256::zero(), // TODO!: ??
-                transactions_root: H256::zero(),
-                receipts_root: H256::zero(),
+                state_root: *EMPTY_TRIE_ROOT,
+                transactions_root: *EMPTY_TRIE_ROOT,
+                receipts_root: *EMPTY_TRIE_ROOT,
                 logs_bloom: Bloom::zero(),
                 difficulty: (2 << 17).into(),
                 number: 0,
                 gas_limit: 3_141_592.into(),
                 gas_used: 0.into(),
-                timestamp: SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                extra_data: [0; 32],
+                timestamp: 0,
+                extra_data: vec![],
                 mix_hash: H256::zero(),
-                nonce: 42, // TODO: spec says KEC((42)), but it's a u64...?
+                nonce: H64::from(42), // TODO: spec says KEC((42)), but it's 64 bits...?
+                base_fee_per_gas: None,
             },
             transactions: vec![],
             ommers: vec![],
         }
     }
 
-    /// Test block with transactions and genesis block as parent
-    pub fn exodus_block() -> Self {
-        let genesis = Self::genesis_block();
-        let transaction = Transaction {
+    /// Test block with transactions and the given (genesis) block as parent
+    pub fn exodus_block(parent: &BlockHeader) -> Self {
+        let transaction = Transaction::Legacy(LegacyTransaction {
             nonce: 1.into(),
             gas_price: 2.into(),
             gas_limit: 84_000.into(),
             to: Some(Address::random()),
             value: 42_000.into(),
-            data: Some(vec![]),
-            ..Transaction::default()
-        };
+            ..LegacyTransaction::default()
+        });
         let gas_used = transaction.intrinsic_gas();
         Block {
             header: BlockHeader {
-                parent_hash: genesis.header.hash(),
+                parent_hash: parent.hash(),
                 ommers_hash: keccak256(&EMPTY_LIST_RLP).into(),
                 beneficiary: Address::zero(),
                 state_root: H256::zero(),
@@ -248,16 +461,17 @@ impl Block {
                 receipts_root: H256::zero(),
                 logs_bloom: Bloom::zero(),
                 difficulty: (2 << 17).into(),
-                number: 1,
-                gas_limit: 3_141_592.into(),
+                number: parent.number + 1,
+                gas_limit: parent.gas_limit,
                 gas_used: gas_used.into(),
                 timestamp: SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap()
                     .as_secs(),
-                extra_data: [0; 32],
+                extra_data: vec![],
                 mix_hash: H256::zero(),
-                nonce: 42,
+                nonce: H64::from(42),
+                base_fee_per_gas: None,
             },
             transactions: vec![transaction],
             ommers: vec![],
@@ -268,51 +482,328 @@ impl Block {
         encode(self)
     }
 
-    /// Keccak 256-bit hash
+    pub fn from_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        rlp::decode(data)
+    }
+
+    /// The hash of a block is the Keccak 256-bit hash of its header.
     pub fn hash(&self) -> H256 {
-        keccak256(&self.to_rlp()).into()
+        self.header.hash()
+    }
+
+    /// H<sub>o</sub> ≡ KEC(RLP(L<sup>*</sup><sub>H</sub>(B<sub>U</sub>)))
+    pub fn ommers_hash(&self) -> H256 {
+        let mut s = RlpStream::new();
+        s.append_list(&self.ommers);
+        keccak256(&s.out()).into()
+    }
+
+    /// H<sub>t</sub> - the root of the trie of the transactions, keyed by their index.
+    pub fn transactions_root(&self) -> H256 {
+        ordered_trie_root(self.transactions.iter().map(Transaction::to_rlp))
+    }
+
+    /// Validates the header against its parent and the ommers hash and transactions root against the body.
+    /// The state-dependent parts are checked in `BlockChain::import_block`.
+    pub fn validate(&self, parent: &BlockHeader, config: &ChainConfig) -> Result<(), BlockError> {
+        self.header.validate(parent, config)?;
+        if self.header.ommers_hash != self.ommers_hash() {
+            return Err(BlockError::InvalidOmmersHash);
+        }
+        if self.header.transactions_root != self.transactions_root() {
+            return Err(BlockError::InvalidTransactionsRoot);
+        }
+        Ok(())
+    }
+
+    /// Executes all transactions in order and applies the block finalisation (Section 11.4).
+    /// Returns the receipts B<sub>R</sub>.
+    pub fn execute(
+        &self,
+        state: &mut WorldState,
+        config: &ChainConfig,
+    ) -> Result<Vec<Receipt>, BlockError> {
+        let mut receipts = Vec::with_capacity(self.transactions.len());
+        let mut cumulative_gas_used = U256::zero();
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            let gas_available = self.header.gas_limit - cumulative_gas_used;
+            transaction
+                .validate(state, gas_available, &self.header, config)
+                .map_err(|e| BlockError::InvalidTransaction(i, e))?;
+            let outcome = transaction
+                .execute(state, &self.header)
+                .map_err(|e| BlockError::InvalidTransaction(i, e))?;
+            cumulative_gas_used += outcome.gas_used;
+            receipts.push(Receipt::new(
+                transaction.transaction_type(),
+                true,
+                cumulative_gas_used,
+                outcome.logs,
+            ));
+        }
+        self.finalize(state);
+        Ok(receipts)
+    }
+
+    /// Builds a valid child block of `parent`, whose post-state `state` must be, with as many of the
+    /// given transactions (in order) as are valid on top of each other and fit into the block.
+    /// Returns the block along with the transactions that were left out and why.
+    pub fn build(
+        parent: &BlockHeader,
+        state: &WorldState,
+        transactions: Vec<Transaction>,
+        beneficiary: Address,
+        timestamp: u64,
+        config: &ChainConfig,
+    ) -> (Block, Vec<(Transaction, TransactionError)>) {
+        let mut block = Block {
+            header: BlockHeader::child_of(parent, beneficiary, timestamp, config),
+            transactions: vec![],
+            ommers: vec![],
+        };
+
+        let mut state = state.clone();
+        let mut receipts = vec![];
+        let mut rejected = vec![];
+        let mut cumulative_gas_used = U256::zero();
+        for transaction in transactions {
+            let gas_available = block.header.gas_limit - cumulative_gas_used;
+            let outcome = transaction
+                .validate(&state, gas_available, &block.header, config)
+                .and_then(|_| transaction.execute(&mut state, &block.header));
+            match outcome {
+                Ok(outcome) => {
+                    cumulative_gas_used += outcome.gas_used;
+                    receipts.push(Receipt::new(
+                        transaction.transaction_type(),
+                        true,
+                        cumulative_gas_used,
+                        outcome.logs,
+                    ));
+                    block.transactions.push(transaction);
+                }
+                Err(e) => rejected.push((transaction, e)),
+            }
+        }
+        block.finalize(&mut state);
+
+        let mut logs_bloom = Bloom::zero();
+        for receipt in &receipts {
+            logs_bloom.accrue_bloom(&receipt.logs_bloom);
+        }
+        block.header.gas_used = cumulative_gas_used;
+        block.header.logs_bloom = logs_bloom;
+        block.header.transactions_root = block.transactions_root();
+        block.header.receipts_root = ordered_trie_root(receipts.iter().map(Receipt::to_rlp));
+        block.header.state_root = state.state_root();
+        (block, rejected)
+    }
+
+    /// Ω - the block reward is paid to the beneficiary, with an extra 1/32 of it for each included ommer,
+    /// and the beneficiaries of the ommers get (1 + (U<sub>i</sub> - B<sub>H<sub>i</sub></sub>)/8) of it
+    /// (Section 11.3, Equations 161-163).
+    pub fn finalize(&self, state: &mut WorldState) {
+        let reward = *BLOCK_REWARD + *BLOCK_REWARD * self.ommers.len() as u64 / 32;
+        state
+            .accounts
+            .entry(self.header.beneficiary)
+            .or_default()
+            .balance += reward;
+
+        for ommer in &self.ommers {
+            // (1 + (U_i - B_Hi) / 8) = (8 + U_i - B_Hi) / 8, where B_Hi - U_i is between 1 and 6
+            let reward = *BLOCK_REWARD * (8 + ommer.number).saturating_sub(self.header.number) / 8;
+            state.accounts.entry(ommer.beneficiary).or_default().balance += reward;
+        }
     }
 }
 
+/// B ≡ (B<sub>H</sub>, B<sub>T</sub>, B<sub>U</sub>) (Equation 35)
 impl Encodable for Block {
     fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
         s.append(&self.header);
-        for t in &self.transactions {
-            s.append(t);
-        }
-        for o in &self.ommers {
-            s.append(o);
+        s.append_list(&self.transactions);
+        s.append_list(&self.ommers);
+    }
+}
+
+impl Decodable for Block {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
         }
+        Ok(Self {
+            header: rlp.val_at(0)?,
+            transactions: rlp.list_at(1)?,
+            ommers: rlp.list_at(2)?,
+        })
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct BlockChain {
     // TODO!: change value to RLP-encoded block? or only header?
     /// key: Keccak Hash of BlockHeader
     pub blocks: HashMap<H256, Block>,
+    /// B<sub>R</sub> of each block, key: Keccak Hash of BlockHeader
+    pub receipts: HashMap<H256, Vec<Receipt>>,
+    /// Hash of the block containing the transaction and its index there, key: Keccak Hash of Transaction
+    pub transaction_locations: HashMap<H256, (H256, usize)>,
+    /// The post-states of the (most recent) blocks, key: state root H<sub>r</sub>
+    pub states: StateDb,
     pub latest_block_hash: H256,
+    pub config: ChainConfig,
 }
 
 #[allow(dead_code)]
 impl BlockChain {
-    pub fn new() -> Self {
-        let genesis_block = Block::genesis_block();
+    pub fn new(genesis_block: Block) -> Self {
         let genesis_hash = genesis_block.header.hash();
         let mut blocks = HashMap::new();
         blocks.insert(genesis_hash, genesis_block);
+        let mut receipts = HashMap::new();
+        receipts.insert(genesis_hash, vec![]);
         Self {
             blocks,
+            receipts,
+            transaction_locations: HashMap::new(),
+            states: StateDb::default(),
             latest_block_hash: genesis_hash,
+            config: ChainConfig::default(),
         }
     }
 
+    /// Chain starting with the genesis block and configuration of the given spec.
+    pub fn from_genesis(genesis: &Genesis) -> Self {
+        let mut block_chain = Self {
+            config: genesis.config.clone(),
+            ..Self::new(genesis.block())
+        };
+        let state = genesis.state();
+        block_chain.states.insert(state.state_root(), state);
+        block_chain
+    }
+
     pub fn add_block(&mut self, block: Block) {
         let hash = block.header.hash();
+        for (index, transaction) in block.transactions.iter().enumerate() {
+            self.transaction_locations
+                .insert(transaction.hash(), (hash, index));
+        }
         self.blocks.insert(hash, block);
         self.latest_block_hash = hash;
     }
 
+    pub fn latest_block(&self) -> &Block {
+        &self.blocks[&self.latest_block_hash]
+    }
+
+    /// The post-state of the block with the given header. States that are no longer retained are
+    /// recomputed by executing the blocks since the most recent retained state.
+    /// `None` if the block is not part of the chain.
+    pub fn state_at(&self, header: &BlockHeader) -> Option<Cow<'_, WorldState>> {
+        if let Some(state) = self.states.get(&header.state_root) {
+            return Some(Cow::Borrowed(state));
+        }
+
+        let mut blocks = vec![];
+        let mut hash = header.hash();
+        let base_state = loop {
+            let block = self.blocks.get(&hash)?;
+            if let Some(state) = self.states.get(&block.header.state_root) {
+                break state;
+            }
+            if block.header.number == 0 {
+                return None;
+            }
+            blocks.push(block);
+            hash = block.header.parent_hash;
+        };
+        let mut state = base_state.clone();
+        for block in blocks.iter().rev() {
+            block.execute(&mut state, &self.config).ok()?;
+        }
+        // The state might have been modified outside of blocks
+        if state.state_root() != header.state_root {
+            return None;
+        }
+        Some(Cow::Owned(state))
+    }
+
+    /// The block containing the transaction with the given hash and the index of the transaction in it.
+    pub fn transaction_location(&self, transaction_hash: &H256) -> Option<(&Block, usize)> {
+        let (block_hash, index) = self.transaction_locations.get(transaction_hash)?;
+        Some((&self.blocks[block_hash], *index))
+    }
+
+    /// Validates and executes the block on top of the latest block, whose post-state `state` must be.
+    /// On success, the block becomes the latest block and `state` is updated to its post-state;
+    /// otherwise, neither the chain nor `state` are modified.
+    pub fn import_block(&mut self, block: Block, state: &mut WorldState) -> Result<(), BlockError> {
+        let parent_hash = block.header.parent_hash;
+        let parent = self
+            .blocks
+            .get(&parent_hash)
+            .ok_or(BlockError::UnknownParent(parent_hash))?;
+        if parent_hash != self.latest_block_hash {
+            return Err(BlockError::NotOnLatestBlock(parent_hash));
+        }
+        block.validate(&parent.header, &self.config)?;
+
+        let mut new_state = state.clone();
+        let receipts = block.execute(&mut new_state, &self.config)?;
+
+        // Section 11.2, Equation 158
+        let gas_used = receipts
+            .last()
+            .map_or_else(U256::zero, |r| r.cumulative_gas_used);
+        if block.header.gas_used != gas_used {
+            return Err(BlockError::InvalidGasUsed {
+                expected: gas_used,
+                got: block.header.gas_used,
+            });
+        }
+        if block.header.receipts_root != ordered_trie_root(receipts.iter().map(Receipt::to_rlp)) {
+            return Err(BlockError::InvalidReceiptsRoot);
+        }
+        let mut logs_bloom = Bloom::zero();
+        for receipt in &receipts {
+            logs_bloom.accrue_bloom(&receipt.logs_bloom);
+        }
+        if block.header.logs_bloom != logs_bloom {
+            return Err(BlockError::InvalidLogsBloom);
+        }
+        let state_root = new_state.state_root();
+        if block.header.state_root != state_root {
+            return Err(BlockError::InvalidStateRoot {
+                expected: state_root,
+                got: block.header.state_root,
+            });
+        }
+
+        self.states.insert(state_root, new_state.clone());
+        *state = new_state;
+        self.receipts.insert(block.header.hash(), receipts);
+        self.add_block(block);
+        Ok(())
+    }
+
+    /// The blocks from genesis to the latest block.
+    pub fn canonical_blocks(&self) -> Vec<&Block> {
+        let mut blocks = vec![];
+        let mut hash = self.latest_block_hash;
+        while let Some(block) = self.blocks.get(&hash) {
+            blocks.push(block);
+            if block.header.number == 0 {
+                break;
+            }
+            hash = block.header.parent_hash;
+        }
+        blocks.reverse();
+        blocks
+    }
+
     /// Section 10, Equation 153, 154
     pub fn total_difficulty(&self, block_hash: &H256) -> U256 {
         let mut block = &self.blocks[block_hash];
@@ -328,6 +819,8 @@ impl BlockChain {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lib::{from_hex, StateRetention};
+    use secp256k1::SecretKey;
 
     #[test]
     fn test_block_header() {
@@ -348,14 +841,438 @@ mod tests {
         let _b = Block::default();
     }
 
+    /// Mainnet genesis block, see also `Block::genesis_block`.
+    fn mainnet_genesis_block() -> Block {
+        Block {
+            header: BlockHeader {
+                parent_hash: H256::zero(),
+                ommers_hash: keccak256(&EMPTY_LIST_RLP).into(),
+                beneficiary: Address::zero(),
+                state_root: H256::from(
+                    "0xd7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544",
+                ),
+                transactions_root: *EMPTY_TRIE_ROOT,
+                receipts_root: *EMPTY_TRIE_ROOT,
+                logs_bloom: Bloom::zero(),
+                difficulty: 0x4_0000_0000u64.into(),
+                number: 0,
+                gas_limit: 5000.into(),
+                gas_used: 0.into(),
+                timestamp: 0,
+                extra_data: from_hex(
+                    "0x11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa",
+                )
+                .unwrap(),
+                mix_hash: H256::zero(),
+                nonce: H64::from(0x42),
+                base_fee_per_gas: None,
+            },
+            transactions: vec![],
+            ommers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_mainnet_genesis_block() {
+        let block = mainnet_genesis_block();
+        assert_eq!(
+            block.hash(),
+            H256::from("0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")
+        );
+        assert_eq!(block.to_rlp().len(), 540);
+        assert_eq!(Block::from_rlp(&block.to_rlp()).unwrap(), block);
+    }
+
+    /// RLP of mainnet block 1.
+    const MAINNET_BLOCK_1_RLP: &str = concat!(
+        "f90216f90211a0d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69a",
+        "ec8c0db1cb8fa3a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413",
+        "f0a142fd40d493479405a56e2d52c817161883f50c441c3228cfe54d9fa0d67e",
+        "4d450343046425ae4271474353857ab860dbc0a1dde64b41b5cd3a532bf3a056",
+        "e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0",
+        "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+        "b901000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000008503ff80000001821388808455ba422499476574682f76312e302e302f",
+        "6c696e75782f676f312e342e32a0969b900de27b6ac6a67742365dd65f55a052",
+        "6c41fd18e1b16f1a1215c2e66f5988539bd4979fef1ec4c0c0",
+    );
+
+    #[test]
+    fn test_mainnet_block_1() {
+        let rlp = from_hex(MAINNET_BLOCK_1_RLP).unwrap();
+        let block = Block::from_rlp(&rlp).unwrap();
+        let header = &block.header;
+        assert_eq!(header.parent_hash, mainnet_genesis_block().hash());
+        assert_eq!(
+            header.beneficiary,
+            Address::from("0x05a56e2d52c817161883f50c441c3228cfe54d9f")
+        );
+        assert_eq!(header.difficulty, 0x3_ff80_0000u64.into());
+        assert_eq!(header.number, 1);
+        assert_eq!(header.gas_limit, 5000.into());
+        assert_eq!(header.timestamp, 1_438_269_988);
+        assert_eq!(header.extra_data, b"Geth/v1.0.0/linux/go1.4.2".to_vec());
+        assert_eq!(header.nonce, H64::from(0x539b_d497_9fef_1ec4));
+        assert!(block.transactions.is_empty());
+        assert!(block.ommers.is_empty());
+        assert_eq!(header.ommers_hash, block.ommers_hash());
+        assert_eq!(header.transactions_root, block.transactions_root());
+
+        assert_eq!(
+            block.hash(),
+            H256::from("0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6")
+        );
+        assert_eq!(block.to_rlp(), rlp);
+
+        let genesis = mainnet_genesis_block();
+        let config = ChainConfig::default();
+        assert_eq!(header.validate(&genesis.header, &config), Ok(()));
+        let mut header = header.clone();
+        header.extra_data = vec![0; 33];
+        assert_eq!(
+            BlockHeader::from_rlp(&header.to_rlp())
+                .unwrap()
+                .extra_data
+                .len(),
+            33
+        );
+        assert_eq!(
+            header.validate(&genesis.header, &config),
+            Err(BlockError::ExtraDataTooLong)
+        );
+    }
+
+    #[test]
+    fn test_block_with_transactions_and_ommers() {
+        // the header of mainnet block 1, the signed transaction of the EIP-155 example and the
+        // mainnet genesis header as an ommer
+        let header_rlp = from_hex(MAINNET_BLOCK_1_RLP).unwrap()[3..3 + 0x214].to_vec();
+        let transaction_rlp = from_hex(concat!(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764",
+            "00008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cb",
+            "e9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        ))
+        .unwrap();
+        let ommer = mainnet_genesis_block().header;
+        let ommer_rlp = ommer.to_rlp();
+        assert_eq!(
+            H256::from(keccak256(&ommer_rlp)),
+            H256::from("0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")
+        );
+
+        // [header, [transaction], [ommer]]
+        let mut rlp = from_hex("f9049e").unwrap();
+        rlp.extend(&header_rlp);
+        rlp.extend(from_hex("f86e").unwrap());
+        rlp.extend(&transaction_rlp);
+        rlp.extend(from_hex("f90217").unwrap());
+        rlp.extend(&ommer_rlp);
+
+        let block = Block::from_rlp(&rlp).unwrap();
+        assert_eq!(block.header.number, 1);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.transactions[0].nonce(), 9.into());
+        assert_eq!(block.transactions[0].to_rlp(), transaction_rlp);
+        assert_eq!(block.ommers, vec![ommer]);
+        assert_eq!(block.to_rlp(), rlp);
+        assert_eq!(
+            block.hash(),
+            H256::from("0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6")
+        );
+        assert_eq!(
+            block.ommers_hash(),
+            H256::from(keccak256(&rlp[3 + 0x214 + 0x70..]))
+        );
+    }
+
+    #[test]
+    fn test_base_fee() {
+        let config = ChainConfig {
+            london_block: Some(5),
+            ..ChainConfig::default()
+        };
+        let mut parent = BlockHeader {
+            number: 3,
+            gas_limit: 5_000_000.into(),
+            ..BlockHeader::default()
+        };
+        assert_eq!(BlockHeader::expected_base_fee(&parent, &config), None);
+        parent.number = 4;
+        assert_eq!(
+            BlockHeader::expected_base_fee(&parent, &config),
+            Some(INITIAL_BASE_FEE.into())
+        );
+
+        // the gas target of the fork block is the gas limit of the parent
+        let mut header = BlockHeader {
+            parent_hash: parent.hash(),
+            number: 5,
+            gas_limit: 10_000_000.into(),
+            timestamp: 15,
+            ..BlockHeader::default()
+        };
+        assert_eq!(
+            header.validate(&parent, &config),
+            Err(BlockError::InvalidBaseFee {
+                expected: Some(INITIAL_BASE_FEE.into()),
+                got: None
+            })
+        );
+        header.base_fee_per_gas = Some(INITIAL_BASE_FEE.into());
+        assert_eq!(header.validate(&parent, &config), Ok(()));
+        assert_eq!(
+            BlockHeader::from_rlp(&header.to_rlp()).unwrap(),
+            header.clone()
+        );
+
+        let mut base_fee = |gas_used: u64| {
+            header.gas_used = gas_used.into();
+            BlockHeader::expected_base_fee(&header, &config).unwrap()
+        };
+        assert_eq!(base_fee(5_000_000), 1_000_000_000.into());
+        assert_eq!(base_fee(10_000_000), 1_125_000_000.into());
+        assert_eq!(base_fee(0), 875_000_000.into());
+        assert_eq!(base_fee(7_500_000), 1_062_500_000.into());
+    }
+
+    fn signed_transfer(nonce: u64, to: Address, value: u64) -> Transaction {
+        let mut transaction = Transaction::Legacy(LegacyTransaction {
+            nonce: nonce.into(),
+            gas_price: 2.into(),
+            gas_limit: 21000.into(),
+            to: Some(to),
+            value: value.into(),
+            ..LegacyTransaction::default()
+        });
+        transaction.sign(
+            &SecretKey::from_slice(&[0x46; 32]).unwrap(),
+            Some(crate::lib::DEV_CHAIN_ID),
+        );
+        transaction
+    }
+
+    /// A valid child block of `parent` (whose post-state is `state`)
+    fn child_block(
+        parent: &BlockHeader,
+        state: &WorldState,
+        transactions: Vec<Transaction>,
+    ) -> Block {
+        let mut block = Block {
+            header: BlockHeader {
+                parent_hash: parent.hash(),
+                ommers_hash: keccak256(&EMPTY_LIST_RLP).into(),
+                difficulty: parent.difficulty,
+                number: parent.number + 1,
+                gas_limit: parent.gas_limit,
+                timestamp: parent.timestamp + 15,
+                ..BlockHeader::default()
+            },
+            transactions,
+            ommers: vec![],
+        };
+        block.header.transactions_root = block.transactions_root();
+        let mut state = state.clone();
+        let receipts = block.execute(&mut state, &ChainConfig::default()).unwrap();
+        block.header.gas_used = receipts
+            .last()
+            .map_or_else(U256::zero, |r| r.cumulative_gas_used);
+        block.header.receipts_root = ordered_trie_root(receipts.iter().map(Receipt::to_rlp));
+        block.header.state_root = state.state_root();
+        block
+    }
+
+    #[test]
+    fn test_block_rlp_roundtrip() {
+        let genesis = Block::genesis_block();
+        let mut block = child_block(&genesis.header, &WorldState::new(), vec![]);
+        let mut typed = Transaction::AccessList(crate::lib::AccessListTransaction {
+            chain_id: crate::lib::DEV_CHAIN_ID,
+            nonce: 1.into(),
+            gas_price: 2.into(),
+            gas_limit: 21000.into(),
+            to: Some(Address::random()),
+            value: 1.into(),
+            ..Default::default()
+        });
+        typed.sign(&SecretKey::from_slice(&[0x46; 32]).unwrap(), None);
+        block.transactions = vec![signed_transfer(0, Address::random(), 1), typed];
+        block.ommers = vec![genesis.header.clone()];
+
+        let decoded = Block::from_rlp(&block.to_rlp()).unwrap();
+        assert_eq!(decoded, block);
+        assert_eq!(decoded.header.hash(), block.header.hash());
+    }
+
+    #[test]
+    fn test_import_block() {
+        let sender = Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let mut genesis = Genesis::dev();
+        genesis.alloc.insert(
+            sender,
+            crate::lib::GenesisAccount {
+                balance: *ONE_ETHER,
+                ..Default::default()
+            },
+        );
+        let mut state = genesis.state();
+        let mut block_chain = BlockChain::from_genesis(&genesis);
+
+        let block = child_block(
+            &block_chain.latest_block().header,
+            &state,
+            vec![signed_transfer(0, recipient, 1000)],
+        );
+        let mut invalid_block = block.clone();
+        invalid_block.header.state_root = H256::zero();
+        assert_eq!(
+            block_chain.import_block(invalid_block, &mut state),
+            Err(BlockError::InvalidStateRoot {
+                expected: block.header.state_root,
+                got: H256::zero()
+            })
+        );
+        assert_eq!(state.state_root(), genesis.state().state_root());
+
+        block_chain.import_block(block.clone(), &mut state).unwrap();
+        assert_eq!(block_chain.latest_block_hash, block.header.hash());
+        assert_eq!(state.accounts[&recipient].balance, 1000.into());
+        assert_eq!(state.accounts[&sender].nonce, 1.into());
+        assert_eq!(
+            state.accounts[&block.header.beneficiary].balance,
+            *BLOCK_REWARD + U256::from(21000 * 2)
+        );
+        assert_eq!(block_chain.receipts[&block.header.hash()].len(), 1);
+        let (location, index) = block_chain
+            .transaction_location(&block.transactions[0].hash())
+            .unwrap();
+        assert_eq!((location.hash(), index), (block.hash(), 0));
+        let genesis_block = genesis.block();
+        let genesis_state = block_chain.state_at(&genesis_block.header).unwrap();
+        assert!(!genesis_state.accounts.contains_key(&recipient));
+        assert_eq!(
+            block_chain.state_at(&block.header).unwrap().state_root(),
+            state.state_root()
+        );
+        assert_eq!(block_chain.canonical_blocks().len(), 2);
+
+        // pruned states are recomputed from the blocks
+        block_chain.states.set_retention(StateRetention::Recent(0));
+        let recomputed = block_chain.state_at(&block.header).unwrap();
+        assert!(matches!(recomputed, Cow::Owned(_)));
+        assert_eq!(recomputed.state_root(), state.state_root());
+
+        // the same transaction again
+        let block = child_block(&block.header, &WorldState::new(), vec![]);
+        let mut replay = block.clone();
+        replay.transactions = vec![signed_transfer(0, recipient, 1000)];
+        replay.header.transactions_root = replay.transactions_root();
+        assert_eq!(
+            block_chain.import_block(replay, &mut state),
+            Err(BlockError::InvalidTransaction(
+                0,
+                TransactionError::NonceTooLow {
+                    expected: 1.into(),
+                    got: 0.into()
+                }
+            ))
+        );
+
+        // signed for another chain
+        let mut transaction = signed_transfer(1, recipient, 1000);
+        transaction.sign(&SecretKey::from_slice(&[0x46; 32]).unwrap(), Some(1));
+        let mut other_chain = block.clone();
+        other_chain.transactions = vec![transaction];
+        other_chain.header.transactions_root = other_chain.transactions_root();
+        assert_eq!(
+            block_chain.import_block(other_chain, &mut state),
+            Err(BlockError::InvalidTransaction(
+                0,
+                TransactionError::InvalidChainId(1)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_build_block() {
+        let sender = Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let mut genesis = Genesis::dev();
+        genesis.config.london_block = Some(1);
+        genesis.alloc.insert(
+            sender,
+            crate::lib::GenesisAccount {
+                balance: *ONE_ETHER,
+                ..Default::default()
+            },
+        );
+        let mut state = genesis.state();
+        let mut block_chain = BlockChain::from_genesis(&genesis);
+
+        let transfer = |nonce: u64| {
+            let mut transaction = Transaction::Legacy(LegacyTransaction {
+                nonce: nonce.into(),
+                gas_price: INITIAL_BASE_FEE.into(),
+                gas_limit: 21000.into(),
+                to: Some(recipient),
+                value: 1000.into(),
+                ..LegacyTransaction::default()
+            });
+            transaction.sign(
+                &SecretKey::from_slice(&[0x46; 32]).unwrap(),
+                Some(crate::lib::DEV_CHAIN_ID),
+            );
+            transaction
+        };
+        let (valid, gap) = (transfer(0), transfer(2));
+        let (block, rejected) = Block::build(
+            &block_chain.latest_block().header,
+            &state,
+            vec![valid.clone(), gap.clone(), transfer(1)],
+            Address::zero(),
+            0,
+            &block_chain.config,
+        );
+        assert_eq!(block.transactions, vec![valid, transfer(1)]);
+        assert_eq!(
+            rejected,
+            vec![(
+                gap,
+                TransactionError::NonceTooHigh {
+                    expected: 1.into(),
+                    got: 2.into()
+                }
+            )]
+        );
+        assert_eq!(block.header.timestamp, 1);
+        assert_eq!(block.header.base_fee_per_gas, Some(INITIAL_BASE_FEE.into()));
+        assert_eq!(block.header.gas_used, (2 * 21000).into());
+
+        block_chain.import_block(block, &mut state).unwrap();
+        assert_eq!(state.accounts[&recipient].balance, 2000.into());
+    }
+
     #[test]
     fn test_blockchain_total_difficulty() {
-        let mut block_chain = BlockChain::new();
-        assert_eq!(block_chain.total_difficulty(&block_chain.latest_block_hash), (2 << 17).into());
+        let mut block_chain = BlockChain::new(Block::genesis_block());
+        assert_eq!(
+            block_chain.total_difficulty(&block_chain.latest_block_hash),
+            (2 << 17).into()
+        );
         let mut new_block = Block::genesis_block();
         new_block.header.parent_hash = block_chain.latest_block_hash;
         block_chain.add_block(new_block);
-        assert_eq!(block_chain.total_difficulty(&block_chain.latest_block_hash), ((2 << 17) * 2).into());
+        assert_eq!(
+            block_chain.total_difficulty(&block_chain.latest_block_hash),
+            ((2 << 17) * 2).into()
+        );
     }
 
     #[test]
@@ -0,0 +1,144 @@
+//! Import and export of the chain as a stream of concatenated RLP-encoded blocks,
+//! the format used by geth's `export` and `import` commands.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use rlp::{DecoderError, Rlp};
+
+use crate::lib::{Block, BlockChain, BlockError, WorldState};
+
+/// Name of the block stream file in the data directory.
+pub const CHAIN_FILE: &str = "chain.rlp";
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    /// The block at the given position in the stream couldn't be decoded.
+    Decode(usize, DecoderError),
+    /// The block with the given number is invalid.
+    InvalidBlock(u64, BlockError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "{}", e),
+            ImportError::Decode(index, e) => {
+                write!(f, "could not decode block #{} in stream: {}", index, e)
+            }
+            ImportError::InvalidBlock(number, e) => write!(f, "invalid block {}: {}", number, e),
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+/// Writes the canonical chain, starting with the genesis block. Returns the number of blocks written.
+pub fn export_blocks<W: Write>(block_chain: &BlockChain, writer: &mut W) -> io::Result<usize> {
+    let blocks = block_chain.canonical_blocks();
+    for block in &blocks {
+        writer.write_all(&block.to_rlp())?;
+    }
+    writer.flush()?;
+    Ok(blocks.len())
+}
+
+/// Reads and decodes all blocks of the stream (without validating them).
+pub fn read_blocks<R: Read>(reader: &mut R) -> Result<Vec<Block>, ImportError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+
+    let mut blocks = vec![];
+    let mut offset = 0;
+    let mut index = 0;
+    while offset < data.len() {
+        let len = Rlp::new(&data[offset..])
+            .payload_info()
+            .map_err(|e| ImportError::Decode(index, e))?
+            .total();
+        if offset + len > data.len() {
+            return Err(ImportError::Decode(index, DecoderError::RlpIsTooShort));
+        }
+        let block = Block::from_rlp(&data[offset..offset + len])
+            .map_err(|e| ImportError::Decode(index, e))?;
+        offset += len;
+        index += 1;
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+/// Reads blocks from the stream and imports them (with full validation and execution) on top of the
+/// latest block, whose post-state `state` must be. Blocks that are already part of the chain, such as
+/// the genesis block, are skipped. Returns the number of imported blocks.
+pub fn import_blocks<R: Read>(
+    block_chain: &mut BlockChain,
+    state: &mut WorldState,
+    reader: &mut R,
+) -> Result<usize, ImportError> {
+    let mut imported = 0;
+    for block in read_blocks(reader)? {
+        if block_chain.blocks.contains_key(&block.header.hash()) {
+            continue;
+        }
+        let number = block.header.number;
+        block_chain
+            .import_block(block, state)
+            .map_err(|e| ImportError::InvalidBlock(number, e))?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::Genesis;
+
+    #[test]
+    fn test_export_import() {
+        let genesis = Genesis::dev();
+        let mut state = genesis.state();
+        let mut block_chain = BlockChain::from_genesis(&genesis);
+
+        let mut block = Block::exodus_block(&block_chain.latest_block().header);
+        block.transactions = vec![];
+        block.header.gas_used = 0.into();
+        block.header.transactions_root = block.transactions_root();
+        let mut post_state = state.clone();
+        block.execute(&mut post_state, &block_chain.config).unwrap();
+        block.header.receipts_root = *crate::lib::EMPTY_TRIE_ROOT;
+        block.header.state_root = post_state.state_root();
+        block_chain.import_block(block, &mut state).unwrap();
+
+        let mut exported = vec![];
+        assert_eq!(export_blocks(&block_chain, &mut exported).unwrap(), 2);
+
+        let mut new_state = genesis.state();
+        let mut new_block_chain = BlockChain::from_genesis(&genesis);
+        let imported = import_blocks(&mut new_block_chain, &mut new_state, &mut &exported[..]);
+        assert_eq!(imported.unwrap(), 1);
+        assert_eq!(
+            new_block_chain.latest_block_hash,
+            block_chain.latest_block_hash
+        );
+        assert_eq!(new_state.state_root(), state.state_root());
+
+        // truncated stream
+        let mut new_block_chain = BlockChain::from_genesis(&genesis);
+        let imported = import_blocks(
+            &mut new_block_chain,
+            &mut genesis.state(),
+            &mut &exported[..exported.len() - 1],
+        );
+        match imported {
+            Err(ImportError::Decode(1, _)) => {}
+            _ => panic!("expected decoding error"),
+        }
+    }
+}
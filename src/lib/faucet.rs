@@ -0,0 +1,407 @@
+//! The faucet handing out ether to players (`teth_faucet`). Payouts are regular transactions from a
+//! funded faucet account. Against abuse, there are cooldowns per address and per client IP, caps on the
+//! payouts per day, and optionally a challenge the requester has to sign with the key of the address
+//! (EIP-191), proving that they own it.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+use ethereum_types::{Address, H256, U256};
+use secp256k1::{Message, RecoverableSignature, RecoveryId, Secp256k1, SecretKey};
+use serde::Serialize;
+use tiny_keccak::keccak256;
+
+use crate::lib::{
+    from_hex, public_key_to_address, secret_key_to_address, to_hex, LegacyTransaction, Transaction,
+    Wei, FEES, ONE_ETHER,
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaucetConfig {
+    /// Amount of each payout.
+    pub amount: Wei,
+    /// Minimum time between two payouts to the same address.
+    pub address_cooldown: Duration,
+    /// Minimum time between two payouts to the same client IP.
+    pub ip_cooldown: Duration,
+    /// Maximum number of payouts within 24 hours.
+    pub daily_limit: usize,
+    /// Maximum number of payouts to the same client IP within 24 hours.
+    pub ip_daily_limit: usize,
+    /// Whether requests have to include the signed challenge of `Faucet::challenge`.
+    pub require_signature: bool,
+    /// How long a challenge can be used.
+    pub challenge_timeout: Duration,
+}
+
+impl Default for FaucetConfig {
+    fn default() -> Self {
+        Self {
+            amount: *ONE_ETHER,
+            address_cooldown: DAY,
+            ip_cooldown: Duration::from_secs(60),
+            daily_limit: 1000,
+            ip_daily_limit: 5,
+            require_signature: false,
+            challenge_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Reasons for refusing a payout.
+///
+/// Serialized as `{"reason": "addressCooldown", "details": {"wait": 3600}}` (seconds until the next
+/// payout is possible), like `TransactionError`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", content = "details", rename_all = "camelCase")]
+pub enum FaucetError {
+    /// The node has no faucet account.
+    Disabled,
+    AddressCooldown {
+        wait: u64,
+    },
+    IpCooldown {
+        wait: u64,
+    },
+    DailyLimitReached {
+        wait: u64,
+    },
+    IpDailyLimitReached {
+        wait: u64,
+    },
+    /// There's no (unexpired) challenge for the address.
+    ChallengeRequired,
+    /// The signature of the challenge is malformed or wasn't made by the key of the address.
+    InvalidSignature,
+}
+
+impl fmt::Display for FaucetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::FaucetError::*;
+        match self {
+            Disabled => write!(f, "faucet is disabled"),
+            AddressCooldown { wait } => {
+                write!(f, "address was funded recently, retry in {} seconds", wait)
+            }
+            IpCooldown { wait } => write!(f, "too many requests, retry in {} seconds", wait),
+            DailyLimitReached { wait } => {
+                write!(f, "daily faucet limit reached, retry in {} seconds", wait)
+            }
+            IpDailyLimitReached { wait } => {
+                write!(
+                    f,
+                    "daily limit of this client reached, retry in {} seconds",
+                    wait
+                )
+            }
+            ChallengeRequired => write!(f, "request and sign a challenge first"),
+            InvalidSignature => write!(f, "invalid signature of the challenge"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Faucet {
+    pub config: FaucetConfig,
+    /// The account the payouts are sent from.
+    pub address: Address,
+    key: SecretKey,
+    /// Time of the last payout to each address (within `address_cooldown`).
+    last_payouts: HashMap<Address, Instant>,
+    /// Time and client IP (if known) of the payouts within the last 24 hours, oldest first.
+    recent_payouts: VecDeque<(Instant, Option<IpAddr>)>,
+    /// The open challenge of each address and when it was issued.
+    challenges: HashMap<Address, (String, Instant)>,
+    issued_challenges: u64,
+}
+
+impl Faucet {
+    pub fn new(key: SecretKey, config: FaucetConfig) -> Self {
+        Self {
+            config,
+            address: secret_key_to_address(&key),
+            key,
+            last_payouts: HashMap::new(),
+            recent_payouts: VecDeque::new(),
+            challenges: HashMap::new(),
+            issued_challenges: 0,
+        }
+    }
+
+    /// Issues a new challenge for the address, a message to be signed with its key (EIP-191) and
+    /// passed to `authorize`. Replaces an earlier challenge of the address.
+    pub fn challenge(&mut self, address: Address, now: Instant) -> String {
+        self.issued_challenges += 1;
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut seed = address.to_vec();
+        seed.extend_from_slice(&self.issued_challenges.to_be_bytes());
+        seed.extend_from_slice(&time.as_nanos().to_be_bytes());
+        let message = format!(
+            "teth faucet challenge for {}: {}",
+            to_hex(&address),
+            to_hex(&keccak256(&seed)[..16])
+        );
+        self.challenges.insert(address, (message.clone(), now));
+        message
+    }
+
+    /// Checks whether the address may get a payout now, requested by the client with the given IP
+    /// (if known). With `require_signature`, `signature` must be the signature of the address's
+    /// challenge, which is used up in the process.
+    pub fn authorize(
+        &mut self,
+        address: Address,
+        client_ip: Option<IpAddr>,
+        signature: Option<&[u8]>,
+        now: Instant,
+    ) -> Result<(), FaucetError> {
+        self.expire(now);
+        let wait = |since: Instant, cooldown: Duration| (cooldown - (now - since)).as_secs() + 1;
+
+        if let Some(&time) = self.last_payouts.get(&address) {
+            return Err(FaucetError::AddressCooldown {
+                wait: wait(time, self.config.address_cooldown),
+            });
+        }
+        if let Some(ip) = client_ip {
+            let payouts: Vec<Instant> = self
+                .recent_payouts
+                .iter()
+                .filter(|(_, payout_ip)| *payout_ip == Some(ip))
+                .map(|(time, _)| *time)
+                .collect();
+            if let Some(&last) = payouts.last() {
+                if now - last < self.config.ip_cooldown {
+                    return Err(FaucetError::IpCooldown {
+                        wait: wait(last, self.config.ip_cooldown),
+                    });
+                }
+            }
+            if payouts.len() >= self.config.ip_daily_limit {
+                return Err(FaucetError::IpDailyLimitReached {
+                    wait: wait(payouts[0], DAY),
+                });
+            }
+        }
+        if self.recent_payouts.len() >= self.config.daily_limit {
+            return Err(FaucetError::DailyLimitReached {
+                wait: wait(self.recent_payouts[0].0, DAY),
+            });
+        }
+
+        if self.config.require_signature {
+            let (challenge, _) = self
+                .challenges
+                .get(&address)
+                .ok_or(FaucetError::ChallengeRequired)?;
+            let signer = signature.and_then(|signature| recover_signer(challenge, signature));
+            if signer != Some(address) {
+                return Err(FaucetError::InvalidSignature);
+            }
+            self.challenges.remove(&address);
+        }
+        Ok(())
+    }
+
+    /// Records a payout for the limits checked by `authorize`.
+    pub fn record(&mut self, address: Address, client_ip: Option<IpAddr>, now: Instant) {
+        self.last_payouts.insert(address, now);
+        self.recent_payouts.push_back((now, client_ip));
+    }
+
+    /// The signed transaction paying out `amount` to the address.
+    pub fn payout(
+        &self,
+        address: Address,
+        nonce: U256,
+        gas_price: Wei,
+        chain_id: Option<u64>,
+    ) -> Transaction {
+        let mut transaction = Transaction::Legacy(LegacyTransaction {
+            nonce,
+            gas_price,
+            gas_limit: FEES.transaction.into(),
+            to: Some(address),
+            value: self.config.amount,
+            ..LegacyTransaction::default()
+        });
+        transaction.sign(&self.key, chain_id);
+        transaction
+    }
+
+    /// Forgets payouts and challenges that no longer matter.
+    fn expire(&mut self, now: Instant) {
+        let config = &self.config;
+        self.last_payouts
+            .retain(|_, time| now - *time < config.address_cooldown);
+        while let Some(&(time, _)) = self.recent_payouts.front() {
+            if now - time < DAY {
+                break;
+            }
+            self.recent_payouts.pop_front();
+        }
+        self.challenges
+            .retain(|_, (_, issued)| now - *issued < config.challenge_timeout);
+    }
+}
+
+/// The hash of a message signed according to EIP-191 (version 0x45, as by `personal_sign`):
+/// KEC("\x19Ethereum Signed Message:\n" · len(message) · message)
+pub fn personal_message_hash(message: &[u8]) -> H256 {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    data.extend_from_slice(message);
+    keccak256(&data).into()
+}
+
+/// Signs the message according to EIP-191. Returns the 65-byte signature r · s · v, with v = 27 or 28.
+pub fn sign_message(key: &SecretKey, message: &str) -> Vec<u8> {
+    let hash = personal_message_hash(message.as_bytes());
+    let message = Message::from_slice(&hash).expect("hash is 32 bytes");
+    let (recovery_id, compact) = Secp256k1::signing_only()
+        .sign_recoverable(&message, key)
+        .serialize_compact();
+    let mut signature = compact.to_vec();
+    signature.push(27 + recovery_id.to_i32() as u8);
+    signature
+}
+
+/// The address whose key made the signature of the message (see `sign_message`). `None` if the
+/// signature is invalid. v may be 0/1 or 27/28.
+pub fn recover_signer(message: &str, signature: &[u8]) -> Option<Address> {
+    if signature.len() != 65 {
+        return None;
+    }
+    let v = signature[64];
+    let recovery_id = RecoveryId::from_i32(i32::from(if v >= 27 { v - 27 } else { v })).ok()?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id).ok()?;
+    let hash = personal_message_hash(message.as_bytes());
+    let message = Message::from_slice(&hash).ok()?;
+    let public_key = Secp256k1::verification_only()
+        .recover(&message, &signature)
+        .ok()?;
+    Some(public_key_to_address(&public_key))
+}
+
+/// Parses a hex-encoded private key (with or without `0x` prefix), e.g. of `--faucet-key-file`.
+pub fn parse_secret_key(s: &str) -> Result<SecretKey, String> {
+    let bytes = from_hex(s.trim())?;
+    SecretKey::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{DEV_ACCOUNT_KEYS, DEV_CHAIN_ID};
+
+    #[test]
+    fn test_sign_message() {
+        let key = parse_secret_key(DEV_ACCOUNT_KEYS[0]).unwrap();
+        let address = secret_key_to_address(&key);
+        let signature = sign_message(&key, "hello world");
+        assert_eq!(signature.len(), 65);
+        assert_eq!(recover_signer("hello world", &signature), Some(address));
+        assert_ne!(recover_signer("hello world!", &signature), Some(address));
+        assert_eq!(recover_signer("hello world", &signature[..64]), None);
+        // `hashMessage("hello world")` of ethers.js
+        assert_eq!(
+            personal_message_hash(b"hello world"),
+            H256::from("0xd9eba16ed0ecae432b71fe008c98cc872bb4cc214d3220a36f365326cf807d68")
+        );
+    }
+
+    #[test]
+    fn test_faucet_limits() {
+        let key = parse_secret_key(DEV_ACCOUNT_KEYS[1]).unwrap();
+        let config = FaucetConfig {
+            ip_daily_limit: 2,
+            daily_limit: 3,
+            ..FaucetConfig::default()
+        };
+        let mut faucet = Faucet::new(key, config);
+        let ip = Some("10.0.0.1".parse().unwrap());
+        let address = |n: u64| Address::from(n);
+        let start = Instant::now();
+        let minutes = |n: u64| start + Duration::from_secs(n * 60);
+
+        assert_eq!(faucet.authorize(address(1), ip, None, start), Ok(()));
+        faucet.record(address(1), ip, start);
+        assert_eq!(
+            faucet.authorize(address(1), None, None, minutes(2)),
+            Err(FaucetError::AddressCooldown {
+                wait: 24 * 60 * 60 - 120 + 1
+            })
+        );
+        assert_eq!(
+            faucet.authorize(address(2), ip, None, start + Duration::from_secs(30)),
+            Err(FaucetError::IpCooldown { wait: 31 })
+        );
+        assert_eq!(faucet.authorize(address(2), ip, None, minutes(2)), Ok(()));
+        faucet.record(address(2), ip, minutes(2));
+        assert!(matches!(
+            faucet.authorize(address(3), ip, None, minutes(4)),
+            Err(FaucetError::IpDailyLimitReached { .. })
+        ));
+        faucet.record(address(3), None, minutes(4));
+        assert!(matches!(
+            faucet.authorize(address(4), None, None, minutes(6)),
+            Err(FaucetError::DailyLimitReached { .. })
+        ));
+        // a day after the first payout
+        assert_eq!(
+            faucet.authorize(address(1), None, None, minutes(24 * 60)),
+            Ok(())
+        );
+
+        let payout = faucet.payout(address(1), 0.into(), 1.into(), Some(DEV_CHAIN_ID));
+        assert_eq!(payout.sender(), Some(faucet.address));
+        assert_eq!(payout.value(), *ONE_ETHER);
+    }
+
+    #[test]
+    fn test_faucet_challenge() {
+        let key = parse_secret_key(DEV_ACCOUNT_KEYS[1]).unwrap();
+        let config = FaucetConfig {
+            require_signature: true,
+            ..FaucetConfig::default()
+        };
+        let mut faucet = Faucet::new(key, config);
+        let player_key = parse_secret_key(DEV_ACCOUNT_KEYS[0]).unwrap();
+        let player = secret_key_to_address(&player_key);
+        let now = Instant::now();
+
+        assert_eq!(
+            faucet.authorize(player, None, None, now),
+            Err(FaucetError::ChallengeRequired)
+        );
+        let challenge = faucet.challenge(player, now);
+        let wrong_signature = sign_message(&key, &challenge);
+        assert_eq!(
+            faucet.authorize(player, None, Some(&wrong_signature), now),
+            Err(FaucetError::InvalidSignature)
+        );
+        let signature = sign_message(&player_key, &challenge);
+        assert_eq!(
+            faucet.authorize(player, None, Some(&signature), now),
+            Ok(())
+        );
+        // challenges can only be used once
+        assert_eq!(
+            faucet.authorize(player, None, Some(&signature), now),
+            Err(FaucetError::ChallengeRequired)
+        );
+
+        let challenge = faucet.challenge(player, now);
+        let signature = sign_message(&player_key, &challenge);
+        let later = now + Duration::from_secs(10 * 60);
+        assert_eq!(
+            faucet.authorize(player, None, Some(&signature), later),
+            Err(FaucetError::ChallengeRequired)
+        );
+    }
+}
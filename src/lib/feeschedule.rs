@@ -12,6 +12,10 @@ pub struct FeeSchedule {
     pub tx_data_non_zero: u64,
     /// Paid for every transaction.
     pub transaction: u64,
+    /// Paid for every address in the access list of a transaction (EIP-2930).
+    pub access_list_address: u64,
+    /// Paid for every storage key in the access list of a transaction (EIP-2930).
+    pub access_list_storage_key: u64,
 }
 
 impl FeeSchedule {
@@ -27,6 +31,8 @@ impl Default for FeeSchedule {
             tx_data_zero: 4,
             tx_data_non_zero: 68,
             transaction: 21000,
+            access_list_address: 2400,
+            access_list_storage_key: 1900,
         }
     }
 }
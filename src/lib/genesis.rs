@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use ethereum_types::{Address, H256, U256};
+use secp256k1::SecretKey;
+use serde::de::{Deserializer, Error as DeError};
+use serde::Deserialize;
+
+use crate::lib::{
+    from_hex, parse_u256, secret_key_to_address, AccountState, Block, WorldState, INITIAL_BASE_FEE,
+    ONE_ETHER,
+};
+
+/// Chain ID used by the built-in dev chain (and by `teth-web`).
+pub const DEV_CHAIN_ID: u64 = 85;
+
+/// Private keys of prefunded accounts of the built-in dev chain, which the node signs transactions for
+/// (`eth_sendTransaction`). These are the first two well-known Hardhat test keys - never use them
+/// for anything of value!
+pub const DEV_ACCOUNT_KEYS: [&str; 2] = [
+    "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+    "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+];
+
+/// The accounts of `DEV_ACCOUNT_KEYS` with their keys.
+pub fn dev_accounts() -> HashMap<Address, SecretKey> {
+    DEV_ACCOUNT_KEYS
+        .iter()
+        .map(|key| {
+            let key = SecretKey::from_slice(&from_hex(key).unwrap()).unwrap();
+            (secret_key_to_address(&key), key)
+        })
+        .collect()
+}
+
+/// Chain configuration, i.e. the `config` object of a geth-style genesis file.
+///
+/// Fork blocks are the numbers of the first block the respective rules apply to;
+/// `None` means the fork is not scheduled, which is also the case for forks missing in the file.
+/// The EIP-1559 parameters can be changed per chain as well.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    #[serde(default = "default_chain_id")]
+    pub chain_id: u64,
+    pub homestead_block: Option<u64>,
+    pub eip150_block: Option<u64>,
+    pub eip155_block: Option<u64>,
+    pub eip158_block: Option<u64>,
+    pub byzantium_block: Option<u64>,
+    pub constantinople_block: Option<u64>,
+    pub petersburg_block: Option<u64>,
+    pub istanbul_block: Option<u64>,
+    pub berlin_block: Option<u64>,
+    pub london_block: Option<u64>,
+    /// ρ - the gas target of a block is its gas limit divided by this (EIP-1559).
+    #[serde(default = "default_elasticity_multiplier")]
+    pub elasticity_multiplier: u64,
+    /// ξ - the base fee changes by at most 1/ξ from one block to the next (EIP-1559).
+    #[serde(default = "default_base_fee_max_change_denominator")]
+    pub base_fee_max_change_denominator: u64,
+}
+
+fn default_chain_id() -> u64 {
+    DEV_CHAIN_ID
+}
+
+fn default_elasticity_multiplier() -> u64 {
+    2
+}
+
+fn default_base_fee_max_change_denominator() -> u64 {
+    8
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: default_chain_id(),
+            homestead_block: Some(0),
+            eip150_block: Some(0),
+            eip155_block: Some(0),
+            eip158_block: Some(0),
+            byzantium_block: Some(0),
+            constantinople_block: Some(0),
+            petersburg_block: Some(0),
+            istanbul_block: Some(0),
+            berlin_block: Some(0),
+            london_block: None,
+            elasticity_multiplier: default_elasticity_multiplier(),
+            base_fee_max_change_denominator: default_base_fee_max_change_denominator(),
+        }
+    }
+}
+
+impl ChainConfig {
+    /// Whether a fork scheduled at `fork_block` is active at block `number`.
+    pub fn is_active(fork_block: Option<u64>, number: u64) -> bool {
+        fork_block.is_some_and(|fork_block| number >= fork_block)
+    }
+
+    /// The chain ID transactions in block `number` may be signed for, `None` before EIP-155.
+    pub fn eip155_chain_id(&self, number: u64) -> Option<u64> {
+        if Self::is_active(self.eip155_block, number) {
+            Some(self.chain_id)
+        } else {
+            None
+        }
+    }
+}
+
+/// An account in the genesis allocation (premine).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GenesisAccount {
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub balance: U256,
+    #[serde(default, deserialize_with = "deserialize_u256")]
+    pub nonce: U256,
+    #[serde(default, deserialize_with = "deserialize_bytes")]
+    pub code: Vec<u8>,
+    #[serde(default, deserialize_with = "deserialize_storage")]
+    pub storage: BTreeMap<H256, U256>,
+}
+
+/// Genesis specification in the JSON format used by geth (`geth init <genesis.json>`).
+///
+/// Everything that's not specified by the Yellow Paper's genesis block (Appendix I) - the premine,
+/// _time_, difficulty etc. - comes from here, so that every node using the same file ends up with
+/// the same genesis block hash.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Genesis {
+    #[serde(default)]
+    pub config: ChainConfig,
+    #[serde(default, deserialize_with = "deserialize_u64")]
+    pub nonce: u64,
+    #[serde(default, deserialize_with = "deserialize_u64")]
+    pub timestamp: u64,
+    #[serde(default, deserialize_with = "deserialize_bytes")]
+    pub extra_data: Vec<u8>,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub gas_limit: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub difficulty: U256,
+    #[serde(default)]
+    pub mix_hash: H256,
+    #[serde(default)]
+    pub coinbase: Address,
+    #[serde(default)]
+    pub parent_hash: H256,
+    #[serde(default, deserialize_with = "deserialize_alloc")]
+    pub alloc: BTreeMap<Address, GenesisAccount>,
+    /// Base fee of the genesis block if London is active from the start.
+    /// Defaults to `INITIAL_BASE_FEE`.
+    #[serde(default, deserialize_with = "deserialize_optional_u256")]
+    pub base_fee_per_gas: Option<U256>,
+}
+
+impl Genesis {
+    /// Reads a geth-style genesis file.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let genesis: Self = serde_json::from_reader(File::open(path)?)?;
+        if genesis.extra_data.len() > 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "extraData must be 32 bytes or fewer",
+            ));
+        }
+        if genesis.config.elasticity_multiplier == 0
+            || genesis.config.base_fee_max_change_denominator == 0
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "elasticityMultiplier and baseFeeMaxChangeDenominator must not be zero",
+            ));
+        }
+        Ok(genesis)
+    }
+
+    /// The built-in development chain with a small premine, including the `dev_accounts`.
+    pub fn dev() -> Self {
+        let mut alloc = BTreeMap::new();
+        let accounts = [
+            ("0xe94C35a6a32c759865dc49807f0ad3056848C8BF", 42u32),
+            ("0x96F1e2BdcB7645773D3DE58BcCB6223c44fA7D29", 420),
+            ("0x000000000000000000000000000000000000dEaD", 4200),
+        ];
+        for (address, ether) in accounts.iter() {
+            alloc.insert(
+                Address::from(*address),
+                GenesisAccount {
+                    balance: *ONE_ETHER * *ether,
+                    ..GenesisAccount::default()
+                },
+            );
+        }
+        for address in dev_accounts().keys() {
+            alloc.insert(
+                *address,
+                GenesisAccount {
+                    balance: *ONE_ETHER * 100u32,
+                    ..GenesisAccount::default()
+                },
+            );
+        }
+
+        Self {
+            config: ChainConfig::default(),
+            nonce: 42,
+            timestamp: 0,
+            extra_data: vec![],
+            gas_limit: 3_141_592.into(),
+            difficulty: (2 << 17).into(),
+            mix_hash: H256::zero(),
+            coinbase: Address::zero(),
+            parent_hash: H256::zero(),
+            alloc,
+            base_fee_per_gas: None,
+        }
+    }
+
+    /// The initial world state σ<sub>0</sub> resulting from the premine.
+    pub fn state(&self) -> WorldState {
+        let mut state = WorldState::new();
+        for (address, account) in &self.alloc {
+            state.accounts.insert(
+                *address,
+                AccountState {
+                    nonce: account.nonce,
+                    ..AccountState::new(account.balance)
+                },
+            );
+            if !account.code.is_empty() {
+                state.set_code(*address, account.code.clone());
+            }
+            for (key, value) in &account.storage {
+                state.set_storage(*address, *key, *value);
+            }
+        }
+        state
+    }
+
+    /// The genesis block, with the state root of `self.state()`.
+    pub fn block(&self) -> Block {
+        let mut block = Block::genesis_block();
+        let header = &mut block.header;
+        header.parent_hash = self.parent_hash;
+        header.beneficiary = self.coinbase;
+        header.state_root = self.state().state_root();
+        header.difficulty = self.difficulty;
+        header.gas_limit = self.gas_limit;
+        header.timestamp = self.timestamp;
+        header.extra_data = self.extra_data.clone();
+        header.mix_hash = self.mix_hash;
+        header.nonce = self.nonce.into();
+        if ChainConfig::is_active(self.config.london_block, 0) {
+            header.base_fee_per_gas = Some(
+                self.base_fee_per_gas
+                    .unwrap_or_else(|| INITIAL_BASE_FEE.into()),
+            );
+        }
+        block
+    }
+}
+
+/// Numbers in genesis files are given as JSON numbers, hex strings or decimal strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Quantity {
+    Number(u64),
+    String(String),
+}
+
+impl Quantity {
+    fn into_u256(self) -> Result<U256, String> {
+        match self {
+            Quantity::Number(n) => Ok(n.into()),
+            Quantity::String(s) => parse_u256(&s),
+        }
+    }
+}
+
+fn deserialize_u256<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+    Quantity::deserialize(deserializer)?
+        .into_u256()
+        .map_err(D::Error::custom)
+}
+
+fn deserialize_optional_u256<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<U256>, D::Error> {
+    deserialize_u256(deserializer).map(Some)
+}
+
+fn deserialize_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = deserialize_u256(deserializer)?;
+    if value > U256::from(u64::MAX) {
+        return Err(D::Error::custom("value does not fit into 64 bits"));
+    }
+    Ok(value.as_u64())
+}
+
+fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    from_hex(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn deserialize_storage<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<H256, U256>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let key = parse_u256(&key).map_err(D::Error::custom)?;
+            let value = parse_u256(&value).map_err(D::Error::custom)?;
+            Ok((key.into(), value))
+        })
+        .collect()
+}
+
+/// Allocation keys may or may not be `0x`-prefixed.
+fn deserialize_alloc<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Address, GenesisAccount>, D::Error> {
+    BTreeMap::<String, GenesisAccount>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(address, account)| {
+            let bytes = from_hex(&address).map_err(D::Error::custom)?;
+            if bytes.len() != 20 {
+                return Err(D::Error::custom(format!("invalid address: {}", address)));
+            }
+            let mut parsed = Address::zero();
+            parsed.copy_from_slice(&bytes);
+            Ok((parsed, account))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H64;
+
+    const GENESIS_JSON: &str = r#"{
+        "config": {
+            "chainId": 1337,
+            "homesteadBlock": 0,
+            "eip155Block": 0,
+            "londonBlock": 10
+        },
+        "nonce": "0x0000000000000042",
+        "timestamp": "0x5c51a607",
+        "extraData": "0x7465746820",
+        "gasLimit": "0x47b760",
+        "difficulty": "0x20000",
+        "alloc": {
+            "e94c35a6a32c759865dc49807f0ad3056848c8bf": { "balance": "42000000000000000000" },
+            "0x0000000000000000000000000000000000000042": {
+                "balance": "0x1",
+                "nonce": "0x2",
+                "code": "0x6000",
+                "storage": { "0x01": "0x2a" }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_genesis() {
+        let genesis: Genesis = serde_json::from_str(GENESIS_JSON).unwrap();
+        assert_eq!(genesis.config.chain_id, 1337);
+        assert_eq!(genesis.config.eip155_block, Some(0));
+        assert_eq!(genesis.config.london_block, Some(10));
+        assert_eq!(genesis.config.byzantium_block, None);
+        assert_eq!(genesis.config.eip155_chain_id(0), Some(1337));
+        assert_eq!(genesis.config.elasticity_multiplier, 2);
+        assert_eq!(genesis.base_fee_per_gas, None);
+        assert_eq!(genesis.nonce, 0x42);
+        assert_eq!(genesis.timestamp, 0x5c51_a607);
+        assert_eq!(genesis.extra_data, b"teth ".to_vec());
+        assert_eq!(genesis.gas_limit, 0x47_b760.into());
+        assert_eq!(genesis.alloc.len(), 2);
+
+        let contract = &genesis.alloc[&Address::from("0x0000000000000000000000000000000000000042")];
+        assert_eq!(contract.nonce, 2.into());
+        assert_eq!(contract.code, vec![0x60, 0x00]);
+        assert_eq!(contract.storage[&H256::from(U256::from(1))], 0x2a.into());
+    }
+
+    #[test]
+    fn test_genesis_state() {
+        let genesis: Genesis = serde_json::from_str(GENESIS_JSON).unwrap();
+        let state = genesis.state();
+        let contract = Address::from("0x0000000000000000000000000000000000000042");
+        assert_eq!(state.accounts[&contract].nonce, 2.into());
+        assert_eq!(state.code_at(&contract), vec![0x60, 0x00]);
+        assert_eq!(
+            state.storage_at(&contract, &H256::from(U256::from(1))),
+            0x2a.into()
+        );
+        assert_eq!(
+            state.accounts[&contract].storage_root,
+            state.storage_root(&contract)
+        );
+    }
+
+    #[test]
+    fn test_dev_accounts() {
+        let accounts = dev_accounts();
+        assert!(accounts.contains_key(&Address::from("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266")));
+        assert!(accounts.contains_key(&Address::from("0x70997970c51812dc3a010c7d01b50e0d17dc79c8")));
+        let state = Genesis::dev().state();
+        for address in accounts.keys() {
+            assert_eq!(state.accounts[address].balance, *ONE_ETHER * 100u32);
+        }
+    }
+
+    #[test]
+    fn test_genesis_block() {
+        let genesis: Genesis = serde_json::from_str(GENESIS_JSON).unwrap();
+        let block = genesis.block();
+        assert_eq!(block.header.state_root, genesis.state().state_root());
+        assert_eq!(block.header.timestamp, 0x5c51_a607);
+        assert_eq!(block.header.nonce, H64::from(0x42));
+        assert_eq!(block.header.extra_data, b"teth ".to_vec());
+        assert_eq!(block.header.base_fee_per_gas, None);
+
+        let mut london = genesis.clone();
+        london.config.london_block = Some(0);
+        assert_eq!(
+            london.block().header.base_fee_per_gas,
+            Some(INITIAL_BASE_FEE.into())
+        );
+    }
+
+    /// `customg` of go-ethereum's `TestSetupGenesis` (core/genesis_test.go), with the gas limit and
+    /// difficulty geth's `Genesis.ToBlock` fills in when they're not given.
+    const GETH_TEST_GENESIS_JSON: &str = r#"{
+        "config": { "chainId": 1, "homesteadBlock": 3 },
+        "gasLimit": "0x47e7c4",
+        "difficulty": "0x20000",
+        "alloc": {
+            "0x0100000000000000000000000000000000000000": {
+                "balance": "0x1",
+                "storage": {
+                    "0x0100000000000000000000000000000000000000000000000000000000000000":
+                        "0x0100000000000000000000000000000000000000000000000000000000000000"
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_geth_genesis_block() {
+        let genesis: Genesis = serde_json::from_str(GETH_TEST_GENESIS_JSON).unwrap();
+        let block = genesis.block();
+        // `customghash` of the geth test, which covers the state root
+        assert_eq!(
+            block.header.hash(),
+            H256::from("0x89c99d90b79719238d2645c7642f2c9295246e80775b38cfd162b696817fbd50")
+        );
+        assert_eq!(
+            block.header.state_root,
+            H256::from("0x8257aee1cdaa2c42ae5dfad636710ee73437466b7504770feb19d63e2be3c913")
+        );
+        assert_eq!(block.header.gas_limit, 4_712_388.into());
+        assert_eq!(block.header.difficulty, 131_072.into());
+    }
+}
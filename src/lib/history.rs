@@ -0,0 +1,102 @@
+//! History of the accounts for the block explorer (`teth_getAccountTransactions`,
+//! `teth_getAccountBalanceHistory`), recorded as blocks are imported.
+
+use std::collections::HashMap;
+
+use ethereum_types::{Address, H256};
+
+use crate::lib::{AccountChanges, Wei};
+
+#[derive(Debug, Clone, Default)]
+pub struct AccountHistory {
+    /// Hashes of the transactions each account sent or was the recipient of, oldest first.
+    transactions: HashMap<Address, Vec<H256>>,
+    /// Number of each block that changed the balance of an account and the balance after it,
+    /// oldest first.
+    balances: HashMap<Address, Vec<(u64, Wei)>>,
+}
+
+impl AccountHistory {
+    /// Adds the transactions to the history of the accounts involved and records the balances that
+    /// changed. Accounts that were deleted are recorded with a zero balance.
+    pub fn record(&mut self, changes: &AccountChanges) {
+        for (hash, accounts) in &changes.transactions {
+            for address in accounts {
+                self.transactions.entry(*address).or_default().push(*hash);
+            }
+        }
+
+        for change in &changes.balances {
+            let balance = change.balance.unwrap_or_default();
+            let balances = self.balances.entry(change.address).or_default();
+            if balances.last().map(|(_, balance)| *balance) != Some(balance) {
+                balances.push((changes.number, balance));
+            }
+        }
+    }
+
+    /// Hashes of the transactions the account sent or received, oldest first.
+    pub fn transactions(&self, address: &Address) -> &[H256] {
+        self.transactions
+            .get(address)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// The block numbers at which the balance of the account changed and the balance after each.
+    pub fn balances(&self, address: &Address) -> &[(u64, Wei)] {
+        self.balances.get(address).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{block_with_transfers, dev_accounts, BalanceUpdate, Transaction};
+
+    #[test]
+    fn test_account_history() {
+        let (sender, key) = dev_accounts().into_iter().next().unwrap();
+        let recipient = Address::from(0x1234);
+        let update =
+            |address: Address, previous: Option<u64>, balance: Option<u64>| BalanceUpdate {
+                address,
+                previous: previous.map(Wei::from),
+                balance: balance.map(Wei::from),
+            };
+        let mut history = AccountHistory::default();
+        history.record(&AccountChanges {
+            number: 0,
+            transactions: vec![],
+            balances: vec![update(sender, None, Some(1000))],
+        });
+
+        let block = block_with_transfers(1, &key, &[recipient, sender]);
+        let mut changes = AccountChanges::of_block(&block);
+        changes.balances = vec![
+            update(sender, Some(1000), Some(900)),
+            update(recipient, None, Some(100)),
+        ];
+        history.record(&changes);
+
+        let hashes: Vec<H256> = block.transactions.iter().map(Transaction::hash).collect();
+        assert_eq!(history.transactions(&sender), &hashes[..]);
+        assert_eq!(history.transactions(&recipient), &hashes[..1]);
+        assert!(history.transactions(&Address::zero()).is_empty());
+
+        // deleted accounts get a zero balance
+        history.record(&AccountChanges {
+            number: 2,
+            transactions: vec![],
+            balances: vec![update(recipient, Some(100), None)],
+        });
+        assert_eq!(
+            history.balances(&sender),
+            &[(0, Wei::from(1000)), (1, Wei::from(900))]
+        );
+        assert_eq!(
+            history.balances(&recipient),
+            &[(1, Wei::from(100)), (2, Wei::zero())]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Ranking of the accounts by balance for the game leaderboard (`teth_topAccounts`), kept up to date
+//! as blocks are imported instead of sorting all accounts for each request.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use ethereum_types::Address;
+
+use crate::lib::{AccountChanges, Wei};
+
+/// Statistics of an account beyond its state.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AccountStats {
+    /// Number of transactions the account sent or was the recipient of.
+    pub transaction_count: u64,
+    /// Number of the block in which the account first appeared (in the state or in a transaction).
+    pub first_seen_block: u64,
+}
+
+/// An account of a leaderboard page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedAccount {
+    /// Position in the ranking, starting with 1.
+    pub rank: usize,
+    pub address: Address,
+    pub balance: Wei,
+    pub stats: AccountStats,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Leaderboard {
+    /// All accounts of the state, descending by balance. Accounts with the same balance are ordered by
+    /// address, so that pages don't overlap or skip accounts.
+    ranking: BTreeSet<(Reverse<Wei>, Address)>,
+    /// The balance of each account in `ranking`.
+    balances: HashMap<Address, Wei>,
+    stats: HashMap<Address, AccountStats>,
+}
+
+impl Leaderboard {
+    /// Adds the transactions to the stats of the accounts involved and moves the accounts whose
+    /// balance changed, adding new and dropping deleted ones.
+    pub fn record(&mut self, changes: &AccountChanges) {
+        let first_seen = AccountStats {
+            transaction_count: 0,
+            first_seen_block: changes.number,
+        };
+        for (_, accounts) in &changes.transactions {
+            for address in accounts {
+                let stats = self.stats.entry(*address).or_insert(first_seen);
+                stats.transaction_count += 1;
+            }
+        }
+
+        for change in &changes.balances {
+            if let Some(previous) = change.previous {
+                self.ranking.remove(&(Reverse(previous), change.address));
+            }
+            if let Some(balance) = change.balance {
+                self.ranking.insert((Reverse(balance), change.address));
+                self.balances.insert(change.address, balance);
+                self.stats.entry(change.address).or_insert(first_seen);
+            } else {
+                self.balances.remove(&change.address);
+            }
+        }
+    }
+
+    /// The balance of each ranked account, i.e. of all accounts of the last recorded state.
+    pub fn balances(&self) -> &HashMap<Address, Wei> {
+        &self.balances
+    }
+
+    /// Number of ranked accounts, not counting the excluded ones.
+    pub fn len(&self, excluded: &HashSet<Address>) -> usize {
+        let excluded = excluded
+            .iter()
+            .filter(|address| self.balances.contains_key(address))
+            .count();
+        self.balances.len() - excluded
+    }
+
+    /// Up to `limit` accounts starting at position `offset` (0-based) of the ranking without the
+    /// excluded accounts. Empty if `offset` is beyond the end.
+    pub fn page(
+        &self,
+        offset: usize,
+        limit: usize,
+        excluded: &HashSet<Address>,
+    ) -> Vec<RankedAccount> {
+        self.ranking
+            .iter()
+            .filter(|(_, address)| !excluded.contains(address))
+            .enumerate()
+            .skip(offset)
+            .take(limit)
+            .map(|(index, (Reverse(balance), address))| RankedAccount {
+                rank: index + 1,
+                address: *address,
+                balance: *balance,
+                stats: self.stats(address),
+            })
+            .collect()
+    }
+
+    pub fn stats(&self, address: &Address) -> AccountStats {
+        self.stats.get(address).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{block_with_transfers, dev_accounts, AccountState, WorldState};
+
+    #[test]
+    fn test_leaderboard() {
+        let mut state = WorldState::new();
+        for i in 1..=5u64 {
+            state
+                .accounts
+                .insert(Address::from(i), AccountState::new((i % 3).into()));
+        }
+        let mut leaderboard = Leaderboard::default();
+        let changes = AccountChanges::of_state(0, &state, leaderboard.balances());
+        leaderboard.record(&changes);
+        let none = HashSet::new();
+        assert_eq!(leaderboard.len(&none), 5);
+        // ties are ordered by address
+        let ranking: Vec<(usize, Address)> = leaderboard
+            .page(0, 10, &none)
+            .iter()
+            .map(|account| (account.rank, account.address))
+            .collect();
+        let expected: Vec<(usize, Address)> = vec![2, 5, 1, 4, 3]
+            .into_iter()
+            .enumerate()
+            .map(|(index, i)| (index + 1, Address::from(i)))
+            .collect();
+        assert_eq!(ranking, expected);
+        assert_eq!(leaderboard.page(3, 10, &none).len(), 2);
+        assert!(leaderboard.page(10, 10, &none).is_empty());
+
+        let excluded: HashSet<Address> = vec![Address::from(2), Address::from(9)]
+            .into_iter()
+            .collect();
+        assert_eq!(leaderboard.len(&excluded), 4);
+        let page = leaderboard.page(0, 1, &excluded);
+        assert_eq!((page[0].rank, page[0].address), (1, Address::from(5)));
+
+        let (sender, key) = dev_accounts().into_iter().next().unwrap();
+        let block = block_with_transfers(7, &key, &[Address::from(3)]);
+        state.accounts.remove(&Address::from(1));
+        state.accounts.get_mut(&Address::from(3)).unwrap().balance = 100.into();
+        state.accounts.insert(sender, AccountState::new(50.into()));
+        let changes =
+            AccountChanges::of_block(&block).with_balances(&state, leaderboard.balances());
+        leaderboard.record(&changes);
+
+        let page = leaderboard.page(0, 2, &none);
+        assert_eq!(page[0].address, Address::from(3));
+        assert_eq!(page[0].balance, 100.into());
+        assert_eq!(page[1].address, sender);
+        assert_eq!(
+            page[1].stats,
+            AccountStats {
+                transaction_count: 1,
+                first_seen_block: 7,
+            }
+        );
+        assert_eq!(leaderboard.stats(&Address::from(3)).transaction_count, 1);
+        assert_eq!(leaderboard.stats(&Address::from(3)).first_seen_block, 0);
+        assert_eq!(leaderboard.len(&none), 5);
+        assert!(leaderboard
+            .page(0, 10, &none)
+            .iter()
+            .all(|account| account.address != Address::from(1)));
+    }
+}
@@ -10,13 +10,51 @@ pub use self::accountstate::*;
 mod transaction;
 pub use self::transaction::*;
 
+mod receipt;
+pub use self::receipt::*;
+
 mod block;
 pub use self::block::*;
 
+mod blockstream;
+pub use self::blockstream::*;
+
+mod statedb;
+pub use self::statedb::*;
+
+mod txpool;
+pub use self::txpool::*;
+
+mod node;
+pub use self::node::*;
+
+mod faucet;
+pub use self::faucet::*;
+
+mod accountchanges;
+pub use self::accountchanges::*;
+
+mod leaderboard;
+pub use self::leaderboard::*;
+
+mod history;
+pub use self::history::*;
+
+mod tracer;
+pub use self::tracer::*;
+
 mod options;
 pub use self::options::*;
 
 mod feeschedule;
 pub use self::feeschedule::*;
 
+mod trie;
+pub use self::trie::*;
+
+mod genesis;
+pub use self::genesis::*;
+
+pub mod serialization;
+
 pub mod rpc;
@@ -0,0 +1,478 @@
+//! A running node: the chain, its latest state and the transaction pool, which gets sealed into new
+//! blocks (there's no consensus yet, the node simply produces the blocks).
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use ethereum_types::{Address, H256, U256};
+use secp256k1::SecretKey;
+
+use crate::lib::{
+    export_blocks, AccountChanges, AccountHistory, Block, BlockChain, BlockError, BlockHeader,
+    ImportError, Leaderboard, PoolError, Transaction, TransactionError, TransactionPool,
+    WorldState, CHAIN_FILE,
+};
+
+/// Changes of the node that listeners (such as RPC subscriptions) are notified about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeEvent {
+    /// A block with the given hash was added to the chain as its new head.
+    NewHead(H256),
+    /// A transaction with the given hash was added to the pool.
+    NewPendingTransaction(H256),
+}
+
+/// Progress of importing a known range of blocks (see `sync_blocks`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncProgress {
+    /// Number of the latest block when the import started.
+    pub starting_block: u64,
+    /// Number of the last block to import.
+    pub highest_block: u64,
+}
+
+pub struct Node {
+    pub block_chain: BlockChain,
+    /// The post-state of the latest block.
+    pub state: WorldState,
+    pub pool: TransactionPool,
+    /// Keys of the accounts the node signs transactions for (`eth_sendTransaction`).
+    pub accounts: HashMap<Address, SecretKey>,
+    /// H<sub>c</sub> of the sealed blocks.
+    pub beneficiary: Address,
+    /// Sealed blocks are appended to the chain file in this directory.
+    pub datadir: Option<PathBuf>,
+    /// Set while blocks are imported by `sync_blocks`. No blocks are sealed meanwhile.
+    pub sync: Option<SyncProgress>,
+    /// Ranking of the accounts by balance, updated with each imported block.
+    pub leaderboard: Leaderboard,
+    /// Transactions and balances of the accounts, updated with each imported block.
+    pub history: AccountHistory,
+    /// Seconds added to the current time for the timestamps of sealed blocks (`evm_increaseTime`).
+    pub time_offset: u64,
+    /// Timestamp of the next sealed block, overriding the current time (`evm_setNextBlockTimestamp`).
+    pub next_timestamp: Option<u64>,
+    /// Whether the state was modified by `modify_state` since the latest block.
+    state_modified: bool,
+    listeners: Vec<Sender<NodeEvent>>,
+}
+
+/// The chain and state of a node at some point, to go back to with `Node::revert`.
+#[derive(Clone)]
+pub struct Snapshot {
+    block_chain: BlockChain,
+    state: WorldState,
+    pool: TransactionPool,
+    leaderboard: Leaderboard,
+    history: AccountHistory,
+    time_offset: u64,
+    state_modified: bool,
+}
+
+impl Node {
+    pub fn new(block_chain: BlockChain, state: WorldState) -> Self {
+        let mut node = Self {
+            leaderboard: Leaderboard::default(),
+            history: AccountHistory::default(),
+            block_chain,
+            state,
+            pool: TransactionPool::new(),
+            accounts: HashMap::new(),
+            beneficiary: Address::zero(),
+            datadir: None,
+            sync: None,
+            time_offset: 0,
+            next_timestamp: None,
+            state_modified: false,
+            listeners: vec![],
+        };
+        // the balances after earlier blocks aren't known, so they're recorded with the latest block
+        let blocks = node.block_chain.canonical_blocks();
+        let (latest, earlier) = blocks.split_last().expect("the chain has a genesis block");
+        let mut changes: Vec<AccountChanges> = earlier
+            .iter()
+            .copied()
+            .map(AccountChanges::of_block)
+            .collect();
+        changes.push(AccountChanges::of_block(latest).with_balances(&node.state, &HashMap::new()));
+        for changes in &changes {
+            node.record_account_changes(changes);
+        }
+        node
+    }
+
+    /// Returns a receiver for the events of the node from now on.
+    pub fn subscribe(&mut self) -> Receiver<NodeEvent> {
+        let (sender, receiver) = channel();
+        self.listeners.push(sender);
+        receiver
+    }
+
+    /// Sends the event to all listeners, dropping those whose receiver is gone.
+    fn emit(&mut self, event: NodeEvent) {
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+    }
+
+    /// The timestamp for a block sealed now: the current time (plus `time_offset`), unless
+    /// `next_timestamp` is set.
+    pub fn block_timestamp(&self) -> u64 {
+        self.next_timestamp
+            .unwrap_or_else(|| now() + self.time_offset)
+    }
+
+    /// The header of the next block, as far as it's known before sealing it.
+    pub fn pending_header(&self) -> BlockHeader {
+        BlockHeader::child_of(
+            &self.block_chain.latest_block().header,
+            self.beneficiary,
+            self.block_timestamp(),
+            &self.block_chain.config,
+        )
+    }
+
+    /// Adds the transaction to the pool, to be included in one of the next blocks.
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> Result<H256, PoolError> {
+        let header = self.pending_header();
+        let hash = self
+            .pool
+            .add(transaction, &self.state, &header, &self.block_chain.config)?;
+        self.emit(NodeEvent::NewPendingTransaction(hash));
+        Ok(hash)
+    }
+
+    /// The nonce of the next transaction of `address`, including the pending ones.
+    pub fn next_nonce(&self, address: &Address) -> U256 {
+        self.pool.next_nonce(address, &self.state)
+    }
+
+    /// Builds a block from the pending transactions and adds it to the chain. Transactions that turn
+    /// out to be invalid are dropped from the pool, unless they might become valid in a later block.
+    /// `next_timestamp` is reset. Returns the hash of the new block.
+    pub fn seal_block(&mut self, timestamp: u64) -> Result<H256, BlockError> {
+        let (block, rejected) = self.build_block(timestamp);
+        for (transaction, error) in rejected {
+            match error {
+                TransactionError::BlockGasLimitExceeded { .. }
+                | TransactionError::MaxFeeBelowBaseFee { .. }
+                | TransactionError::NonceTooHigh { .. } => {}
+                _ => {
+                    self.pool.remove(&transaction.hash());
+                }
+            }
+        }
+
+        let hash = self.import_block(block)?;
+        self.next_timestamp = None;
+        if let Err(e) = self.persist_latest_block() {
+            eprintln!("Error: could not save block {:?}: {}", hash, e);
+        }
+        Ok(hash)
+    }
+
+    /// The block that would be sealed next if it was sealed now (without the transactions that would
+    /// be rejected).
+    pub fn pending_block(&self) -> Block {
+        self.build_block(self.block_timestamp()).0
+    }
+
+    /// Builds a block on top of the latest block from the pending transactions. Also returns the
+    /// rejected transactions with the reason.
+    fn build_block(&self, timestamp: u64) -> (Block, Vec<(Transaction, TransactionError)>) {
+        let parent = &self.block_chain.latest_block().header;
+        let config = &self.block_chain.config;
+        let base_fee = BlockHeader::expected_base_fee(parent, config).unwrap_or_default();
+        Block::build(
+            parent,
+            &self.state,
+            self.pool.ordered(base_fee),
+            self.beneficiary,
+            timestamp,
+            config,
+        )
+    }
+
+    /// Imports the block on top of the latest block, drops the transactions that became invalid from
+    /// the pool and updates the leaderboard and account history. Returns the hash of the block.
+    pub fn import_block(&mut self, block: Block) -> Result<H256, BlockError> {
+        let hash = block.hash();
+        self.block_chain.import_block(block, &mut self.state)?;
+        if self.state_modified {
+            // executing the block on the post-state of its parent doesn't lead to this state
+            let state_root = self.block_chain.latest_block().header.state_root;
+            self.block_chain.states.pin(state_root, self.state.clone());
+            self.state_modified = false;
+        }
+        self.pool.update(&self.state);
+        let block = self.block_chain.latest_block();
+        let changes =
+            AccountChanges::of_block(block).with_balances(&self.state, self.leaderboard.balances());
+        self.record_account_changes(&changes);
+        self.emit(NodeEvent::NewHead(hash));
+        Ok(hash)
+    }
+
+    /// Modifies the latest state directly, outside of blocks (for testing, see the dev RPC methods).
+    /// The changes become part of the post-state of the next block, which is therefore kept in the
+    /// state database for good.
+    ///
+    /// Not allowed with a data directory: the chain file couldn't be imported again, as the blocks
+    /// alone don't lead to the modified state.
+    pub fn modify_state(&mut self, f: impl FnOnce(&mut WorldState)) {
+        assert!(
+            self.datadir.is_none(),
+            "the state can't be modified with a data directory"
+        );
+        f(&mut self.state);
+        self.state_modified = true;
+        let number = self.block_chain.latest_block().header.number;
+        self.pool.update(&self.state);
+        let changes = AccountChanges::of_state(number, &self.state, self.leaderboard.balances());
+        self.record_account_changes(&changes);
+    }
+
+    /// Feeds the changes of a block to the leaderboard and the account history.
+    fn record_account_changes(&mut self, changes: &AccountChanges) {
+        self.leaderboard.record(changes);
+        self.history.record(changes);
+    }
+
+    /// A copy of the chain and state, including the transaction pool.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            block_chain: self.block_chain.clone(),
+            state: self.state.clone(),
+            pool: self.pool.clone(),
+            leaderboard: self.leaderboard.clone(),
+            history: self.history.clone(),
+            time_offset: self.time_offset,
+            state_modified: self.state_modified,
+        }
+    }
+
+    /// Goes back to the snapshot. The chain file in the data directory is rewritten accordingly.
+    pub fn revert(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        self.block_chain = snapshot.block_chain;
+        self.state = snapshot.state;
+        self.pool = snapshot.pool;
+        self.leaderboard = snapshot.leaderboard;
+        self.history = snapshot.history;
+        self.time_offset = snapshot.time_offset;
+        self.state_modified = snapshot.state_modified;
+        self.next_timestamp = None;
+        if let Some(datadir) = &self.datadir {
+            let chain_file = datadir.join(CHAIN_FILE);
+            if chain_file.exists() {
+                fs::remove_file(chain_file)?;
+            }
+        }
+        self.persist_latest_block()
+    }
+
+    /// Appends the latest block to the chain file in the data directory (or writes the whole chain if
+    /// there's no chain file yet).
+    fn persist_latest_block(&self) -> io::Result<()> {
+        let datadir = match &self.datadir {
+            Some(datadir) => datadir,
+            None => return Ok(()),
+        };
+        let chain_file = datadir.join(CHAIN_FILE);
+        if chain_file.exists() {
+            OpenOptions::new()
+                .append(true)
+                .open(chain_file)?
+                .write_all(&self.block_chain.latest_block().to_rlp())
+        } else {
+            fs::create_dir_all(datadir)?;
+            export_blocks(&self.block_chain, &mut File::create(chain_file)?).map(|_| ())
+        }
+    }
+}
+
+/// Imports the blocks (e.g. read from a chain file) into the chain of the node, locking it for one
+/// block at a time so that it can serve requests in between. Meanwhile, `Node::sync` reports the
+/// progress. Blocks that are already part of the chain are skipped. Returns the number of imported blocks.
+pub fn sync_blocks(node: &Mutex<Node>, blocks: Vec<Block>) -> Result<usize, ImportError> {
+    let highest_block = match blocks.last() {
+        Some(block) => block.header.number,
+        None => return Ok(0),
+    };
+    {
+        let mut node = node.lock().unwrap();
+        let starting_block = node.block_chain.latest_block().header.number;
+        node.sync = Some(SyncProgress {
+            starting_block,
+            highest_block,
+        });
+    }
+
+    let mut imported = 0;
+    let mut result = Ok(());
+    for block in blocks {
+        let mut node = node.lock().unwrap();
+        if node.block_chain.blocks.contains_key(&block.hash()) {
+            continue;
+        }
+        let number = block.header.number;
+        if let Err(e) = node.import_block(block) {
+            result = Err(ImportError::InvalidBlock(number, e));
+            break;
+        }
+        imported += 1;
+    }
+    node.lock().unwrap().sync = None;
+    result.map(|_| imported)
+}
+
+/// The current Unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{
+        dev_accounts, import_blocks, Genesis, LegacyTransaction, StateRetention, DEV_CHAIN_ID,
+    };
+
+    #[test]
+    fn test_seal_block() {
+        let genesis = Genesis::dev();
+        let mut node = Node::new(BlockChain::from_genesis(&genesis), genesis.state());
+        let events = node.subscribe();
+        let (sender, key) = dev_accounts().into_iter().next().unwrap();
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+
+        let transfer = |nonce: u64| {
+            let mut transaction = Transaction::Legacy(LegacyTransaction {
+                nonce: nonce.into(),
+                gas_price: 2.into(),
+                gas_limit: 21000.into(),
+                to: Some(recipient),
+                value: 1000.into(),
+                ..LegacyTransaction::default()
+            });
+            transaction.sign(&key, Some(DEV_CHAIN_ID));
+            transaction
+        };
+        let hashes: Vec<H256> = (0..3)
+            .map(|nonce| node.submit_transaction(transfer(nonce)).unwrap())
+            .collect();
+        assert_eq!(node.next_nonce(&sender), 3.into());
+        assert_eq!(
+            node.submit_transaction(transfer(0)),
+            Err(PoolError::AlreadyKnown)
+        );
+
+        let pending = node.pending_block();
+        assert_eq!(pending.header.number, 1);
+        assert_eq!(pending.transactions.len(), 3);
+        assert_eq!(node.block_chain.latest_block().header.number, 0);
+
+        let datadir = std::env::temp_dir().join(format!("teth-test-seal-{}", now()));
+        node.datadir = Some(datadir.clone());
+        let hash = node.seal_block(now()).unwrap();
+        assert_eq!(node.block_chain.latest_block_hash, hash);
+        let block = node.block_chain.latest_block();
+        assert_eq!(
+            block
+                .transactions
+                .iter()
+                .map(Transaction::hash)
+                .collect::<Vec<_>>(),
+            hashes
+        );
+        assert_eq!(node.pool.pending_count(), 0);
+        assert_eq!(node.state.accounts[&recipient].balance, 3000.into());
+        let mut expected_events: Vec<NodeEvent> = hashes
+            .iter()
+            .map(|hash| NodeEvent::NewPendingTransaction(*hash))
+            .collect();
+        expected_events.push(NodeEvent::NewHead(hash));
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), expected_events);
+        assert_eq!(node.next_nonce(&sender), 3.into());
+
+        // the node picks up the saved chain
+        let mut state = genesis.state();
+        let mut block_chain = BlockChain::from_genesis(&genesis);
+        let mut file = File::open(datadir.join(CHAIN_FILE)).unwrap();
+        assert_eq!(
+            import_blocks(&mut block_chain, &mut state, &mut file).unwrap(),
+            1
+        );
+        assert_eq!(block_chain.latest_block_hash, hash);
+        fs::remove_dir_all(datadir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_blocks() {
+        let genesis = Genesis::dev();
+        let mut node = Node::new(BlockChain::from_genesis(&genesis), genesis.state());
+        node.seal_block(now()).unwrap();
+        node.seal_block(now()).unwrap();
+        let blocks: Vec<Block> = node
+            .block_chain
+            .canonical_blocks()
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut syncing = Node::new(BlockChain::from_genesis(&genesis), genesis.state());
+        let events = syncing.subscribe();
+        let syncing = Mutex::new(syncing);
+        assert_eq!(sync_blocks(&syncing, blocks.clone()).unwrap(), 2);
+        let syncing = syncing.into_inner().unwrap();
+        assert_eq!(syncing.sync, None);
+        assert_eq!(
+            syncing.block_chain.latest_block_hash,
+            node.block_chain.latest_block_hash
+        );
+        assert_eq!(events.try_iter().count(), 2);
+
+        let mut invalid = blocks;
+        invalid[2].header.gas_used = 1.into();
+        let syncing = Mutex::new(Node::new(
+            BlockChain::from_genesis(&genesis),
+            genesis.state(),
+        ));
+        assert!(sync_blocks(&syncing, invalid).is_err());
+        let syncing = syncing.into_inner().unwrap();
+        assert_eq!(syncing.sync, None);
+        assert_eq!(syncing.block_chain.latest_block().header.number, 1);
+    }
+
+    #[test]
+    fn test_modify_state() {
+        let genesis = Genesis::dev();
+        let mut block_chain = BlockChain::from_genesis(&genesis);
+        block_chain.states.set_retention(StateRetention::Recent(1));
+        let mut node = Node::new(block_chain, genesis.state());
+        let address = Address::from(0x42);
+        node.modify_state(|state| {
+            state.accounts.entry(address).or_default().balance = 7.into();
+        });
+        let hash = node.seal_block(now()).unwrap();
+        for _ in 0..3 {
+            node.seal_block(now()).unwrap();
+        }
+
+        // the state of the block after the modification is kept, later ones are recomputed from it
+        let chain = &node.block_chain;
+        for header in [
+            &chain.blocks[&hash].header,
+            &chain.canonical_blocks()[3].header,
+        ] {
+            let state = chain.state_at(header).unwrap();
+            assert_eq!(state.accounts[&address].balance, 7.into());
+        }
+    }
+}
@@ -1,5 +1,10 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 
+use crate::lib::rpc::ApiModule;
+
 /// CLI options (via structopt)
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -26,8 +31,126 @@ pub enum SubCommand {
         raw(setting = "structopt::clap::AppSettings::DisableVersion")
     )]
     Node {
-        /// Bootstrap the chain (with the genesis block).
+        /// Bootstrap the chain (with the genesis block). Implied by `--genesis` and `--datadir`.
         #[structopt(long)]
         bootstrap: bool,
+        /// Genesis file (geth format) to bootstrap from. Defaults to the built-in dev chain.
+        #[structopt(long, parse(from_os_str))]
+        genesis: Option<PathBuf>,
+        /// Data directory. The chain stored there is loaded on startup.
+        #[structopt(long, parse(from_os_str))]
+        datadir: Option<PathBuf>,
+        /// Accept transactions without replay protection (legacy signatures with v = 27/28, see EIP-155).
+        #[structopt(long)]
+        allow_unprotected_txs: bool,
+        /// Seconds between blocks, which are only sealed when there are pending transactions.
+        /// 0 seals a block for each transaction right away.
+        #[structopt(long, default_value = "5")]
+        block_time: u64,
+        /// Number of recent block states kept in memory (states aren't saved to the data directory).
+        /// Older states (for queries at past blocks) are recomputed from the blocks.
+        #[structopt(long, default_value = "128")]
+        state_history: usize,
+        /// Keep the states of all blocks (archive node).
+        #[structopt(long)]
+        archive: bool,
+        /// Address the WebSocket RPC server listens on.
+        #[structopt(long, default_value = "0.0.0.0")]
+        ws_addr: IpAddr,
+        /// Port of the WebSocket RPC server.
+        #[structopt(long, default_value = "8546")]
+        ws_port: u16,
+        /// Serve the RPC API over HTTP as well.
+        #[structopt(long)]
+        http: bool,
+        /// Address the HTTP RPC server listens on.
+        #[structopt(long, default_value = "127.0.0.1")]
+        http_addr: IpAddr,
+        /// Port of the HTTP RPC server.
+        #[structopt(long, default_value = "8545")]
+        http_port: u16,
+        /// Comma-separated list of origins allowed to make cross-origin HTTP requests (CORS), `*` for any.
+        #[structopt(long, raw(use_delimiter = "true"))]
+        http_corsdomain: Vec<String>,
+        /// Comma-separated list of accepted hostnames in the `Host` header of HTTP requests, `*` for any.
+        #[structopt(long, default_value = "localhost", raw(use_delimiter = "true"))]
+        http_vhosts: Vec<String>,
+        /// Path of a Unix socket to serve the RPC API on, including the privileged `admin_*` and `debug_*`
+        /// methods.
+        #[structopt(long, parse(from_os_str))]
+        ipc_path: Option<PathBuf>,
+        /// Comma-separated list of API modules served over WebSocket and HTTP
+        /// (eth, net, web3, teth, txpool, debug, admin, personal, evm). `personal` includes
+        /// `eth_sendTransaction`, which signs with the node's keys. `evm`, which only exists on the built-in
+        /// dev chain, includes `teth_set*`, which modify the state.
+        #[structopt(long, default_value = "eth,net,web3,teth", raw(use_delimiter = "true"))]
+        rpc_apis: Vec<ApiModule>,
+        /// The WebSocket and HTTP servers are only reachable through a reverse proxy, which appends the
+        /// client IP to the `X-Forwarded-For` header. Without it, the client IP isn't known and only the
+        /// per-address and daily faucet limits apply.
+        #[structopt(long)]
+        rpc_behind_proxy: bool,
+        /// Comma-separated list of API modules served over IPC.
+        #[structopt(
+            long,
+            default_value = "eth,net,web3,teth,txpool,debug,admin,personal,evm",
+            raw(use_delimiter = "true")
+        )]
+        ipc_apis: Vec<ApiModule>,
+        /// File with the hex-encoded private key of the account paying out `teth_faucet` requests.
+        /// Defaults to the second dev account on the dev chain; without it the faucet is disabled.
+        #[structopt(long, parse(from_os_str))]
+        faucet_key_file: Option<PathBuf>,
+        /// Require faucet requests to be signed by the key of the address (see `teth_faucetChallenge`).
+        #[structopt(long)]
+        faucet_signature: bool,
+        /// Seconds an address has to wait between faucet payouts.
+        #[structopt(long, default_value = "86400")]
+        faucet_address_cooldown: u64,
+        /// Seconds a client IP has to wait between faucet payouts.
+        #[structopt(long, default_value = "60")]
+        faucet_ip_cooldown: u64,
+        /// Maximum number of faucet payouts in 24 hours.
+        #[structopt(long, default_value = "1000")]
+        faucet_daily_limit: usize,
+        /// Maximum number of faucet payouts to a client IP in 24 hours.
+        #[structopt(long, default_value = "5")]
+        faucet_ip_daily_limit: usize,
+    },
+    /// Export the chain of a data directory to a file (concatenated RLP-encoded blocks).
+    #[structopt(
+        name = "export",
+        author = "",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp"),
+        raw(setting = "structopt::clap::AppSettings::DisableVersion")
+    )]
+    Export {
+        /// File to write the blocks to.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Genesis file (geth format) the chain started with. Defaults to the built-in dev chain.
+        #[structopt(long, parse(from_os_str))]
+        genesis: Option<PathBuf>,
+        /// Data directory to export the chain from.
+        #[structopt(long, parse(from_os_str))]
+        datadir: PathBuf,
+    },
+    /// Import blocks from a file (concatenated RLP-encoded blocks) into a data directory.
+    #[structopt(
+        name = "import",
+        author = "",
+        raw(setting = "structopt::clap::AppSettings::ColoredHelp"),
+        raw(setting = "structopt::clap::AppSettings::DisableVersion")
+    )]
+    Import {
+        /// File to read the blocks from.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Genesis file (geth format) the chain starts with. Defaults to the built-in dev chain.
+        #[structopt(long, parse(from_os_str))]
+        genesis: Option<PathBuf>,
+        /// Data directory to import the chain into.
+        #[structopt(long, parse(from_os_str))]
+        datadir: PathBuf,
     },
 }
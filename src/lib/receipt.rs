@@ -0,0 +1,182 @@
+use ethereum_types::{Address, Bloom, BloomInput, H256, U256};
+use rlp::{encode, Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+/// O
+///
+/// A log entry, O, is a tuple of the logger’s address, O<sub>a</sub>; a possibly empty series of
+/// 32-byte log topics, O<sub>t</sub>, and some number of bytes of data, O<sub>d</sub>:
+/// O ≡ (O<sub>a</sub>, (O<sub>t0</sub>, O<sub>t1</sub>, ...), O<sub>d</sub>)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogEntry {
+    /// O<sub>a</sub>
+    pub address: Address,
+    /// O<sub>t</sub>
+    pub topics: Vec<H256>,
+    /// O<sub>d</sub>
+    pub data: Vec<u8>,
+}
+
+impl LogEntry {
+    /// M(O) - the Bloom filter of the logger's address and all topics (Equation 27).
+    pub fn bloom(&self) -> Bloom {
+        let mut bloom = Bloom::zero();
+        bloom.accrue(BloomInput::Raw(&self.address));
+        for topic in &self.topics {
+            bloom.accrue(BloomInput::Raw(topic));
+        }
+        bloom
+    }
+}
+
+impl Encodable for LogEntry {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        s.append(&self.address);
+        s.append_list(&self.topics);
+        s.append(&self.data);
+    }
+}
+
+impl Decodable for LogEntry {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            address: rlp.val_at(0)?,
+            topics: rlp.list_at(1)?,
+            data: rlp.val_at(2)?,
+        })
+    }
+}
+
+/// R
+///
+/// The receipt of a transaction is a tuple of four items comprising the status code of the
+/// transaction, R<sub>z</sub>, the cumulative gas used in the block containing the transaction
+/// receipt as of immediately after the transaction has happened, R<sub>u</sub>, the set of logs
+/// created through execution of the transaction, R<sub>l</sub>, and the Bloom filter composed
+/// from information in those logs, R<sub>b</sub>:
+/// R ≡ (R<sub>z</sub>, R<sub>u</sub>, R<sub>b</sub>, R<sub>l</sub>)
+///
+/// Receipts additionally carry the type of their transaction, R<sub>x</sub> (EIP-2718).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Receipt {
+    /// R<sub>x</sub> - equal to T<sub>x</sub>, 0 for legacy transactions.
+    pub transaction_type: u8,
+    /// R<sub>z</sub> - `true` if the transaction succeeded.
+    pub status_code: bool,
+    /// R<sub>u</sub>
+    pub cumulative_gas_used: U256,
+    /// R<sub>b</sub>
+    pub logs_bloom: Bloom,
+    /// R<sub>l</sub>
+    pub logs: Vec<LogEntry>,
+}
+
+impl Receipt {
+    pub fn new(
+        transaction_type: u8,
+        status_code: bool,
+        cumulative_gas_used: U256,
+        logs: Vec<LogEntry>,
+    ) -> Self {
+        // R_b = ⋁_{O ∈ R_l} M(O) (Equation 26)
+        let mut logs_bloom = Bloom::zero();
+        for log in &logs {
+            logs_bloom.accrue_bloom(&log.bloom());
+        }
+        Self {
+            transaction_type,
+            status_code,
+            cumulative_gas_used,
+            logs_bloom,
+            logs,
+        }
+    }
+
+    /// RLP(R) for receipts of legacy transactions, R<sub>x</sub> · RLP(R) otherwise (EIP-2718).
+    /// This is what goes into the receipts trie.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        let payload = encode(self);
+        if self.transaction_type == 0 {
+            return payload;
+        }
+        let mut out = Vec::with_capacity(payload.len() + 1);
+        out.push(self.transaction_type);
+        out.extend(payload);
+        out
+    }
+
+    /// Decodes the encoding of `to_rlp`.
+    pub fn from_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        match data.first() {
+            Some(&first) if first >= 0xc0 => rlp::decode(data),
+            Some(&transaction_type) if transaction_type <= 2 => Ok(Self {
+                transaction_type,
+                ..rlp::decode(&data[1..])?
+            }),
+            _ => Err(DecoderError::Custom("unknown receipt type")),
+        }
+    }
+}
+
+impl Encodable for Receipt {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&(self.status_code as u8));
+        s.append(&self.cumulative_gas_used);
+        s.append(&self.logs_bloom);
+        s.append_list(&self.logs);
+    }
+}
+
+impl Decodable for Receipt {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            transaction_type: 0,
+            status_code: rlp.val_at::<u8>(0)? == 1,
+            cumulative_gas_used: rlp.val_at(1)?,
+            logs_bloom: rlp.val_at(2)?,
+            logs: rlp.list_at(3)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt() {
+        let log = LogEntry {
+            address: Address::from("0x0000000000000000000000000000000000000042"),
+            topics: vec![H256::from(U256::from(1))],
+            data: vec![1, 2, 3],
+        };
+        let receipt = Receipt::new(0, true, 21000.into(), vec![log.clone()]);
+        assert!(receipt
+            .logs_bloom
+            .contains_input(BloomInput::Raw(&log.address)));
+        assert!(receipt
+            .logs_bloom
+            .contains_input(BloomInput::Raw(&log.topics[0])));
+
+        let decoded: Receipt = rlp::decode(&rlp::encode(&receipt)).unwrap();
+        assert_eq!(decoded, receipt);
+        assert_eq!(receipt.to_rlp(), rlp::encode(&receipt));
+    }
+
+    #[test]
+    fn test_typed_receipt() {
+        let receipt = Receipt::new(2, false, 21000.into(), vec![]);
+        let rlp = receipt.to_rlp();
+        assert_eq!(rlp[0], 2);
+        assert_eq!(&rlp[1..], &rlp::encode(&receipt)[..]);
+        assert_eq!(Receipt::from_rlp(&rlp).unwrap(), receipt);
+        assert!(Receipt::from_rlp(&[3, 0xc0]).is_err());
+    }
+}
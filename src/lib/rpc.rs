@@ -1,86 +1,180 @@
-use std::sync::Mutex;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use ethereum_types::{Address, Bloom, H256, U256};
+use ethereum_types::{Address, Bloom, BloomInput, H256, H64, U256};
 use jsonrpc_derive::rpc;
-use jsonrpc_ws_server::jsonrpc_core::{Error, IoHandler, Result};
-use jsonrpc_ws_server::ServerBuilder;
-use serde::Serialize;
+use jsonrpc_http_server::hyper::{Body, Request};
+use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, Host};
+use jsonrpc_pubsub::typed::{Sink, Subscriber};
+use jsonrpc_pubsub::{PubSubHandler, PubSubMetadata, Session, SubscriptionId};
+use jsonrpc_ws_server::jsonrpc_core::futures::Future;
+use jsonrpc_ws_server::jsonrpc_core::{Error, ErrorCode, MetaIoHandler, RemoteProcedure, Result};
+use jsonrpc_ws_server::{ws, RequestContext, ServerBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_keccak::keccak256;
 
+use crate::lib::serialization::{bytes, option_bytes, option_quantity, quantity, Bytes, U64};
 use crate::lib::{
-    AccountState, Block, BlockChain, BlockHeader, Transaction, Wei, WorldState, ONE_ETHER,
+    contract_address, export_blocks, now, to_hex, AccessListItem, AccessListTransaction, Block,
+    BlockChain, BlockHeader, CallFrame, CallTracer, ChainConfig, DynamicFeeTransaction,
+    ExecutionResult, Faucet, FaucetError, LegacyTransaction, LogEntry, Node, NodeEvent, PoolError,
+    RankedAccount, Receipt, Snapshot, StructLogger, StructLoggerConfig, Tracer, Transaction,
+    TransactionError, TransactionOutcome, TransactionPool, TransactionsBySender, Wei, WorldState,
 };
 
+/// Name and version of the client, as reported by `web3_clientVersion` and `admin_nodeInfo`.
+pub const CLIENT_VERSION: &str = concat!("teth/v", env!("CARGO_PKG_VERSION"));
+/// Version of the `eth` wire protocol (eth/68) reported by `eth_protocolVersion`.
+const PROTOCOL_VERSION: u64 = 68;
+/// Maximum number of blocks `eth_feeHistory` returns.
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+/// Filters that haven't been polled for this long are uninstalled (as in geth).
+const FILTER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Number of recent blocks `eth_maxPriorityFeePerGas` looks at.
+const PRIORITY_FEE_BLOCKS: usize = 20;
+/// Percentile of the recent priority fees `eth_maxPriorityFeePerGas` suggests.
+const PRIORITY_FEE_PERCENTILE: usize = 60;
+/// Suggested priority fee when there are no recent transactions, 1 Gwei.
+const DEFAULT_PRIORITY_FEE: u64 = 1_000_000_000;
+/// Number of accounts `teth_topAccounts` returns by default.
+const DEFAULT_PAGE_SIZE: usize = 5;
+/// Maximum number of accounts `teth_topAccounts` returns.
+const MAX_PAGE_SIZE: usize = 100;
+/// Number of transactions per page of `teth_getAccountTransactions`.
+const ACCOUNT_TRANSACTIONS_PAGE_SIZE: usize = 25;
+/// Maximum number of blocks `teth_getRecentBlocks` returns.
+const MAX_RECENT_BLOCKS: usize = 100;
+
 /// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getblockbyhash
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct BlockResponse {
     /// The block number. `None` when it's a pending block.
+    #[serde(with = "option_quantity")]
     pub number: Option<u64>,
     pub hash: Option<H256>,
     pub parent_hash: H256,
     /// Hash of the generated proof-of-work. `None` when its pending block
-    pub nonce: Option<u64>,
-    // /// SHA3 of the uncles data in the block.
-    // pub sha3_uncles: H256,
+    pub nonce: Option<H64>,
+    pub mix_hash: H256,
+    /// SHA3 of the uncles data in the block.
+    pub sha3_uncles: H256,
     /// The bloom filter for the logs of the block. `None` when its pending block.
     pub logs_bloom: Option<Bloom>,
     /// The root of the transaction trie of the block.
     pub transactions_root: H256,
+    /// The root of the final state trie of the block.
+    pub state_root: H256,
     /// The root of the receipts trie of the block.
     pub receipts_root: H256,
     /// The address of the beneficiary to whom the mining rewards were given.
     pub miner: Address,
     pub difficulty: U256,
-    pub total_difficulty: U256,
-    pub extra_data: [u8; 32],
+    /// The total difficulty of the chain until this block. `None` for uncles.
+    pub total_difficulty: Option<U256>,
+    #[serde(with = "bytes")]
+    pub extra_data: Vec<u8>,
     /// The size of this block in bytes
+    #[serde(with = "quantity")]
     pub size: usize,
     pub gas_limit: U256,
     pub gas_used: U256,
+    #[serde(with = "quantity")]
     pub timestamp: u64,
-    #[serde(rename = "transactions", skip_serializing_if = "Vec::is_empty")]
-    pub full_transactions: Vec<TransactionResponse>,
-    #[serde(rename = "transactions", skip_serializing_if = "Vec::is_empty")]
-    pub transaction_hashes: Vec<H256>,
+    /// Only after the London fork (EIP-1559).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<U256>,
+    /// The full transactions or only their hashes, depending on the request.
+    pub transactions: BlockTransactions,
     /// Array of uncle hashes
     pub uncles: Vec<H256>,
 }
 
 impl BlockResponse {
-    fn new(block: &Block, block_chain: &BlockChain, include_full_transactions: bool) -> Self {
+    fn new(block: &Block, total_difficulty: Option<U256>, include_full_transactions: bool) -> Self {
         let header = &block.header;
-        let mut response = Self {
+        Self {
             number: Some(header.number),
             hash: Some(header.hash()),
             parent_hash: header.parent_hash,
             nonce: Some(header.nonce),
-            // sha3_uncles: keccak256(&encode(&block.ommers)).into(),
-            logs_bloom: Some(header.logs_bloom), // TODO!: None for pending block..
+            mix_hash: header.mix_hash,
+            sha3_uncles: header.ommers_hash,
+            logs_bloom: Some(header.logs_bloom),
             transactions_root: header.transactions_root,
+            state_root: header.state_root,
             receipts_root: header.receipts_root,
             miner: header.beneficiary,
             difficulty: header.difficulty,
-            total_difficulty: block_chain.total_difficulty(&block_chain.latest_block_hash),
-            extra_data: header.extra_data,
+            total_difficulty,
+            extra_data: header.extra_data.clone(),
             size: block.to_rlp().len(),
             gas_limit: header.gas_limit,
             gas_used: header.gas_used,
             timestamp: header.timestamp,
-            full_transactions: vec![],
-            transaction_hashes: vec![],
+            base_fee_per_gas: header.base_fee_per_gas,
+            transactions: if include_full_transactions {
+                BlockTransactions::Full(
+                    block
+                        .transactions
+                        .iter()
+                        .map(|t| TransactionResponse::new(t, Some(block)))
+                        .collect(),
+                )
+            } else {
+                BlockTransactions::Hashes(block.transactions.iter().map(|t| t.hash()).collect())
+            },
             uncles: block.ommers.iter().map(BlockHeader::hash).collect(),
-        };
-        if include_full_transactions {
-            response.full_transactions = block
-                .transactions
-                .iter()
-                .map(|t| TransactionResponse::new(t, Some(block)))
-                .collect();
-        } else {
-            response.transaction_hashes = block.transactions.iter().map(|t| t.hash()).collect();
         }
-        response
     }
+
+    /// A block of the chain.
+    fn from_chain(
+        block: &Block,
+        block_chain: &BlockChain,
+        include_full_transactions: bool,
+    ) -> Self {
+        let total_difficulty = block_chain.total_difficulty(&block.header.hash());
+        Self::new(block, Some(total_difficulty), include_full_transactions)
+    }
+
+    /// The block that would be sealed next (see `Node::pending_block`), without hash, nonce and
+    /// logs bloom.
+    fn pending(block: &Block, include_full_transactions: bool) -> Self {
+        Self {
+            hash: None,
+            nonce: None,
+            logs_bloom: None,
+            ..Self::new(block, None, include_full_transactions)
+        }
+    }
+
+    /// An uncle (ommer) header, as a block without transactions and uncles.
+    fn uncle(header: &BlockHeader) -> Self {
+        let block = Block {
+            header: header.clone(),
+            ..Block::default()
+        };
+        Self::new(&block, None, false)
+    }
+}
+
+/// The transactions of a `BlockResponse`.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum BlockTransactions {
+    Hashes(Vec<H256>),
+    Full(Vec<TransactionResponse>),
 }
 
 /// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionbyhash
@@ -90,54 +184,88 @@ pub struct TransactionResponse {
     /// Hash of the block where this transaction was in. `None` when it's pending.
     pub block_hash: Option<H256>,
     /// Block number where this transaction was in. `None` when it's pending.
-    pub number: Option<u64>,
+    #[serde(with = "option_quantity")]
+    pub block_number: Option<u64>,
     /// Address of the sender.
     pub from: Address,
     /// Gas provided by the sender.
     pub gas: Wei,
-    /// Gas price provided by the sender in Wei
+    /// Gas price provided by the sender in Wei. For EIP-1559 transactions, the effective gas price
+    /// once included in a block, the max fee per gas before.
     pub gas_price: Wei,
     /// Hash of the transaction.
     pub hash: H256,
     /// The data sent along with the transaction.
+    #[serde(with = "option_bytes")]
     pub input: Option<Vec<u8>>,
     /// The number of transactions made by the sender prior to this one.
     pub nonce: U256,
     /// Address of the receiver. `None` when it's a contract creation transaction.
     pub to: Option<Address>,
     /// Integer of the transaction's index position in the block. `None` when it's pending.
+    #[serde(with = "option_quantity")]
     pub transaction_index: Option<usize>,
     /// Value transferred in Wei.
     pub value: Wei,
-    /// ECDSA recovery id
-    pub v: u8,
+    /// ECDSA recovery id (with the chain ID for EIP-155 transactions)
+    #[serde(with = "quantity")]
+    pub v: u64,
     /// ECDSA signature r
     pub r: U256,
     /// ECDSA signature s
     pub s: U256,
+    /// The transaction type (EIP-2718).
+    #[serde(rename = "type", with = "quantity")]
+    pub transaction_type: u8,
+    /// The chain ID the transaction is bound to. `None` for transactions without replay protection.
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_quantity")]
+    pub chain_id: Option<u64>,
+    /// Only for typed transactions (EIP-2930).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<Vec<AccessListItem>>,
+    /// Only for EIP-1559 transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<Wei>,
+    /// Only for EIP-1559 transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<Wei>,
 }
 
 impl TransactionResponse {
     pub fn new(transaction: &Transaction, block: Option<&Block>) -> Self {
         let mut response = Self {
-            from: transaction.sender(),
-            gas: transaction.gas_limit,
-            gas_price: transaction.gas_price,
+            from: transaction.sender().unwrap_or_default(),
+            gas: transaction.gas_limit(),
+            gas_price: transaction.gas_price(),
             hash: transaction.hash(),
-            input: transaction.data.clone(),
-            nonce: transaction.nonce,
-            to: transaction.to,
-            value: transaction.value,
-            v: transaction.signature.v,
-            r: transaction.signature.r,
-            s: transaction.signature.s,
+            input: Some(transaction.data().to_vec()),
+            nonce: transaction.nonce(),
+            to: transaction.to(),
+            value: transaction.value(),
+            v: transaction.signature().v,
+            r: transaction.signature().r,
+            s: transaction.signature().s,
+            transaction_type: transaction.transaction_type(),
+            chain_id: transaction.chain_id(),
             ..Self::default()
         };
+        match transaction {
+            Transaction::Legacy(_) => {}
+            Transaction::AccessList(t) => {
+                response.access_list = Some(t.access_list.clone());
+            }
+            Transaction::DynamicFee(t) => {
+                response.access_list = Some(t.access_list.clone());
+                response.max_fee_per_gas = Some(t.max_fee_per_gas);
+                response.max_priority_fee_per_gas = Some(t.max_priority_fee_per_gas);
+            }
+        }
 
         if let Some(block) = block {
             let header = &block.header;
             response.block_hash = Some(header.hash());
-            response.number = Some(header.number);
+            response.block_number = Some(header.number);
+            response.gas_price = transaction.effective_gas_price(header.base_fee());
             // TODO!: optimize?
             response.transaction_index = block
                 .transactions
@@ -149,157 +277,3696 @@ impl TransactionResponse {
     }
 }
 
+/// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionreceipt
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptResponse {
+    pub transaction_hash: H256,
+    /// Integer of the transaction's index position in the block.
+    #[serde(with = "quantity")]
+    pub transaction_index: usize,
+    pub block_hash: H256,
+    #[serde(with = "quantity")]
+    pub block_number: u64,
+    /// Address of the sender.
+    pub from: Address,
+    /// Address of the receiver. `None` when it's a contract creation transaction.
+    pub to: Option<Address>,
+    /// The total amount of gas used in the block when this transaction was executed.
+    pub cumulative_gas_used: U256,
+    /// The amount of gas used by this transaction alone.
+    pub gas_used: U256,
+    /// The price per gas the sender actually paid, including the base fee.
+    pub effective_gas_price: Wei,
+    /// The address of the created contract, if the transaction was a contract creation.
+    pub contract_address: Option<Address>,
+    pub logs: Vec<LogResponse>,
+    pub logs_bloom: Bloom,
+    /// The transaction type (EIP-2718).
+    #[serde(rename = "type", with = "quantity")]
+    pub transaction_type: u8,
+    /// 1 for success, 0 for failure.
+    #[serde(with = "quantity")]
+    pub status: u8,
+}
+
+impl ReceiptResponse {
+    /// The receipt of the transaction at `index` of `block`, whose receipts are `receipts`.
+    pub fn new(block: &Block, receipts: &[Receipt], index: usize) -> Self {
+        let header = &block.header;
+        let transaction = &block.transactions[index];
+        let receipt = &receipts[index];
+        let sender = transaction.sender().unwrap_or_default();
+        let previous_cumulative_gas_used = if index == 0 {
+            U256::zero()
+        } else {
+            receipts[index - 1].cumulative_gas_used
+        };
+        let first_log_index: usize = receipts[..index].iter().map(|r| r.logs.len()).sum();
+
+        Self {
+            transaction_hash: transaction.hash(),
+            transaction_index: index,
+            block_hash: header.hash(),
+            block_number: header.number,
+            from: sender,
+            to: transaction.to(),
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            gas_used: receipt.cumulative_gas_used - previous_cumulative_gas_used,
+            effective_gas_price: transaction.effective_gas_price(header.base_fee()),
+            contract_address: match transaction.to() {
+                Some(_) => None,
+                None => Some(contract_address(&sender, &transaction.nonce())),
+            },
+            logs: receipt
+                .logs
+                .iter()
+                .enumerate()
+                .map(|(i, log)| LogResponse::new(log, block, index, first_log_index + i))
+                .collect(),
+            logs_bloom: receipt.logs_bloom,
+            transaction_type: receipt.transaction_type,
+            status: receipt.status_code as u8,
+        }
+    }
+}
+
+/// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getfilterchanges
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogResponse {
+    /// `true` when the log was removed due to a chain reorganization.
+    pub removed: bool,
+    /// Integer of the log's index position in the block.
+    #[serde(with = "quantity")]
+    pub log_index: usize,
+    #[serde(with = "quantity")]
+    pub transaction_index: usize,
+    pub transaction_hash: H256,
+    pub block_hash: H256,
+    #[serde(with = "quantity")]
+    pub block_number: u64,
+    /// Address from which this log originated.
+    pub address: Address,
+    #[serde(with = "bytes")]
+    pub data: Vec<u8>,
+    pub topics: Vec<H256>,
+}
+
+impl LogResponse {
+    pub fn new(log: &LogEntry, block: &Block, transaction_index: usize, log_index: usize) -> Self {
+        Self {
+            removed: false,
+            log_index,
+            transaction_index,
+            transaction_hash: block.transactions[transaction_index].hash(),
+            block_hash: block.header.hash(),
+            block_number: block.header.number,
+            address: log.address,
+            data: log.data.clone(),
+            topics: log.topics.clone(),
+        }
+    }
+}
+
+/// Progress of importing blocks, see `SyncProgress`.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncInfo {
+    #[serde(with = "quantity")]
+    starting_block: u64,
+    #[serde(with = "quantity")]
+    current_block: u64,
+    #[serde(with = "quantity")]
+    highest_block: u64,
+}
+
+/// Result of `eth_syncing`: the progress, or `false` if the node isn't importing blocks.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum SyncStatus {
+    Syncing(SyncInfo),
+    NotSyncing(bool),
+}
+
+/// Result of `teth_topAccounts`: a page of the leaderboard.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LeaderboardPage {
+    /// Number of ranked accounts (without the excluded ones).
+    #[serde(with = "quantity")]
+    pub total: usize,
+    pub accounts: Vec<LeaderboardEntry>,
+}
+
+/// An account of the leaderboard, see `RankedAccount` and `AccountStats`.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    #[serde(with = "quantity")]
+    pub rank: usize,
+    pub address: Address,
+    pub balance: Wei,
+    #[serde(with = "quantity")]
+    pub transaction_count: u64,
+    #[serde(with = "quantity")]
+    pub first_seen_block: u64,
+}
+
+impl From<RankedAccount> for LeaderboardEntry {
+    fn from(account: RankedAccount) -> Self {
+        Self {
+            rank: account.rank,
+            address: account.address,
+            balance: account.balance,
+            transaction_count: account.stats.transaction_count,
+            first_seen_block: account.stats.first_seen_block,
+        }
+    }
+}
+
+/// A transaction of `teth_getAccountTransactions`, with the time and outcome of its block.
+#[derive(Serialize, Debug)]
+pub struct AccountTransaction {
+    #[serde(flatten)]
+    pub transaction: TransactionResponse,
+    /// Timestamp of the block.
+    #[serde(with = "quantity")]
+    pub timestamp: u64,
+    /// 1 for success, 0 for failure (as in the receipt).
+    #[serde(with = "quantity")]
+    pub status: u8,
+}
+
+/// Result of `teth_getAccountTransactions`: a page of the transactions of an account.
+#[derive(Serialize, Debug)]
+pub struct AccountTransactions {
+    /// Number of transactions of the account.
+    #[serde(with = "quantity")]
+    pub total: usize,
+    pub transactions: Vec<AccountTransaction>,
+}
+
+/// The balance of an account after a block that changed it (`teth_getAccountBalanceHistory`).
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceChange {
+    #[serde(with = "quantity")]
+    pub block_number: u64,
+    pub balance: Wei,
+}
+
+/// The header fields of a block the explorer shows (`teth_getRecentBlocks`).
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSummary {
+    #[serde(with = "quantity")]
+    pub number: u64,
+    pub hash: H256,
+    #[serde(with = "quantity")]
+    pub timestamp: u64,
+    pub miner: Address,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    /// Only after the London fork (EIP-1559).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<U256>,
+    #[serde(with = "quantity")]
+    pub transaction_count: usize,
+}
+
+impl BlockSummary {
+    fn new(block: &Block) -> Self {
+        let header = &block.header;
+        Self {
+            number: header.number,
+            hash: header.hash(),
+            timestamp: header.timestamp,
+            miner: header.beneficiary,
+            gas_used: header.gas_used,
+            gas_limit: header.gas_limit,
+            base_fee_per_gas: header.base_fee_per_gas,
+            transaction_count: block.transactions.len(),
+        }
+    }
+}
+
+/// Source: https://github.com/ethereum/execution-apis (eth_feeHistory)
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeHistory {
+    /// Number of the first block of the returned range.
+    #[serde(with = "quantity")]
+    pub oldest_block: u64,
+    /// Base fee of each block in the range, plus the one of the next block.
+    /// Zero for blocks before London.
+    pub base_fee_per_gas: Vec<U256>,
+    /// gasUsed / gasLimit of each block in the range.
+    pub gas_used_ratio: Vec<f64>,
+    /// The requested percentiles of the priority fees paid in each block, weighted by gas used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward: Option<Vec<Vec<U256>>>,
+}
+
+/// Result of `txpool_status`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TxpoolStatus {
+    #[serde(with = "quantity")]
+    pub pending: usize,
+    #[serde(with = "quantity")]
+    pub queued: usize,
+}
+
+/// Result of `txpool_content` and `txpool_inspect`: something about each transaction of the pool by
+/// sender and nonce (as decimal string, like geth).
+#[derive(Serialize, Debug)]
+pub struct TxpoolContent<T> {
+    pub pending: BTreeMap<Address, BTreeMap<String, T>>,
+    pub queued: BTreeMap<Address, BTreeMap<String, T>>,
+}
+
+impl<T> TxpoolContent<T> {
+    fn new(pool: &TransactionPool, f: impl Fn(&Transaction) -> T) -> Self {
+        let map = |transactions: &TransactionsBySender| {
+            transactions
+                .iter()
+                .map(|(sender, by_nonce)| {
+                    let by_nonce = by_nonce
+                        .iter()
+                        .map(|(nonce, transaction)| (nonce.to_string(), f(transaction)))
+                        .collect();
+                    (*sender, by_nonce)
+                })
+                .collect()
+        };
+        Self {
+            pending: map(&pool.pending),
+            queued: map(&pool.queued),
+        }
+    }
+}
+
+/// The block parameter of state queries: a tag ("latest", "earliest", "pending") or hex number as for
+/// `eth_getBlockByNumber`, or an object with the hash or number of the block (EIP-1898).
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum BlockParameter {
+    NumberOrTag(String),
+    #[serde(rename_all = "camelCase")]
+    Hash {
+        block_hash: H256,
+        /// Fail if the block is not part of the canonical chain.
+        #[serde(default)]
+        require_canonical: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Number {
+        block_number: String,
+    },
+}
+
+impl Default for BlockParameter {
+    fn default() -> Self {
+        BlockParameter::NumberOrTag("latest".to_string())
+    }
+}
+
+impl BlockParameter {
+    fn is_pending(&self) -> bool {
+        match self {
+            BlockParameter::NumberOrTag(tag) => tag == "pending",
+            _ => false,
+        }
+    }
+}
+
+/// A single value or an array of values, as in the filters of `eth_subscribe("logs")`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ValueOrArray<T> {
+    Value(T),
+    Array(Vec<T>),
+}
+
+impl<T: PartialEq> ValueOrArray<T> {
+    /// Whether the predicate holds for the value or one of the array (an empty array matches anything).
+    fn any(&self, predicate: impl Fn(&T) -> bool) -> bool {
+        match self {
+            ValueOrArray::Value(v) => predicate(v),
+            ValueOrArray::Array(values) => values.is_empty() || values.iter().any(predicate),
+        }
+    }
+
+    fn matches(&self, value: &T) -> bool {
+        self.any(|v| v == value)
+    }
+}
+
+/// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_newfilter
+///
+/// Matches the logs of the given address(es) (any if `None`) whose topics match `topics` by position:
+/// `null` matches any topic, an array any of its topics. The blocks are either given as a range
+/// (`fromBlock`/`toBlock`, default: "latest") or by `blockHash` (EIP-234); subscriptions ignore them.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilter {
+    pub from_block: Option<String>,
+    pub to_block: Option<String>,
+    pub block_hash: Option<H256>,
+    pub address: Option<ValueOrArray<Address>>,
+    pub topics: Option<Vec<Option<ValueOrArray<H256>>>>,
+}
+
+impl LogFilter {
+    /// `false` if a block with the given logs bloom can't contain matching logs.
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let contains = |bytes: &[u8]| bloom.contains_input(BloomInput::Raw(bytes));
+        if let Some(address) = &self.address {
+            if !address.any(|address| contains(address)) {
+                return false;
+            }
+        }
+        let topics = self.topics.as_ref().map_or(&[][..], Vec::as_slice);
+        topics
+            .iter()
+            .flatten()
+            .all(|topic| topic.any(|topic| contains(topic)))
+    }
+
+    pub fn matches(&self, log: &LogEntry) -> bool {
+        if let Some(address) = &self.address {
+            if !address.matches(&log.address) {
+                return false;
+            }
+        }
+        let topics = self.topics.as_ref().map_or(&[][..], Vec::as_slice);
+        topics.iter().enumerate().all(|(i, topic)| match topic {
+            None => true,
+            Some(topic) => log.topics.get(i).is_some_and(|t| topic.matches(t)),
+        })
+    }
+}
+
+/// The kinds of `eth_subscribe` subscriptions.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionKind {
+    /// The header of each new block.
+    NewHeads,
+    /// The logs of new blocks that match a `LogFilter`.
+    Logs,
+    /// The hash of each transaction added to the transaction pool.
+    NewPendingTransactions,
+}
+
+/// Source: https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_sendtransaction
+///
+/// All fields but `from` are optional. EIP-1559 transactions are sent when `maxFeePerGas` or
+/// `maxPriorityFeePerGas` are given, or after the London fork if `gasPrice` isn't.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionRequest {
+    /// Optional for `eth_call` and `eth_estimateGas` (default: the zero address).
+    #[serde(default)]
+    pub from: Address,
+    /// `None` for contract creations.
+    pub to: Option<Address>,
+    /// Defaults to the intrinsic gas, which is all a transaction needs without an EVM (`eth_call` and
+    /// `eth_estimateGas`: the block gas limit).
+    pub gas: Option<U256>,
+    pub gas_price: Option<Wei>,
+    pub max_fee_per_gas: Option<Wei>,
+    pub max_priority_fee_per_gas: Option<Wei>,
+    pub value: Option<Wei>,
+    /// Input data, or init code for contract creations.
+    pub data: Option<Bytes>,
+    /// Alias of `data`.
+    pub input: Option<Bytes>,
+    /// Defaults to the next nonce of the sender, including pending transactions.
+    pub nonce: Option<U256>,
+    pub access_list: Option<Vec<AccessListItem>>,
+}
+
 /// Trait for RPC methods, using jsonrpc-derive.
 #[rpc]
 pub trait Rpc {
-    /// Returns the balance of the account of given address. See also
-    /// [eth_getBalance](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getbalance).
+    /// The WebSocket session of a request, which subscriptions are tied to.
+    type Metadata;
+
+    /// Returns the number of the latest block. See also
+    /// [eth_blockNumber](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_blocknumber).
+    #[rpc(name = "eth_blockNumber")]
+    fn block_number(&self) -> Result<U256>;
+
+    /// Returns the balance of the account of given address at the given block (default: "latest").
+    /// See also [eth_getBalance](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getbalance).
     #[rpc(name = "eth_getBalance")]
-    fn get_balance(&self, address: Address, block_number: String) -> Result<Wei>;
+    fn get_balance(&self, address: Address, block: Option<BlockParameter>) -> Result<Wei>;
+
+    /// Returns the nonce of the account, i.e. the number of transactions it has sent. For "pending",
+    /// the transactions in the pool are counted as well. See also
+    /// [eth_getTransactionCount](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactioncount).
+    #[rpc(name = "eth_getTransactionCount")]
+    fn get_transaction_count(
+        &self,
+        address: Address,
+        block: Option<BlockParameter>,
+    ) -> Result<U256>;
+
+    /// Returns the code of the account. See also
+    /// [eth_getCode](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getcode).
+    #[rpc(name = "eth_getCode")]
+    fn get_code(&self, address: Address, block: Option<BlockParameter>) -> Result<Bytes>;
 
-    /// Returns information about a block by block number. See also
+    /// Returns the value of a storage position of the account. See also
+    /// [eth_getStorageAt](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getstorageat).
+    #[rpc(name = "eth_getStorageAt")]
+    fn get_storage_at(
+        &self,
+        address: Address,
+        position: U256,
+        block: Option<BlockParameter>,
+    ) -> Result<H256>;
+
+    /// Returns information about a block by block number or tag, `null` if there's no such block.
+    /// "pending" is the block that would be sealed next from the transaction pool. See also
     /// [eth_getBlockByNumber](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getblockbynumber).
     #[rpc(name = "eth_getBlockByNumber")]
     fn get_block_by_number(
         &self,
         number: String,
         return_transaction_objects: bool,
-    ) -> Result<BlockResponse>;
+    ) -> Result<Option<BlockResponse>>;
+
+    /// Returns information about a block by hash, `null` if it's unknown. See also
+    /// [eth_getBlockByHash](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getblockbyhash).
+    #[rpc(name = "eth_getBlockByHash")]
+    fn get_block_by_hash(
+        &self,
+        block_hash: H256,
+        return_transaction_objects: bool,
+    ) -> Result<Option<BlockResponse>>;
+
+    /// See also [eth_getUncleByBlockHashAndIndex](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getunclebyblockhashandindex).
+    #[rpc(name = "eth_getUncleByBlockHashAndIndex")]
+    fn get_uncle_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U256,
+    ) -> Result<Option<BlockResponse>>;
+
+    /// See also [eth_getUncleByBlockNumberAndIndex](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getunclebyblocknumberandindex).
+    #[rpc(name = "eth_getUncleByBlockNumberAndIndex")]
+    fn get_uncle_by_block_number_and_index(
+        &self,
+        number: String,
+        index: U256,
+    ) -> Result<Option<BlockResponse>>;
+
+    /// See also [eth_getUncleCountByBlockHash](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getunclecountbyblockhash).
+    #[rpc(name = "eth_getUncleCountByBlockHash")]
+    fn get_uncle_count_by_block_hash(&self, block_hash: H256) -> Result<Option<U256>>;
+
+    /// See also [eth_getUncleCountByBlockNumber](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getunclecountbyblocknumber).
+    #[rpc(name = "eth_getUncleCountByBlockNumber")]
+    fn get_uncle_count_by_block_number(&self, number: String) -> Result<Option<U256>>;
 
-    /// Non-standard RPC method to the 'top' accounts by balance. 
+    /// Non-standard RPC method to get the 'top' accounts by balance (the leaderboard), ties ordered by
+    /// address: up to `limit` accounts (5 by default, at most 100) from position `offset` (0-based) on,
+    /// and the total number of ranked accounts. With `exclude_system`, the faucet account and the
+    /// beneficiary of the sealed blocks are left out.
     #[rpc(name = "teth_topAccounts")]
     fn top_accounts(
         &self,
         limit: Option<usize>,
         offset: Option<usize>,
-    ) -> Result<Vec<(Address, Wei)>>;
+        exclude_system: Option<bool>,
+    ) -> Result<LeaderboardPage>;
+
+    /// Non-standard RPC method to get ether (1 by default) from the faucet account of the node, in a
+    /// transaction whose hash is returned. Payouts are limited per address and client IP; refused
+    /// requests result in an error with the reason as data (see `FaucetError`). If the node requires it,
+    /// `signature` is the signature of a challenge of `teth_faucetChallenge` by the key of the address
+    /// (EIP-191, as made by `personal_sign`).
+    #[rpc(meta, name = "teth_faucet")]
+    fn faucet(
+        &self,
+        meta: Self::Metadata,
+        address: Address,
+        signature: Option<Bytes>,
+    ) -> Result<H256>;
+
+    /// Non-standard RPC method returning a message to sign for `teth_faucet`, valid for a few minutes.
+    #[rpc(name = "teth_faucetChallenge")]
+    fn faucet_challenge(&self, address: Address) -> Result<String>;
+
+    /// Non-standard RPC method to get the transactions the account sent or received, newest first, in
+    /// pages of 25 (`page` starts at 0, the default), and the total number of its transactions.
+    #[rpc(name = "teth_getAccountTransactions")]
+    fn get_account_transactions(
+        &self,
+        address: Address,
+        page: Option<usize>,
+    ) -> Result<AccountTransactions>;
+
+    /// Non-standard RPC method to get the balance of the account after each block that changed it
+    /// (since the node started), oldest first.
+    #[rpc(name = "teth_getAccountBalanceHistory")]
+    fn get_account_balance_history(&self, address: Address) -> Result<Vec<BalanceChange>>;
+
+    /// Non-standard RPC method to get summaries of the latest `count` blocks (at most 100), newest first.
+    #[rpc(name = "teth_getRecentBlocks")]
+    fn get_recent_blocks(&self, count: usize) -> Result<Vec<BlockSummary>>;
+
+    /// Returns the name and version of the client. See also
+    /// [web3_clientVersion](https://github.com/ethereum/wiki/wiki/JSON-RPC#web3_clientversion).
+    #[rpc(name = "web3_clientVersion")]
+    fn client_version(&self) -> Result<String>;
+
+    /// Returns the Keccak-256 hash of the data. See also
+    /// [web3_sha3](https://github.com/ethereum/wiki/wiki/JSON-RPC#web3_sha3).
+    #[rpc(name = "web3_sha3")]
+    fn sha3(&self, data: Bytes) -> Result<H256>;
+
+    /// Returns the network ID, which is the chain ID, as a decimal string. See also
+    /// [net_version](https://github.com/ethereum/wiki/wiki/JSON-RPC#net_version).
+    #[rpc(name = "net_version")]
+    fn net_version(&self) -> Result<String>;
+
+    /// Returns whether the node listens for peer connections, which it doesn't (there's no networking
+    /// yet). See also [net_listening](https://github.com/ethereum/wiki/wiki/JSON-RPC#net_listening).
+    #[rpc(name = "net_listening")]
+    fn net_listening(&self) -> Result<bool>;
+
+    /// Returns the number of connected peers. See also
+    /// [net_peerCount](https://github.com/ethereum/wiki/wiki/JSON-RPC#net_peercount).
+    #[rpc(name = "net_peerCount")]
+    fn net_peer_count(&self) -> Result<U256>;
 
-    /// Non-standard RPC method to get one Ether. 
-    /// Works only when the account has no balance.
-    #[rpc(name = "teth_faucet")]
-    fn faucet(&self, address: Address) -> Result<bool>;
+    /// Returns the version of the `eth` wire protocol. See also
+    /// [eth_protocolVersion](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_protocolversion).
+    #[rpc(name = "eth_protocolVersion")]
+    fn protocol_version(&self) -> Result<U256>;
 
+    /// Returns the progress of importing blocks (`startingBlock`, `currentBlock`, `highestBlock`), or
+    /// `false` if the node is up to date. See also
+    /// [eth_syncing](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_syncing).
     #[rpc(name = "eth_syncing")]
-    fn syncing(&self) -> Result<bool>;
+    fn syncing(&self) -> Result<SyncStatus>;
+
+    /// Returns the beneficiary of the blocks the node seals. See also
+    /// [eth_coinbase](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_coinbase).
+    #[rpc(name = "eth_coinbase")]
+    fn coinbase(&self) -> Result<Address>;
+
+    /// Returns whether the node seals blocks, which it does unless it's importing blocks. See also
+    /// [eth_mining](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_mining).
+    #[rpc(name = "eth_mining")]
+    fn mining(&self) -> Result<bool>;
+
+    /// Returns a suggestion for the gas price of legacy transactions: the base fee of the next block
+    /// plus `eth_maxPriorityFeePerGas`. See also
+    /// [eth_gasPrice](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gasprice).
+    #[rpc(name = "eth_gasPrice")]
+    fn gas_price(&self) -> Result<Wei>;
+
+    /// Returns the chain ID used for signing replay-protected transactions (EIP-155). See also
+    /// [eth_chainId](https://eips.ethereum.org/EIPS/eip-695).
+    #[rpc(name = "eth_chainId")]
+    fn chain_id(&self) -> Result<U256>;
+
+    /// Returns the base fees, gas usage and (optionally) priority fee percentiles of up to `block_count`
+    /// blocks, ending with `newest_block`. See also
+    /// [eth_feeHistory](https://github.com/ethereum/execution-apis).
+    #[rpc(name = "eth_feeHistory")]
+    fn fee_history(
+        &self,
+        block_count: U256,
+        newest_block: String,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> Result<FeeHistory>;
+
+    /// Returns a suggestion for the priority fee per gas of EIP-1559 transactions, based on the
+    /// priority fees paid in recent blocks.
+    #[rpc(name = "eth_maxPriorityFeePerGas")]
+    fn max_priority_fee_per_gas(&self) -> Result<Wei>;
+
+    /// Returns the addresses of the accounts the node signs transactions for. See also
+    /// [eth_accounts](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_accounts).
+    #[rpc(name = "eth_accounts")]
+    fn accounts(&self) -> Result<Vec<Address>>;
+
+    /// Submits a signed transaction (hex-encoded RLP, or the EIP-2718 envelope for typed transactions)
+    /// to the transaction pool and returns its hash. Rejected transactions result in an error with the
+    /// reason as data. See also
+    /// [eth_sendRawTransaction](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_sendrawtransaction).
+    #[rpc(name = "eth_sendRawTransaction")]
+    fn send_raw_transaction(&self, data: Bytes) -> Result<H256>;
+
+    /// Like `eth_sendRawTransaction`, but the node fills in missing fields and signs the transaction
+    /// with the key of the sender, which must be one of `eth_accounts`. Part of the `personal` module,
+    /// as it spends the funds of the node's accounts. See also
+    /// [eth_sendTransaction](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_sendtransaction).
+    #[rpc(name = "eth_sendTransaction")]
+    fn send_transaction(&self, request: TransactionRequest) -> Result<H256>;
+
+    /// Executes the transaction described by the request on (a copy of) the state of the given block
+    /// (default: "latest") without committing it, and returns its output. The `from` field isn't checked
+    /// against a signature. Transactions without any fee fields pay nothing for gas. Invalid
+    /// transactions result in an error with the reason as data. There's no EVM yet, so the output is
+    /// always empty and calls can't revert. See also
+    /// [eth_call](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_call).
+    #[rpc(name = "eth_call")]
+    fn call(&self, request: TransactionRequest, block: Option<BlockParameter>) -> Result<Bytes>;
+
+    /// Returns the lowest gas limit (at most the block gas limit or `gas`) with which the transaction
+    /// executes successfully, like `eth_call`. See also
+    /// [eth_estimateGas](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_estimategas).
+    #[rpc(name = "eth_estimateGas")]
+    fn estimate_gas(
+        &self,
+        request: TransactionRequest,
+        block: Option<BlockParameter>,
+    ) -> Result<U256>;
+
+    /// Returns a transaction of the chain or the transaction pool by its hash, `null` if unknown. See also
+    /// [eth_getTransactionByHash](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionbyhash).
+    #[rpc(name = "eth_getTransactionByHash")]
+    fn get_transaction_by_hash(&self, hash: H256) -> Result<Option<TransactionResponse>>;
+
+    /// See also [eth_getTransactionByBlockHashAndIndex](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionbyblockhashandindex).
+    #[rpc(name = "eth_getTransactionByBlockHashAndIndex")]
+    fn get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U256,
+    ) -> Result<Option<TransactionResponse>>;
 
-    // #[rpc(name = "eth_subscribe")]
-    // fn subscribe(&self, type_: String) -> Result<()>;
+    /// See also [eth_getTransactionByBlockNumberAndIndex](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionbyblocknumberandindex).
+    #[rpc(name = "eth_getTransactionByBlockNumberAndIndex")]
+    fn get_transaction_by_block_number_and_index(
+        &self,
+        number: String,
+        index: U256,
+    ) -> Result<Option<TransactionResponse>>;
+
+    /// Returns the receipt of a transaction, `null` while it's pending or if it's unknown. See also
+    /// [eth_getTransactionReceipt](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_gettransactionreceipt).
+    #[rpc(name = "eth_getTransactionReceipt")]
+    fn get_transaction_receipt(&self, hash: H256) -> Result<Option<ReceiptResponse>>;
+
+    /// See also [eth_getBlockTransactionCountByHash](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getblocktransactioncountbyhash).
+    #[rpc(name = "eth_getBlockTransactionCountByHash")]
+    fn get_block_transaction_count_by_hash(&self, block_hash: H256) -> Result<Option<U256>>;
+
+    /// See also [eth_getBlockTransactionCountByNumber](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getblocktransactioncountbynumber).
+    #[rpc(name = "eth_getBlockTransactionCountByNumber")]
+    fn get_block_transaction_count_by_number(&self, number: String) -> Result<Option<U256>>;
+
+    /// Returns the logs matching the filter. See also
+    /// [eth_getLogs](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getlogs).
+    #[rpc(name = "eth_getLogs")]
+    fn get_logs(&self, filter: LogFilter) -> Result<Vec<LogResponse>>;
+
+    /// Installs a filter for the logs of new blocks that match, for polling with `eth_getFilterChanges`.
+    /// Filters are uninstalled when they haven't been polled for 5 minutes. See also
+    /// [eth_newFilter](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_newfilter).
+    #[rpc(name = "eth_newFilter")]
+    fn new_filter(&self, filter: LogFilter) -> Result<U256>;
+
+    /// Installs a filter for the hashes of new blocks. See also
+    /// [eth_newBlockFilter](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_newblockfilter).
+    #[rpc(name = "eth_newBlockFilter")]
+    fn new_block_filter(&self) -> Result<U256>;
+
+    /// Installs a filter for the hashes of new pending transactions. See also
+    /// [eth_newPendingTransactionFilter](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_newpendingtransactionfilter).
+    #[rpc(name = "eth_newPendingTransactionFilter")]
+    fn new_pending_transaction_filter(&self) -> Result<U256>;
+
+    /// Returns what's new since the last poll of the filter: logs for log filters, hashes otherwise.
+    /// See also
+    /// [eth_getFilterChanges](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getfilterchanges).
+    #[rpc(name = "eth_getFilterChanges")]
+    fn get_filter_changes(&self, id: U256) -> Result<FilterChanges>;
+
+    /// Returns all logs matching a log filter. See also
+    /// [eth_getFilterLogs](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_getfilterlogs).
+    #[rpc(name = "eth_getFilterLogs")]
+    fn get_filter_logs(&self, id: U256) -> Result<Vec<LogResponse>>;
+
+    /// Removes a filter. Returns whether it existed. See also
+    /// [eth_uninstallFilter](https://github.com/ethereum/wiki/wiki/JSON-RPC#eth_uninstallfilter).
+    #[rpc(name = "eth_uninstallFilter")]
+    fn uninstall_filter(&self, id: U256) -> Result<bool>;
+
+    /// Returns the number of pending transactions (executable in the next block) and queued ones
+    /// (waiting for transactions with lower nonces) in the transaction pool. See also
+    /// [txpool_status](https://geth.ethereum.org/docs/interacting-with-geth/rpc/ns-txpool).
+    #[rpc(name = "txpool_status")]
+    fn txpool_status(&self) -> Result<TxpoolStatus>;
+
+    /// Returns the pending and queued transactions of the pool by sender and nonce. See also
+    /// [txpool_content](https://geth.ethereum.org/docs/interacting-with-geth/rpc/ns-txpool).
+    #[rpc(name = "txpool_content")]
+    fn txpool_content(&self) -> Result<TxpoolContent<TransactionResponse>>;
+
+    /// Like `txpool_content`, but with a textual summary of each transaction (recipient, value, gas
+    /// and gas price). See also
+    /// [txpool_inspect](https://geth.ethereum.org/docs/interacting-with-geth/rpc/ns-txpool).
+    #[rpc(name = "txpool_inspect")]
+    fn txpool_inspect(&self) -> Result<TxpoolContent<String>>;
+
+    /// Subscribes to notifications of the given kind (`logs` with an optional filter), which are sent
+    /// as `eth_subscription` messages until `eth_unsubscribe` or the end of the WebSocket session. See also
+    /// [eth_subscribe](https://geth.ethereum.org/docs/rpc/pubsub).
+    #[pubsub(subscription = "eth_subscription", subscribe, name = "eth_subscribe")]
+    fn subscribe(
+        &self,
+        meta: Self::Metadata,
+        subscriber: Subscriber<Value>,
+        kind: SubscriptionKind,
+        filter: Option<LogFilter>,
+    );
+
+    /// Cancels a subscription. Returns whether it existed.
+    #[pubsub(
+        subscription = "eth_subscription",
+        unsubscribe,
+        name = "eth_unsubscribe"
+    )]
+    fn unsubscribe(&self, meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool>;
 }
 
-/// See `Rpc` trait for method descriptions.
-pub struct RpcImpl {
-    world_state: Mutex<WorldState>,
-    block_chain: BlockChain,
+struct Subscription {
+    kind: SubscriptionKind,
+    /// Only for `SubscriptionKind::Logs`.
+    filter: LogFilter,
+    sink: Sink<Value>,
 }
 
-impl RpcImpl {
-    pub fn new(world_state: WorldState, block_chain: BlockChain) -> Self {
-        Self {
-            world_state: Mutex::new(world_state),
-            block_chain,
-        }
-    }
+/// The active `eth_subscribe` subscriptions.
+#[derive(Default)]
+pub struct Subscriptions {
+    last_id: u64,
+    active: HashMap<SubscriptionId, Subscription>,
 }
-impl Rpc for RpcImpl {
-    fn get_balance(&self, address: Address, _block: String) -> Result<Wei> {
-        let state = self.world_state.lock().unwrap();
-        let account = state.accounts.get(&address);
-        if let Some(account) = account {
-            Ok(account.balance)
-        } else {
-            Ok(0.into())
-        }
+
+impl Subscriptions {
+    /// Returns the ID of the new subscription, `None` if the session is already closed.
+    fn add(
+        &mut self,
+        kind: SubscriptionKind,
+        filter: LogFilter,
+        subscriber: Subscriber<Value>,
+    ) -> Option<SubscriptionId> {
+        self.last_id += 1;
+        let id = SubscriptionId::String(format!("{:#x}", self.last_id));
+        let sink = subscriber.assign_id(id.clone()).ok()?;
+        let subscription = Subscription { kind, filter, sink };
+        self.active.insert(id.clone(), subscription);
+        Some(id)
     }
 
-    fn get_block_by_number(
-        &self,
-        number: String,
-        return_transaction_objects: bool,
-    ) -> Result<BlockResponse> {
-        match number.as_ref() {
-            "earliest" => Err(Error::internal_error()), // not implemented yet
-            "latest" => {
-                let block = &self.block_chain.blocks[&self.block_chain.latest_block_hash];
-                Ok(BlockResponse::new(
-                    &block,
-                    &self.block_chain,
-                    return_transaction_objects,
-                ))
-            },
-            "pending" => Err(Error::internal_error()), // not implemented yet
-            _ => {
-                // TODO!!: deal with ommers with the same number...
-                let number = u64::from_str_radix(&number[2..], 16)
-                    .map_err(|_| Error::invalid_params("invalid block number"))?;
-                self.block_chain.blocks.values()
-                    .find_map(|block| {
-                        if block.header.number == number {
-                            Some(BlockResponse::new(&block, &self.block_chain,
-                                return_transaction_objects))
-                        } else {
-                            None
-                        }
-                    })
-                    .ok_or_else(|| Error::invalid_params("block not found"))
-            },         
-        }
+    fn remove(&mut self, id: &SubscriptionId) -> bool {
+        self.active.remove(id).is_some()
     }
 
-    fn top_accounts(
-        &self,
-        offset: Option<usize>,
-        limit: Option<usize>,
-    ) -> Result<Vec<(Address, Wei)>> {
-        let state = self.world_state.lock().unwrap();
-        let mut balances: Vec<(Address, Wei)> = state
-            .accounts
+    /// The notifications for the event, with the subscriptions and sinks to send them to.
+    fn pending(&self, node: &Node, event: &NodeEvent) -> Vec<PendingNotifications> {
+        self.active
             .iter()
-            .map(|(address, account)| (*address, account.balance))
-            .collect();
-        balances.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap()); // desc by balance
+            .map(|(id, subscription)| PendingNotifications {
+                id: id.clone(),
+                sink: subscription.sink.clone(),
+                notifications: notifications(node, event, subscription.kind, &subscription.filter),
+            })
+            .filter(|pending| !pending.notifications.is_empty())
+            .collect()
+    }
+}
 
-        let limit = limit.unwrap_or(5);
-        let offset = offset.unwrap_or(0);
+/// Notifications for a subscription, collected while the node is locked and sent after releasing it,
+/// as sending blocks until the client reads them.
+struct PendingNotifications {
+    id: SubscriptionId,
+    sink: Sink<Value>,
+    notifications: Vec<Value>,
+}
 
-        Ok(balances[offset..(offset + limit).min(balances.len())].to_vec())
+impl PendingNotifications {
+    /// Returns whether all notifications were sent, i.e. the session isn't gone.
+    fn send(self) -> bool {
+        let sink = self.sink;
+        self.notifications
+            .into_iter()
+            .all(|notification| sink.notify(Ok(notification)).wait().is_ok())
     }
+}
 
-    fn faucet(&self, address: Address) -> Result<bool> {
-        // TODO!: require signed message
-        let mut state = self.world_state.lock().unwrap();
-        let mut account = state
-            .accounts
-            .get(&address)
-            .cloned()
-            .unwrap_or_else(AccountState::default);
-
-        if account.balance == 0.into() {
-            // TODO!: make it a transaction...
-            account.balance = *ONE_ETHER;
-            state.accounts.insert(address, account);
-            Ok(true)
-        } else {
-            Err(Error::invalid_params("Account must be empty"))
+/// The notifications of a subscription of the given kind for the event.
+fn notifications(
+    node: &Node,
+    event: &NodeEvent,
+    kind: SubscriptionKind,
+    filter: &LogFilter,
+) -> Vec<Value> {
+    let block_chain = &node.block_chain;
+    match (kind, event) {
+        (SubscriptionKind::NewHeads, NodeEvent::NewHead(hash)) => block_chain
+            .blocks
+            .get(hash)
+            .map(|block| json!(BlockResponse::from_chain(block, block_chain, false)))
+            .into_iter()
+            .collect(),
+        (SubscriptionKind::Logs, NodeEvent::NewHead(hash)) => block_chain
+            .blocks
+            .get(hash)
+            .map_or_else(Vec::new, |block| block_logs(block_chain, block, filter))
+            .into_iter()
+            .map(|log| json!(log))
+            .collect(),
+        (SubscriptionKind::NewPendingTransactions, NodeEvent::NewPendingTransaction(hash)) => {
+            vec![json!(hash)]
         }
+        _ => vec![],
     }
+}
 
-    fn syncing(&self) -> Result<bool> {
-        Ok(false)
-    }
+/// What a filter of `eth_getFilterChanges` reports.
+enum FilterKind {
+    /// Logs of new blocks.
+    Logs(LogFilter),
+    /// Hashes of new blocks.
+    Blocks,
+    /// Hashes of new pending transactions, collected until the next poll.
+    PendingTransactions(Vec<H256>),
+}
 
-    // fn subscribe(&self, _type: String) -> Result<()> {
-    //     Err(Error::invalid_request())
-    // }
+struct Filter {
+    kind: FilterKind,
+    /// Number of the latest block when the filter was last polled.
+    last_block: u64,
+    last_poll: Instant,
 }
 
-pub fn start_websocket_server(world_state: WorldState, block_chain: BlockChain) {
-    let mut io = IoHandler::new();
-    let rpc = RpcImpl::new(world_state, block_chain);
-    io.extend_with(rpc.to_delegate());
+/// Result of `eth_getFilterChanges`.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum FilterChanges {
+    Hashes(Vec<H256>),
+    Logs(Vec<LogResponse>),
+}
 
-    let server = ServerBuilder::new(io)
-        .start(&"0.0.0.0:8546".parse().unwrap())
-        .expect("Server must start with no issues");
+/// The installed filters of `eth_newFilter` etc. Filters that aren't polled within the timeout are
+/// removed.
+pub struct Filters {
+    last_id: u64,
+    installed: HashMap<U256, Filter>,
+    timeout: Duration,
+}
 
-    server.wait().unwrap()
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            last_id: 0,
+            installed: HashMap::new(),
+            timeout: FILTER_TIMEOUT,
+        }
+    }
+}
+
+impl Filters {
+    fn install(&mut self, kind: FilterKind, latest_block: u64) -> U256 {
+        self.expire();
+        self.last_id += 1;
+        let id = U256::from(self.last_id);
+        let filter = Filter {
+            kind,
+            last_block: latest_block,
+            last_poll: Instant::now(),
+        };
+        self.installed.insert(id, filter);
+        id
+    }
+
+    fn uninstall(&mut self, id: &U256) -> bool {
+        self.expire();
+        self.installed.remove(id).is_some()
+    }
+
+    /// The filter with the given ID, which counts as being polled.
+    fn poll(&mut self, id: &U256) -> Result<&mut Filter> {
+        self.expire();
+        let filter = self
+            .installed
+            .get_mut(id)
+            .ok_or_else(|| Error::invalid_params("filter not found"))?;
+        filter.last_poll = Instant::now();
+        Ok(filter)
+    }
+
+    /// Collects new pending transactions for the pending transaction filters.
+    fn record(&mut self, event: &NodeEvent) {
+        if let NodeEvent::NewPendingTransaction(hash) = event {
+            for filter in self.installed.values_mut() {
+                if let FilterKind::PendingTransactions(hashes) = &mut filter.kind {
+                    hashes.push(*hash);
+                }
+            }
+        }
+    }
+
+    /// Moves filters that were last polled at a later block than the latest one (after `evm_revert`)
+    /// back to it, so that the blocks sealed from there on are reported.
+    fn rewind(&mut self, latest_block: u64) {
+        for filter in self.installed.values_mut() {
+            filter.last_block = filter.last_block.min(latest_block);
+        }
+    }
+
+    fn expire(&mut self) {
+        let timeout = self.timeout;
+        self.installed
+            .retain(|_, filter| filter.last_poll.elapsed() < timeout);
+    }
+}
+
+/// Metadata of a request: the session of the connection (for subscriptions over WebSocket and IPC)
+/// and the IP of the client if known (for the limits of `teth_faucet`). The servers don't expose the
+/// address of the connection, so the IP is only known behind a reverse proxy (see `forwarded_ip`).
+#[derive(Clone, Default)]
+pub struct RequestMeta {
+    session: Option<Arc<Session>>,
+    client_ip: Option<IpAddr>,
+}
+
+impl jsonrpc_ws_server::jsonrpc_core::Metadata for RequestMeta {}
+
+impl PubSubMetadata for RequestMeta {
+    fn session(&self) -> Option<Arc<Session>> {
+        self.session.clone()
+    }
+}
+
+impl RequestMeta {
+    fn with_session(session: Session, client_ip: Option<IpAddr>) -> Self {
+        Self {
+            session: Some(Arc::new(session)),
+            client_ip,
+        }
+    }
+
+    /// Metadata of an HTTP request, with the client IP reported by the reverse proxy if
+    /// `behind_proxy`.
+    fn from_http_request(request: &Request<Body>, behind_proxy: bool) -> Self {
+        let header = request.headers().get("x-forwarded-for");
+        Self {
+            session: None,
+            client_ip: forwarded_ip(header.and_then(|value| value.to_str().ok()))
+                .filter(|_| behind_proxy),
+        }
+    }
+}
+
+/// The client IP in the `X-Forwarded-For` header of a request forwarded by a reverse proxy: the last
+/// address, which the proxy appended. Earlier ones were sent by the client and can be spoofed.
+fn forwarded_ip(header: Option<&str>) -> Option<IpAddr> {
+    header?.rsplit(',').next()?.trim().parse().ok()
+}
+
+thread_local! {
+    /// The client IP of the WebSocket handshake being processed. The request middleware sees the
+    /// headers of the handshake and the meta extractor, which runs right after it on the same thread,
+    /// only the session.
+    static HANDSHAKE_CLIENT_IP: Cell<Option<IpAddr>> = const { Cell::new(None) };
+}
+
+/// See `Rpc` trait for method descriptions.
+#[derive(Clone)]
+pub struct RpcImpl {
+    node: Arc<Mutex<Node>>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    filters: Arc<Mutex<Filters>>,
+    /// Whether transactions without replay protection (EIP-155) are accepted.
+    allow_unprotected_txs: bool,
+    /// Whether a block is sealed right away for each submitted transaction.
+    instant_seal: bool,
+    /// Pays out `teth_faucet` requests, disabled if `None`.
+    faucet: Option<Arc<Mutex<Faucet>>>,
+    /// Whether the dev methods (`DevRpc`) are served.
+    dev: bool,
+    snapshots: Arc<Mutex<Snapshots>>,
+}
+
+impl RpcImpl {
+    pub fn new(node: Arc<Mutex<Node>>, allow_unprotected_txs: bool, instant_seal: bool) -> Self {
+        Self {
+            node,
+            subscriptions: Arc::new(Mutex::new(Subscriptions::default())),
+            filters: Arc::new(Mutex::new(Filters::default())),
+            allow_unprotected_txs,
+            instant_seal,
+            faucet: None,
+            dev: false,
+            snapshots: Arc::new(Mutex::new(Snapshots::default())),
+        }
+    }
+
+    /// Rejects transactions without replay protection, unless allowed by the node configuration.
+    fn check_replay_protection(&self, transaction: &Transaction) -> Result<()> {
+        if transaction.chain_id().is_none() && !self.allow_unprotected_txs {
+            return Err(pool_error(TransactionError::Unprotected.into()));
+        }
+        Ok(())
+    }
+
+    /// Adds the transaction to the pool of the node (and seals it into a block with `instant_seal`,
+    /// unless the node is importing blocks).
+    fn submit_transaction(&self, node: &mut Node, transaction: Transaction) -> Result<H256> {
+        self.check_replay_protection(&transaction)?;
+        let hash = node.submit_transaction(transaction).map_err(pool_error)?;
+        if self.instant_seal && node.sync.is_none() {
+            let timestamp = node.block_timestamp();
+            node.seal_block(timestamp).map_err(|e| Error {
+                code: ErrorCode::InternalError,
+                message: format!("could not seal block: {}", e),
+                data: None,
+            })?;
+        }
+        Ok(hash)
+    }
+
+    fn install_filter(&self, kind: FilterKind) -> U256 {
+        let node = self.node.lock().unwrap();
+        let latest_block = node.block_chain.latest_block().header.number;
+        self.filters.lock().unwrap().install(kind, latest_block)
+    }
+
+    /// Fills in the missing fields of the request and signs the transaction.
+    fn transaction_from_request(node: &Node, request: TransactionRequest) -> Result<Transaction> {
+        let from = request.from;
+        let secret_key = node
+            .accounts
+            .get(&from)
+            .ok_or_else(|| Error::invalid_params("unknown account"))?;
+        let header = node.pending_header();
+        let config = &node.block_chain.config;
+        let nonce = node.next_nonce(&from);
+        let mut transaction = unsigned_transaction(request, &header, config, nonce)?;
+        let legacy_chain_id = match transaction {
+            Transaction::Legacy(_) => config.eip155_chain_id(header.number),
+            _ => None,
+        };
+        transaction.sign(secret_key, legacy_chain_id);
+        Ok(transaction)
+    }
+}
+
+/// The (unsigned) transaction described by the request, for a block with the given header.
+/// Missing fees are derived from its base fee, `nonce` is used if the request has none.
+fn unsigned_transaction(
+    request: TransactionRequest,
+    header: &BlockHeader,
+    config: &ChainConfig,
+    nonce: U256,
+) -> Result<Transaction> {
+    let data = request.input.or(request.data).unwrap_or_default().0;
+    let base_fee = header.base_fee();
+    let chain_id = config.chain_id;
+    let access_list = request.access_list.unwrap_or_default();
+
+    let dynamic_fee = request.max_fee_per_gas.is_some()
+        || request.max_priority_fee_per_gas.is_some()
+        || (header.base_fee_per_gas.is_some() && request.gas_price.is_none());
+    let nonce = request.nonce.unwrap_or(nonce);
+    let to = request.to;
+    let value = request.value.unwrap_or_default();
+
+    let mut transaction: Transaction = if dynamic_fee {
+        let max_priority_fee_per_gas = request
+            .max_priority_fee_per_gas
+            .unwrap_or_else(|| DEFAULT_PRIORITY_FEE.into());
+        let max_fee_per_gas = request
+            .max_fee_per_gas
+            .unwrap_or_else(|| base_fee * 2u32 + max_priority_fee_per_gas);
+        DynamicFeeTransaction {
+            chain_id,
+            nonce,
+            max_priority_fee_per_gas,
+            max_fee_per_gas,
+            to,
+            value,
+            data,
+            access_list,
+            ..DynamicFeeTransaction::default()
+        }
+        .into()
+    } else {
+        let gas_price = request
+            .gas_price
+            .unwrap_or_else(|| base_fee + U256::from(DEFAULT_PRIORITY_FEE));
+        if access_list.is_empty() {
+            LegacyTransaction {
+                nonce,
+                gas_price,
+                to,
+                value,
+                data,
+                ..LegacyTransaction::default()
+            }
+            .into()
+        } else {
+            AccessListTransaction {
+                chain_id,
+                nonce,
+                gas_price,
+                to,
+                value,
+                data,
+                access_list,
+                ..AccessListTransaction::default()
+            }
+            .into()
+        }
+    };
+    let gas_limit = request
+        .gas
+        .unwrap_or_else(|| transaction.intrinsic_gas().into());
+    transaction.set_gas_limit(gas_limit);
+    Ok(transaction)
+}
+
+/// The unsigned transaction of an `eth_call`/`eth_estimateGas` request and the header and state of
+/// the block it's executed in. Without any fee fields, the gas price is 0 and the base fee is ignored.
+fn call_context(
+    node: &Node,
+    mut request: TransactionRequest,
+    block: &BlockParameter,
+) -> Result<(Transaction, BlockHeader, WorldState)> {
+    let mut header = resolve_block(&node.block_chain, block)?.header.clone();
+    let state = state_at(node, block)?.into_owned();
+    if request.gas_price.is_none()
+        && request.max_fee_per_gas.is_none()
+        && request.max_priority_fee_per_gas.is_none()
+    {
+        request.gas_price = Some(U256::zero());
+        if header.base_fee_per_gas.is_some() {
+            header.base_fee_per_gas = Some(U256::zero());
+        }
+    }
+    let gas_limit = request.gas.unwrap_or(header.gas_limit);
+    let nonce = state
+        .accounts
+        .get(&request.from)
+        .map_or_else(U256::zero, |account| account.nonce);
+    let mut transaction = unsigned_transaction(request, &header, &node.block_chain.config, nonce)?;
+    transaction.set_gas_limit(gas_limit);
+    Ok((transaction, header, state))
+}
+
+/// Validates and executes the transaction as if `from` had signed it, on a copy of `state`.
+fn simulate(
+    transaction: &Transaction,
+    from: Address,
+    state: &WorldState,
+    header: &BlockHeader,
+    config: &ChainConfig,
+) -> std::result::Result<TransactionOutcome, TransactionError> {
+    let mut state = state.clone();
+    transaction.validate_as(from, &state, header.gas_limit, header, config)?;
+    Ok(transaction.execute_as(from, &mut state, header))
+}
+
+/// Runs `execute` with the tracer selected by the options and returns its output.
+fn trace(
+    options: Option<TraceOptions>,
+    execute: impl FnOnce(&mut dyn Tracer),
+) -> Result<TraceResult> {
+    let options = options.unwrap_or_default();
+    match options.tracer.as_deref() {
+        None => {
+            let mut logger = StructLogger::new(options.struct_logger);
+            execute(&mut logger);
+            Ok(TraceResult::StructLogs(logger.into_result()))
+        }
+        Some("callTracer") => {
+            let mut tracer = CallTracer::default();
+            execute(&mut tracer);
+            tracer
+                .into_result()
+                .map(TraceResult::Calls)
+                .ok_or_else(Error::internal_error)
+        }
+        Some(tracer) => Err(Error::invalid_params(format!("unknown tracer: {}", tracer))),
+    }
+}
+
+/// JSON-RPC error for transactions that were not accepted, with the reason as data, e.g.
+/// `{"reason": "nonceTooLow", "details": {"expected": "0x1", "got": "0x0"}}`.
+fn pool_error(error: PoolError) -> Error {
+    let data = match &error {
+        PoolError::AlreadyKnown => json!({ "reason": "alreadyKnown" }),
+        PoolError::ReplacementUnderpriced => json!({ "reason": "replacementUnderpriced" }),
+        PoolError::PoolFull => json!({ "reason": "poolFull" }),
+        PoolError::SenderLimitExceeded => json!({ "reason": "senderLimitExceeded" }),
+        PoolError::NonceTooFarAhead { max, got } => json!({
+            "reason": "nonceTooFarAhead",
+            "details": { "max": max, "got": got }
+        }),
+        PoolError::Invalid(e) => return transaction_error(e),
+    };
+    Error {
+        code: ErrorCode::ServerError(-32000),
+        message: error.to_string(),
+        data: Some(data),
+    }
+}
+
+/// JSON-RPC error for refused faucet requests, with the reason as data (see `pool_error`).
+fn faucet_error(error: FaucetError) -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32000),
+        message: error.to_string(),
+        data: Some(serde_json::to_value(&error).unwrap_or_default()),
+    }
+}
+
+/// JSON-RPC error for invalid transactions, with the reason as data (see `pool_error`).
+fn transaction_error(error: &TransactionError) -> Error {
+    Error {
+        code: ErrorCode::ServerError(-32000),
+        message: error.to_string(),
+        data: Some(serde_json::to_value(error).unwrap_or_default()),
+    }
+}
+
+/// Resolves "latest", "earliest", "pending" (the latest block, except for `eth_getBlockByNumber`),
+/// "safe", "finalized" (the latest block as well, there are no reorgs) and hex numbers to a block of
+/// the canonical chain. `None` if there's no block with that number (yet).
+fn block_by_number<'a>(block_chain: &'a BlockChain, number: &str) -> Result<Option<&'a Block>> {
+    let latest = block_chain.latest_block();
+    let number = match number {
+        "latest" | "pending" | "safe" | "finalized" => return Ok(Some(latest)),
+        "earliest" => 0,
+        _ => u64::from_str_radix(number.trim_start_matches("0x"), 16)
+            .map_err(|_| Error::invalid_params("invalid block number"))?,
+    };
+    if number > latest.header.number {
+        return Ok(None);
+    }
+    Ok(Some(block_chain.canonical_blocks()[number as usize]))
+}
+
+/// The block the parameter refers to. Unknown blocks are an error.
+fn resolve_block<'a>(block_chain: &'a BlockChain, block: &BlockParameter) -> Result<&'a Block> {
+    match block {
+        BlockParameter::NumberOrTag(number)
+        | BlockParameter::Number {
+            block_number: number,
+        } => canonical_block(block_chain, number),
+        BlockParameter::Hash {
+            block_hash,
+            require_canonical,
+        } => {
+            let block = block_chain
+                .blocks
+                .get(block_hash)
+                .ok_or_else(|| Error::invalid_params("block not found"))?;
+            let canonical = block_chain
+                .canonical_blocks()
+                .get(block.header.number as usize)
+                .is_some_and(|canonical| canonical.header.hash() == *block_hash);
+            if *require_canonical && !canonical {
+                return Err(Error::invalid_params("block is not canonical"));
+            }
+            Ok(block)
+        }
+    }
+}
+
+/// The post-state of the block the parameter refers to ("pending" is the latest state).
+fn state_at<'a>(node: &'a Node, block: &BlockParameter) -> Result<Cow<'a, WorldState>> {
+    let block_chain = &node.block_chain;
+    let header = &resolve_block(block_chain, block)?.header;
+    if header.hash() == block_chain.latest_block_hash {
+        return Ok(Cow::Borrowed(&node.state));
+    }
+    block_chain.state_at(header).ok_or_else(|| {
+        Error::invalid_params(format!("state of block {} is not available", header.number))
+    })
+}
+
+/// The number of the block `block_by_number` resolves to. Numbers of future blocks are returned as is.
+fn block_number(block_chain: &BlockChain, number: &str) -> Result<u64> {
+    match block_by_number(block_chain, number)? {
+        Some(block) => Ok(block.header.number),
+        None => Ok(u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap_or_default()),
+    }
+}
+
+/// The logs matching the filter of the canonical blocks with numbers from `from` to `to` (inclusive).
+/// Blocks whose bloom shows that they contain no matching logs are skipped.
+fn logs_in_range(
+    block_chain: &BlockChain,
+    filter: &LogFilter,
+    from: u64,
+    to: u64,
+) -> Vec<LogResponse> {
+    let blocks = block_chain.canonical_blocks();
+    let to = to.min(blocks.len() as u64 - 1);
+    if from > to {
+        return vec![];
+    }
+    blocks[from as usize..=to as usize]
+        .iter()
+        .filter(|block| filter.may_match(&block.header.logs_bloom))
+        .flat_map(|block| block_logs(block_chain, block, filter))
+        .collect()
+}
+
+/// The logs matching the filter, from the block given by hash or the range of blocks.
+fn filter_logs(block_chain: &BlockChain, filter: &LogFilter) -> Result<Vec<LogResponse>> {
+    if let Some(block_hash) = filter.block_hash {
+        if filter.from_block.is_some() || filter.to_block.is_some() {
+            return Err(Error::invalid_params(
+                "blockHash can't be combined with fromBlock/toBlock",
+            ));
+        }
+        let block = block_chain
+            .blocks
+            .get(&block_hash)
+            .ok_or_else(|| Error::invalid_params("block not found"))?;
+        if !filter.may_match(&block.header.logs_bloom) {
+            return Ok(vec![]);
+        }
+        return Ok(block_logs(block_chain, block, filter));
+    }
+    let number_or_latest = |number: &Option<String>| {
+        let number = number.as_ref().map_or("latest", String::as_str);
+        block_number(block_chain, number)
+    };
+    let from = number_or_latest(&filter.from_block)?;
+    let to = number_or_latest(&filter.to_block)?;
+    Ok(logs_in_range(block_chain, filter, from, to))
+}
+
+/// Like `block_by_number`, but unknown blocks are an error.
+fn canonical_block<'a>(block_chain: &'a BlockChain, number: &str) -> Result<&'a Block> {
+    block_by_number(block_chain, number)?.ok_or_else(|| Error::invalid_params("block not found"))
+}
+
+/// The transaction at `index` of the block, `None` if it has fewer transactions.
+fn transaction_at(block: &Block, index: U256) -> Option<TransactionResponse> {
+    if index >= U256::from(block.transactions.len()) {
+        return None;
+    }
+    let transaction = &block.transactions[index.low_u64() as usize];
+    Some(TransactionResponse::new(transaction, Some(block)))
+}
+
+/// The logs of the block that match the filter.
+fn block_logs(block_chain: &BlockChain, block: &Block, filter: &LogFilter) -> Vec<LogResponse> {
+    let receipts = block_chain
+        .receipts
+        .get(&block.header.hash())
+        .map_or(&[][..], Vec::as_slice);
+    receipts
+        .iter()
+        .enumerate()
+        .flat_map(|(transaction_index, receipt)| {
+            receipt.logs.iter().map(move |log| (transaction_index, log))
+        })
+        .enumerate()
+        .filter(|(_, (_, log))| filter.matches(log))
+        .map(|(log_index, (transaction_index, log))| {
+            LogResponse::new(log, block, transaction_index, log_index)
+        })
+        .collect()
+}
+
+/// The uncle at `index` of the block, `None` if it has fewer uncles.
+fn uncle_at(block: &Block, index: U256) -> Option<BlockResponse> {
+    if index >= U256::from(block.ommers.len()) {
+        return None;
+    }
+    let ommer = &block.ommers[index.low_u64() as usize];
+    Some(BlockResponse::uncle(ommer))
+}
+
+/// The priority fee paid by each transaction of the block (in ascending order), with the gas it used.
+fn priority_fees(block_chain: &BlockChain, block: &Block) -> Vec<(Wei, U256)> {
+    let base_fee = block.header.base_fee();
+    let receipts = block_chain
+        .receipts
+        .get(&block.header.hash())
+        .map_or(&[][..], Vec::as_slice);
+    let mut previous_cumulative_gas_used = U256::zero();
+    let mut fees: Vec<(Wei, U256)> = block
+        .transactions
+        .iter()
+        .zip(receipts)
+        .map(|(transaction, receipt)| {
+            let gas_used = receipt.cumulative_gas_used - previous_cumulative_gas_used;
+            previous_cumulative_gas_used = receipt.cumulative_gas_used;
+            let priority_fee = transaction
+                .effective_gas_price(base_fee)
+                .saturating_sub(base_fee);
+            (priority_fee, gas_used)
+        })
+        .collect();
+    fees.sort();
+    fees
+}
+
+/// A percentile of the priority fees paid in the recent blocks.
+fn suggested_priority_fee(block_chain: &BlockChain) -> Wei {
+    let mut fees: Vec<Wei> = block_chain
+        .canonical_blocks()
+        .iter()
+        .rev()
+        .take(PRIORITY_FEE_BLOCKS)
+        .flat_map(|block| priority_fees(block_chain, block))
+        .map(|(fee, _)| fee)
+        .collect();
+    if fees.is_empty() {
+        return DEFAULT_PRIORITY_FEE.into();
+    }
+    fees.sort();
+    fees[(fees.len() - 1) * PRIORITY_FEE_PERCENTILE / 100]
+}
+
+impl Rpc for RpcImpl {
+    type Metadata = RequestMeta;
+
+    fn block_number(&self) -> Result<U256> {
+        let node = self.node.lock().unwrap();
+        Ok(node.block_chain.latest_block().header.number.into())
+    }
+
+    fn get_balance(&self, address: Address, block: Option<BlockParameter>) -> Result<Wei> {
+        let node = self.node.lock().unwrap();
+        let state = state_at(&node, &block.unwrap_or_default())?;
+        let account = state.accounts.get(&address);
+        if let Some(account) = account {
+            Ok(account.balance)
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    fn get_transaction_count(
+        &self,
+        address: Address,
+        block: Option<BlockParameter>,
+    ) -> Result<U256> {
+        let node = self.node.lock().unwrap();
+        let block = block.unwrap_or_default();
+        if block.is_pending() {
+            return Ok(node.next_nonce(&address));
+        }
+        let state = state_at(&node, &block)?;
+        Ok(state
+            .accounts
+            .get(&address)
+            .map_or_else(U256::zero, |account| account.nonce))
+    }
+
+    fn get_code(&self, address: Address, block: Option<BlockParameter>) -> Result<Bytes> {
+        let node = self.node.lock().unwrap();
+        Ok(state_at(&node, &block.unwrap_or_default())?
+            .code_at(&address)
+            .into())
+    }
+
+    fn get_storage_at(
+        &self,
+        address: Address,
+        position: U256,
+        block: Option<BlockParameter>,
+    ) -> Result<H256> {
+        let node = self.node.lock().unwrap();
+        let state = state_at(&node, &block.unwrap_or_default())?;
+        Ok(state.storage_at(&address, &position.into()).into())
+    }
+
+    fn get_block_by_number(
+        &self,
+        number: String,
+        return_transaction_objects: bool,
+    ) -> Result<Option<BlockResponse>> {
+        let node = self.node.lock().unwrap();
+        if number == "pending" {
+            let block = node.pending_block();
+            return Ok(Some(BlockResponse::pending(
+                &block,
+                return_transaction_objects,
+            )));
+        }
+        let block_chain = &node.block_chain;
+        Ok(block_by_number(block_chain, &number)?
+            .map(|block| BlockResponse::from_chain(block, block_chain, return_transaction_objects)))
+    }
+
+    fn get_block_by_hash(
+        &self,
+        block_hash: H256,
+        return_transaction_objects: bool,
+    ) -> Result<Option<BlockResponse>> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        Ok(block_chain
+            .blocks
+            .get(&block_hash)
+            .map(|block| BlockResponse::from_chain(block, block_chain, return_transaction_objects)))
+    }
+
+    fn get_uncle_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U256,
+    ) -> Result<Option<BlockResponse>> {
+        let node = self.node.lock().unwrap();
+        Ok(node
+            .block_chain
+            .blocks
+            .get(&block_hash)
+            .and_then(|block| uncle_at(block, index)))
+    }
+
+    fn get_uncle_by_block_number_and_index(
+        &self,
+        number: String,
+        index: U256,
+    ) -> Result<Option<BlockResponse>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?.and_then(|block| uncle_at(block, index)))
+    }
+
+    fn get_uncle_count_by_block_hash(&self, block_hash: H256) -> Result<Option<U256>> {
+        let node = self.node.lock().unwrap();
+        Ok(node
+            .block_chain
+            .blocks
+            .get(&block_hash)
+            .map(|block| block.ommers.len().into()))
+    }
+
+    fn get_uncle_count_by_block_number(&self, number: String) -> Result<Option<U256>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?.map(|block| block.ommers.len().into()))
+    }
+
+    fn top_accounts(
+        &self,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        exclude_system: Option<bool>,
+    ) -> Result<LeaderboardPage> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit > MAX_PAGE_SIZE {
+            return Err(Error::invalid_params(format!(
+                "limit must be at most {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        let mut excluded = HashSet::new();
+        if exclude_system.unwrap_or(false) {
+            if let Some(faucet) = &self.faucet {
+                excluded.insert(faucet.lock().unwrap().address);
+            }
+            excluded.insert(self.node.lock().unwrap().beneficiary);
+        }
+
+        let node = self.node.lock().unwrap();
+        let leaderboard = &node.leaderboard;
+        let accounts = leaderboard.page(offset.unwrap_or(0), limit, &excluded);
+        Ok(LeaderboardPage {
+            total: leaderboard.len(&excluded),
+            accounts: accounts.into_iter().map(LeaderboardEntry::from).collect(),
+        })
+    }
+
+    fn faucet(
+        &self,
+        meta: Self::Metadata,
+        address: Address,
+        signature: Option<Bytes>,
+    ) -> Result<H256> {
+        let faucet = self.faucet.as_ref();
+        let mut faucet = faucet
+            .ok_or_else(|| faucet_error(FaucetError::Disabled))?
+            .lock()
+            .unwrap();
+        let time = Instant::now();
+        let signature = signature.as_ref().map(|signature| &signature.0[..]);
+        faucet
+            .authorize(address, meta.client_ip, signature, time)
+            .map_err(faucet_error)?;
+
+        let mut node = self.node.lock().unwrap();
+        let header = node.pending_header();
+        let gas_price = header.base_fee() + suggested_priority_fee(&node.block_chain);
+        let chain_id = node.block_chain.config.eip155_chain_id(header.number);
+        let nonce = node.next_nonce(&faucet.address);
+        let transaction = faucet.payout(address, nonce, gas_price, chain_id);
+        let hash = self.submit_transaction(&mut node, transaction)?;
+        faucet.record(address, meta.client_ip, time);
+        Ok(hash)
+    }
+
+    fn faucet_challenge(&self, address: Address) -> Result<String> {
+        let faucet = self.faucet.as_ref();
+        let mut faucet = faucet
+            .ok_or_else(|| faucet_error(FaucetError::Disabled))?
+            .lock()
+            .unwrap();
+        Ok(faucet.challenge(address, Instant::now()))
+    }
+
+    fn get_account_transactions(
+        &self,
+        address: Address,
+        page: Option<usize>,
+    ) -> Result<AccountTransactions> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let hashes = node.history.transactions(&address);
+        let skipped = page
+            .unwrap_or(0)
+            .saturating_mul(ACCOUNT_TRANSACTIONS_PAGE_SIZE);
+        let transactions = hashes
+            .iter()
+            .rev()
+            .skip(skipped)
+            .take(ACCOUNT_TRANSACTIONS_PAGE_SIZE)
+            .filter_map(|hash| {
+                let (block, index) = block_chain.transaction_location(hash)?;
+                let receipts = block_chain.receipts.get(&block.header.hash())?;
+                Some(AccountTransaction {
+                    transaction: TransactionResponse::new(&block.transactions[index], Some(block)),
+                    timestamp: block.header.timestamp,
+                    status: receipts[index].status_code as u8,
+                })
+            })
+            .collect();
+        Ok(AccountTransactions {
+            total: hashes.len(),
+            transactions,
+        })
+    }
+
+    fn get_account_balance_history(&self, address: Address) -> Result<Vec<BalanceChange>> {
+        let node = self.node.lock().unwrap();
+        let balances = node.history.balances(&address);
+        Ok(balances
+            .iter()
+            .map(|(block_number, balance)| BalanceChange {
+                block_number: *block_number,
+                balance: *balance,
+            })
+            .collect())
+    }
+
+    fn get_recent_blocks(&self, count: usize) -> Result<Vec<BlockSummary>> {
+        if count > MAX_RECENT_BLOCKS {
+            return Err(Error::invalid_params(format!(
+                "count must be at most {}",
+                MAX_RECENT_BLOCKS
+            )));
+        }
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let mut summaries = vec![];
+        let mut block = block_chain.latest_block();
+        while summaries.len() < count {
+            summaries.push(BlockSummary::new(block));
+            match block_chain.blocks.get(&block.header.parent_hash) {
+                Some(parent) => block = parent,
+                None => break,
+            }
+        }
+        Ok(summaries)
+    }
+
+    fn client_version(&self) -> Result<String> {
+        Ok(CLIENT_VERSION.to_string())
+    }
+
+    fn sha3(&self, data: Bytes) -> Result<H256> {
+        Ok(keccak256(&data.0).into())
+    }
+
+    fn net_version(&self) -> Result<String> {
+        let node = self.node.lock().unwrap();
+        Ok(node.block_chain.config.chain_id.to_string())
+    }
+
+    fn net_listening(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn net_peer_count(&self) -> Result<U256> {
+        Ok(0.into())
+    }
+
+    fn protocol_version(&self) -> Result<U256> {
+        Ok(PROTOCOL_VERSION.into())
+    }
+
+    fn syncing(&self) -> Result<SyncStatus> {
+        let node = self.node.lock().unwrap();
+        Ok(match node.sync {
+            Some(progress) => SyncStatus::Syncing(SyncInfo {
+                starting_block: progress.starting_block,
+                current_block: node.block_chain.latest_block().header.number,
+                highest_block: progress.highest_block,
+            }),
+            None => SyncStatus::NotSyncing(false),
+        })
+    }
+
+    fn coinbase(&self) -> Result<Address> {
+        Ok(self.node.lock().unwrap().beneficiary)
+    }
+
+    fn mining(&self) -> Result<bool> {
+        Ok(self.node.lock().unwrap().sync.is_none())
+    }
+
+    fn gas_price(&self) -> Result<Wei> {
+        let node = self.node.lock().unwrap();
+        let base_fee = node.pending_header().base_fee();
+        Ok(base_fee + suggested_priority_fee(&node.block_chain))
+    }
+
+    fn chain_id(&self) -> Result<U256> {
+        Ok(self.node.lock().unwrap().block_chain.config.chain_id.into())
+    }
+
+    fn fee_history(
+        &self,
+        block_count: U256,
+        newest_block: String,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> Result<FeeHistory> {
+        if let Some(percentiles) = &reward_percentiles {
+            let in_range = percentiles.iter().all(|p| *p >= 0.0 && *p <= 100.0);
+            let ascending = percentiles.windows(2).all(|w| w[0] <= w[1]);
+            if !in_range || !ascending {
+                return Err(Error::invalid_params(
+                    "reward percentiles must be ascending and between 0 and 100",
+                ));
+            }
+        }
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let newest = canonical_block(block_chain, &newest_block)?.header.number;
+        let block_count = block_count.min(MAX_FEE_HISTORY_BLOCKS.into()).as_u64();
+        if block_count == 0 {
+            return Ok(FeeHistory::default());
+        }
+        let oldest = (newest + 1).saturating_sub(block_count);
+        let blocks = block_chain.canonical_blocks();
+        let blocks = &blocks[oldest as usize..=newest as usize];
+
+        let mut history = FeeHistory {
+            oldest_block: oldest,
+            reward: reward_percentiles.as_ref().map(|_| vec![]),
+            ..FeeHistory::default()
+        };
+        for block in blocks {
+            let header = &block.header;
+            history.base_fee_per_gas.push(header.base_fee());
+            // a zero gas limit would give NaN, which isn't valid JSON
+            let gas_used_ratio = if header.gas_limit.is_zero() {
+                0.0
+            } else {
+                header.gas_used.low_u64() as f64 / header.gas_limit.low_u64() as f64
+            };
+            history.gas_used_ratio.push(gas_used_ratio);
+
+            if let (Some(percentiles), Some(reward)) = (&reward_percentiles, &mut history.reward) {
+                let fees = priority_fees(block_chain, block);
+                let mut rewards = Vec::with_capacity(percentiles.len());
+                let mut index = 0;
+                let mut sum_gas_used = fees.first().map_or(0, |f| f.1.low_u64());
+                for percentile in percentiles {
+                    if fees.is_empty() {
+                        rewards.push(Wei::zero());
+                        continue;
+                    }
+                    let threshold = header.gas_used.low_u64() as f64 * percentile / 100.0;
+                    while (sum_gas_used as f64) < threshold && index < fees.len() - 1 {
+                        index += 1;
+                        sum_gas_used += fees[index].1.low_u64();
+                    }
+                    rewards.push(fees[index].0);
+                }
+                reward.push(rewards);
+            }
+        }
+        let newest_header = &blocks[blocks.len() - 1].header;
+        history.base_fee_per_gas.push(
+            BlockHeader::expected_base_fee(newest_header, &block_chain.config).unwrap_or_default(),
+        );
+        Ok(history)
+    }
+
+    fn max_priority_fee_per_gas(&self) -> Result<Wei> {
+        let node = self.node.lock().unwrap();
+        Ok(suggested_priority_fee(&node.block_chain))
+    }
+
+    fn accounts(&self) -> Result<Vec<Address>> {
+        let node = self.node.lock().unwrap();
+        let mut accounts: Vec<Address> = node.accounts.keys().cloned().collect();
+        accounts.sort();
+        Ok(accounts)
+    }
+
+    fn send_raw_transaction(&self, data: Bytes) -> Result<H256> {
+        let transaction = Transaction::from_rlp(&data.0)
+            .map_err(|e| Error::invalid_params(format!("invalid transaction: {}", e)))?;
+        let mut node = self.node.lock().unwrap();
+        self.submit_transaction(&mut node, transaction)
+    }
+
+    fn send_transaction(&self, request: TransactionRequest) -> Result<H256> {
+        let mut node = self.node.lock().unwrap();
+        let transaction = Self::transaction_from_request(&node, request)?;
+        self.submit_transaction(&mut node, transaction)
+    }
+
+    fn call(&self, request: TransactionRequest, block: Option<BlockParameter>) -> Result<Bytes> {
+        let node = self.node.lock().unwrap();
+        let from = request.from;
+        let (transaction, header, state) =
+            call_context(&node, request, &block.unwrap_or_default())?;
+        let config = &node.block_chain.config;
+        simulate(&transaction, from, &state, &header, config)
+            .map(|outcome| outcome.output.into())
+            .map_err(|e| transaction_error(&e))
+    }
+
+    fn estimate_gas(
+        &self,
+        request: TransactionRequest,
+        block: Option<BlockParameter>,
+    ) -> Result<U256> {
+        let node = self.node.lock().unwrap();
+        let from = request.from;
+        let (mut transaction, header, state) =
+            call_context(&node, request, &block.unwrap_or_default())?;
+        let config = &node.block_chain.config;
+
+        // binary search for the lowest gas limit that succeeds: `low` fails, `high` succeeds
+        let mut high = transaction.gas_limit().min(header.gas_limit);
+        let mut low = U256::from(transaction.intrinsic_gas()) - 1;
+        transaction.set_gas_limit(high);
+        simulate(&transaction, from, &state, &header, config).map_err(|e| transaction_error(&e))?;
+        while low + 1 < high {
+            let middle = (low + high) / 2;
+            transaction.set_gas_limit(middle);
+            if simulate(&transaction, from, &state, &header, config).is_ok() {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        Ok(high)
+    }
+
+    fn get_transaction_by_hash(&self, hash: H256) -> Result<Option<TransactionResponse>> {
+        let node = self.node.lock().unwrap();
+        if let Some((block, index)) = node.block_chain.transaction_location(&hash) {
+            return Ok(Some(TransactionResponse::new(
+                &block.transactions[index],
+                Some(block),
+            )));
+        }
+        Ok(node
+            .pool
+            .get(&hash)
+            .map(|transaction| TransactionResponse::new(transaction, None)))
+    }
+
+    fn get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U256,
+    ) -> Result<Option<TransactionResponse>> {
+        let node = self.node.lock().unwrap();
+        Ok(node
+            .block_chain
+            .blocks
+            .get(&block_hash)
+            .and_then(|block| transaction_at(block, index)))
+    }
+
+    fn get_transaction_by_block_number_and_index(
+        &self,
+        number: String,
+        index: U256,
+    ) -> Result<Option<TransactionResponse>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?
+            .and_then(|block| transaction_at(block, index)))
+    }
+
+    fn get_transaction_receipt(&self, hash: H256) -> Result<Option<ReceiptResponse>> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        Ok(block_chain
+            .transaction_location(&hash)
+            .map(|(block, index)| {
+                let receipts = &block_chain.receipts[&block.header.hash()];
+                ReceiptResponse::new(block, receipts, index)
+            }))
+    }
+
+    fn get_block_transaction_count_by_hash(&self, block_hash: H256) -> Result<Option<U256>> {
+        let node = self.node.lock().unwrap();
+        Ok(node
+            .block_chain
+            .blocks
+            .get(&block_hash)
+            .map(|block| block.transactions.len().into()))
+    }
+
+    fn get_block_transaction_count_by_number(&self, number: String) -> Result<Option<U256>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?
+            .map(|block| block.transactions.len().into()))
+    }
+
+    fn get_logs(&self, filter: LogFilter) -> Result<Vec<LogResponse>> {
+        let node = self.node.lock().unwrap();
+        filter_logs(&node.block_chain, &filter)
+    }
+
+    fn new_filter(&self, filter: LogFilter) -> Result<U256> {
+        Ok(self.install_filter(FilterKind::Logs(filter)))
+    }
+
+    fn new_block_filter(&self) -> Result<U256> {
+        Ok(self.install_filter(FilterKind::Blocks))
+    }
+
+    fn new_pending_transaction_filter(&self) -> Result<U256> {
+        Ok(self.install_filter(FilterKind::PendingTransactions(vec![])))
+    }
+
+    fn get_filter_changes(&self, id: U256) -> Result<FilterChanges> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let latest_block = block_chain.latest_block().header.number;
+        let mut filters = self.filters.lock().unwrap();
+        let filter = filters.poll(&id)?;
+        let first_new_block = filter.last_block + 1;
+        filter.last_block = latest_block;
+        let changes = match &mut filter.kind {
+            FilterKind::Logs(log_filter) => {
+                let from = match &log_filter.from_block {
+                    Some(from) => block_number(block_chain, from)?.max(first_new_block),
+                    None => first_new_block,
+                };
+                let to = match &log_filter.to_block {
+                    Some(to) => block_number(block_chain, to)?,
+                    None => latest_block,
+                };
+                FilterChanges::Logs(logs_in_range(block_chain, log_filter, from, to))
+            }
+            FilterKind::Blocks => {
+                // the chain may have become shorter since the last poll
+                let blocks = block_chain.canonical_blocks();
+                let first_new_block = (first_new_block as usize).min(blocks.len());
+                FilterChanges::Hashes(
+                    blocks[first_new_block..]
+                        .iter()
+                        .map(|block| block.hash())
+                        .collect(),
+                )
+            }
+            FilterKind::PendingTransactions(hashes) => {
+                FilterChanges::Hashes(std::mem::take(hashes))
+            }
+        };
+        Ok(changes)
+    }
+
+    fn get_filter_logs(&self, id: U256) -> Result<Vec<LogResponse>> {
+        let node = self.node.lock().unwrap();
+        let mut filters = self.filters.lock().unwrap();
+        match &filters.poll(&id)?.kind {
+            FilterKind::Logs(filter) => filter_logs(&node.block_chain, filter),
+            _ => Err(Error::invalid_params("not a log filter")),
+        }
+    }
+
+    fn uninstall_filter(&self, id: U256) -> Result<bool> {
+        Ok(self.filters.lock().unwrap().uninstall(&id))
+    }
+
+    fn txpool_status(&self) -> Result<TxpoolStatus> {
+        let node = self.node.lock().unwrap();
+        Ok(TxpoolStatus {
+            pending: node.pool.pending_count(),
+            queued: node.pool.queued_count(),
+        })
+    }
+
+    fn txpool_content(&self) -> Result<TxpoolContent<TransactionResponse>> {
+        let node = self.node.lock().unwrap();
+        Ok(TxpoolContent::new(&node.pool, |transaction| {
+            TransactionResponse::new(transaction, None)
+        }))
+    }
+
+    fn txpool_inspect(&self) -> Result<TxpoolContent<String>> {
+        let node = self.node.lock().unwrap();
+        Ok(TxpoolContent::new(&node.pool, |transaction| {
+            let to = match transaction.to() {
+                Some(to) => to_hex(&to),
+                None => "contract creation".to_string(),
+            };
+            format!(
+                "{}: {} wei + {} gas × {} wei",
+                to,
+                transaction.value(),
+                transaction.gas_limit(),
+                transaction.gas_price()
+            )
+        }))
+    }
+
+    fn subscribe(
+        &self,
+        meta: Self::Metadata,
+        subscriber: Subscriber<Value>,
+        kind: SubscriptionKind,
+        filter: Option<LogFilter>,
+    ) {
+        let id = {
+            let mut subscriptions = self.subscriptions.lock().unwrap();
+            subscriptions.add(kind, filter.unwrap_or_default(), subscriber)
+        };
+        // not locked anymore, as the callback runs here if this was the last session reference
+        if let (Some(id), Some(session)) = (id, meta.session) {
+            let subscriptions = self.subscriptions.clone();
+            session.on_drop(move || {
+                subscriptions.lock().unwrap().remove(&id);
+            });
+        }
+    }
+
+    fn unsubscribe(&self, _meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+        Ok(self.subscriptions.lock().unwrap().remove(&id))
+    }
+}
+
+/// Result of `admin_nodeInfo`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    name: String,
+    chain_id: U256,
+    genesis: H256,
+    head: H256,
+    #[serde(with = "quantity")]
+    head_number: u64,
+}
+
+/// Options of `debug_traceTransaction` and `debug_traceCall` as in geth: the tracer (`callTracer`,
+/// the struct logger by default) and what the struct logs include.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceOptions {
+    tracer: Option<String>,
+    #[serde(flatten)]
+    struct_logger: StructLoggerConfig,
+}
+
+/// Result of `debug_traceTransaction` and `debug_traceCall`, the output of the tracer.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum TraceResult {
+    StructLogs(ExecutionResult),
+    Calls(CallFrame),
+}
+
+/// Privileged RPC methods for local administration and debugging (the `admin` and `debug` modules),
+/// by default only served over IPC.
+#[rpc]
+pub trait AdminRpc {
+    /// Client version, chain and head of the node.
+    #[rpc(name = "admin_nodeInfo")]
+    fn node_info(&self) -> Result<NodeInfo>;
+
+    /// Data directory of the node, `null` if the chain is only kept in memory.
+    #[rpc(name = "admin_datadir")]
+    fn datadir(&self) -> Result<Option<String>>;
+
+    /// Writes the canonical chain to a file on the node's host, like `teth export`.
+    #[rpc(name = "admin_exportChain")]
+    fn export_chain(&self, file: String) -> Result<bool>;
+
+    /// RLP-encoded header of a block, `null` if it's unknown.
+    #[rpc(name = "debug_getRawHeader")]
+    fn get_raw_header(&self, number: String) -> Result<Option<Bytes>>;
+
+    /// RLP-encoded block, `null` if it's unknown.
+    #[rpc(name = "debug_getRawBlock")]
+    fn get_raw_block(&self, number: String) -> Result<Option<Bytes>>;
+
+    /// RLP-encoded (signed) transaction of a block or the pool, `null` if it's unknown.
+    #[rpc(name = "debug_getRawTransaction")]
+    fn get_raw_transaction(&self, hash: H256) -> Result<Option<Bytes>>;
+
+    /// RLP-encoded receipts of a block, `null` if it's unknown.
+    #[rpc(name = "debug_getRawReceipts")]
+    fn get_raw_receipts(&self, number: String) -> Result<Option<Vec<Bytes>>>;
+
+    /// Replays a transaction of the chain (after the ones before it in its block) with the tracer of
+    /// the options. See also
+    /// [debug_traceTransaction](https://geth.ethereum.org/docs/rpc/ns-debug#debug_tracetransaction).
+    #[rpc(name = "debug_traceTransaction")]
+    fn trace_transaction(&self, hash: H256, options: Option<TraceOptions>) -> Result<TraceResult>;
+
+    /// Executes a call like `eth_call` with the tracer of the options.
+    #[rpc(name = "debug_traceCall")]
+    fn trace_call(
+        &self,
+        request: TransactionRequest,
+        block: Option<BlockParameter>,
+        options: Option<TraceOptions>,
+    ) -> Result<TraceResult>;
+}
+
+impl AdminRpc for RpcImpl {
+    fn node_info(&self) -> Result<NodeInfo> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let head = &block_chain.latest_block().header;
+        Ok(NodeInfo {
+            name: CLIENT_VERSION.to_string(),
+            chain_id: block_chain.config.chain_id.into(),
+            genesis: block_chain.canonical_blocks()[0].header.hash(),
+            head: block_chain.latest_block_hash,
+            head_number: head.number,
+        })
+    }
+
+    fn datadir(&self) -> Result<Option<String>> {
+        let node = self.node.lock().unwrap();
+        Ok(node
+            .datadir
+            .as_ref()
+            .map(|datadir| datadir.display().to_string()))
+    }
+
+    fn export_chain(&self, file: String) -> Result<bool> {
+        let node = self.node.lock().unwrap();
+        File::create(&file)
+            .and_then(|mut f| export_blocks(&node.block_chain, &mut f))
+            .map_err(|e| Error {
+                code: ErrorCode::ServerError(-32000),
+                message: format!("could not export chain to {}: {}", file, e),
+                data: None,
+            })?;
+        Ok(true)
+    }
+
+    fn get_raw_header(&self, number: String) -> Result<Option<Bytes>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?.map(|block| Bytes(block.header.to_rlp())))
+    }
+
+    fn get_raw_block(&self, number: String) -> Result<Option<Bytes>> {
+        let node = self.node.lock().unwrap();
+        Ok(block_by_number(&node.block_chain, &number)?.map(|block| Bytes(block.to_rlp())))
+    }
+
+    fn get_raw_transaction(&self, hash: H256) -> Result<Option<Bytes>> {
+        let node = self.node.lock().unwrap();
+        let transaction = match node.block_chain.transaction_location(&hash) {
+            Some((block, index)) => Some(&block.transactions[index]),
+            None => node.pool.get(&hash),
+        };
+        Ok(transaction.map(|transaction| Bytes(transaction.to_rlp())))
+    }
+
+    fn get_raw_receipts(&self, number: String) -> Result<Option<Vec<Bytes>>> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        Ok(block_by_number(block_chain, &number)?.map(|block| {
+            block_chain.receipts[&block.header.hash()]
+                .iter()
+                .map(|receipt| Bytes(receipt.to_rlp()))
+                .collect()
+        }))
+    }
+
+    fn trace_transaction(&self, hash: H256, options: Option<TraceOptions>) -> Result<TraceResult> {
+        let node = self.node.lock().unwrap();
+        let block_chain = &node.block_chain;
+        let (block, index) = block_chain
+            .transaction_location(&hash)
+            .ok_or_else(|| Error::invalid_params("transaction not found"))?;
+        let parent = &block_chain.blocks[&block.header.parent_hash].header;
+        let mut state = block_chain
+            .state_at(parent)
+            .ok_or_else(|| {
+                Error::invalid_params(format!("state of block {} is not available", parent.number))
+            })?
+            .into_owned();
+        for transaction in &block.transactions[..index] {
+            transaction
+                .execute(&mut state, &block.header)
+                .map_err(|e| transaction_error(&e))?;
+        }
+        let transaction = &block.transactions[index];
+        let sender = transaction
+            .sender()
+            .ok_or_else(|| transaction_error(&TransactionError::InvalidSignature))?;
+        trace(options, |tracer| {
+            transaction.execute_traced(sender, &mut state, &block.header, tracer);
+        })
+    }
+
+    fn trace_call(
+        &self,
+        request: TransactionRequest,
+        block: Option<BlockParameter>,
+        options: Option<TraceOptions>,
+    ) -> Result<TraceResult> {
+        let node = self.node.lock().unwrap();
+        let from = request.from;
+        let (transaction, header, mut state) =
+            call_context(&node, request, &block.unwrap_or_default())?;
+        let config = &node.block_chain.config;
+        transaction
+            .validate_as(from, &state, header.gas_limit, &header, config)
+            .map_err(|e| transaction_error(&e))?;
+        trace(options, |tracer| {
+            transaction.execute_traced(from, &mut state, &header, tracer);
+        })
+    }
+}
+
+/// Snapshots of `evm_snapshot` by ID, oldest first.
+#[derive(Default)]
+pub struct Snapshots {
+    last_id: U256,
+    snapshots: Vec<(U256, Snapshot)>,
+}
+
+/// RPC methods for tests against the node (as in Ganache and Hardhat), which change the chain, time
+/// and state at will. Only served in dev mode (on the built-in dev chain).
+#[rpc]
+pub trait DevRpc {
+    /// Saves the chain and state (including the transaction pool) and returns the ID of the snapshot.
+    #[rpc(name = "evm_snapshot")]
+    fn snapshot(&self) -> Result<U256>;
+
+    /// Goes back to the snapshot with the given ID, which is removed along with all later ones.
+    /// Returns whether the snapshot existed.
+    #[rpc(name = "evm_revert")]
+    fn revert(&self, id: U256) -> Result<bool>;
+
+    /// Seals a block with the pending transactions (if any), optionally with the given timestamp
+    /// (see `evm_setNextBlockTimestamp`). Returns "0x0" like Ganache.
+    #[rpc(name = "evm_mine")]
+    fn mine(&self, timestamp: Option<U64>) -> Result<String>;
+
+    /// Moves the time of the following blocks forward by the given number of seconds. Returns the
+    /// total number of seconds added so far.
+    #[rpc(name = "evm_increaseTime")]
+    fn increase_time(&self, seconds: U64) -> Result<u64>;
+
+    /// Sets the timestamp of the next block, which must be after the one of the latest block. The time
+    /// of later blocks continues from there.
+    #[rpc(name = "evm_setNextBlockTimestamp")]
+    fn set_next_block_timestamp(&self, timestamp: U64) -> Result<bool>;
+
+    /// Sets the balance of the account in the latest state. Part of the `evm` module despite its name,
+    /// like the other `teth_set*` methods. Returns true.
+    ///
+    /// Like the other `teth_set*` methods, it fails if the node saves the chain to a data directory,
+    /// which couldn't be imported again: the blocks alone don't lead to the modified state. For the
+    /// same reason, a chain exported after modifying the state (`admin_exportChain`) can't be imported.
+    #[rpc(name = "teth_setBalance")]
+    fn set_balance(&self, address: Address, balance: Wei) -> Result<bool>;
+
+    /// Sets the code of the account in the latest state. Returns true.
+    #[rpc(name = "teth_setCode")]
+    fn set_code(&self, address: Address, code: Bytes) -> Result<bool>;
+
+    /// Sets the value of a storage slot of the account in the latest state. Returns true.
+    #[rpc(name = "teth_setStorageAt")]
+    fn set_storage_at(&self, address: Address, position: U256, value: H256) -> Result<bool>;
+
+    /// Sets the nonce of the account in the latest state. Returns true.
+    #[rpc(name = "teth_setNonce")]
+    fn set_nonce(&self, address: Address, nonce: U256) -> Result<bool>;
+}
+
+/// Sets the timestamp of the next block and the time offset of the following ones, see
+/// `evm_setNextBlockTimestamp`.
+fn set_next_timestamp(node: &mut Node, timestamp: u64) -> Result<()> {
+    let latest = node.block_chain.latest_block().header.timestamp;
+    if timestamp <= latest {
+        return Err(Error::invalid_params(format!(
+            "timestamp must be after the one of the latest block ({})",
+            latest
+        )));
+    }
+    node.next_timestamp = Some(timestamp);
+    node.time_offset = timestamp.saturating_sub(now());
+    Ok(())
+}
+
+/// Modifies the latest state for the `teth_set*` methods, unless the chain is saved to a data directory
+/// (see `Node::modify_state`).
+fn modify_state(node: &mut Node, f: impl FnOnce(&mut WorldState)) -> Result<bool> {
+    if node.datadir.is_some() {
+        return Err(Error {
+            code: ErrorCode::ServerError(-32000),
+            message: "the state can't be modified when the chain is saved to a data directory"
+                .to_string(),
+            data: None,
+        });
+    }
+    node.modify_state(f);
+    Ok(true)
+}
+
+impl DevRpc for RpcImpl {
+    fn snapshot(&self) -> Result<U256> {
+        let snapshot = self.node.lock().unwrap().snapshot();
+        let mut snapshots = self.snapshots.lock().unwrap();
+        snapshots.last_id += 1.into();
+        let id = snapshots.last_id;
+        snapshots.snapshots.push((id, snapshot));
+        Ok(id)
+    }
+
+    fn revert(&self, id: U256) -> Result<bool> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let index = match snapshots.snapshots.iter().position(|(i, _)| *i == id) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let (_, snapshot) = snapshots.snapshots.drain(index..).next().unwrap();
+        let mut node = self.node.lock().unwrap();
+        let result = node.revert(snapshot);
+        let latest_block = node.block_chain.latest_block().header.number;
+        self.filters.lock().unwrap().rewind(latest_block);
+        result.map_err(|e| Error {
+            code: ErrorCode::InternalError,
+            message: format!("could not save the chain: {}", e),
+            data: None,
+        })?;
+        Ok(true)
+    }
+
+    fn mine(&self, timestamp: Option<U64>) -> Result<String> {
+        let mut node = self.node.lock().unwrap();
+        if node.sync.is_some() {
+            return Err(Error {
+                code: ErrorCode::ServerError(-32000),
+                message: "the node is importing blocks".to_string(),
+                data: None,
+            });
+        }
+        if let Some(U64(timestamp)) = timestamp {
+            set_next_timestamp(&mut node, timestamp)?;
+        }
+        let timestamp = node.block_timestamp();
+        node.seal_block(timestamp).map_err(|e| Error {
+            code: ErrorCode::InternalError,
+            message: format!("could not seal block: {}", e),
+            data: None,
+        })?;
+        Ok("0x0".to_string())
+    }
+
+    fn increase_time(&self, seconds: U64) -> Result<u64> {
+        let mut node = self.node.lock().unwrap();
+        node.time_offset += seconds.0;
+        Ok(node.time_offset)
+    }
+
+    fn set_next_block_timestamp(&self, timestamp: U64) -> Result<bool> {
+        set_next_timestamp(&mut self.node.lock().unwrap(), timestamp.0)?;
+        Ok(true)
+    }
+
+    fn set_balance(&self, address: Address, balance: Wei) -> Result<bool> {
+        modify_state(&mut self.node.lock().unwrap(), |state| {
+            state.accounts.entry(address).or_default().balance = balance;
+        })
+    }
+
+    fn set_code(&self, address: Address, code: Bytes) -> Result<bool> {
+        modify_state(&mut self.node.lock().unwrap(), |state| {
+            state.set_code(address, code.0)
+        })
+    }
+
+    fn set_storage_at(&self, address: Address, position: U256, value: H256) -> Result<bool> {
+        modify_state(&mut self.node.lock().unwrap(), |state| {
+            state.set_storage(address, position.into(), value.into())
+        })
+    }
+
+    fn set_nonce(&self, address: Address, nonce: U256) -> Result<bool> {
+        modify_state(&mut self.node.lock().unwrap(), |state| {
+            state.accounts.entry(address).or_default().nonce = nonce;
+        })
+    }
+}
+
+/// Group of RPC methods that can be enabled per transport. Methods belong to the module named like
+/// the prefix of their name, e.g. `eth_call` to `eth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiModule {
+    Eth,
+    Net,
+    Web3,
+    Teth,
+    Txpool,
+    Debug,
+    Admin,
+    Personal,
+    Evm,
+}
+
+impl ApiModule {
+    pub const ALL: [ApiModule; 9] = [
+        ApiModule::Eth,
+        ApiModule::Net,
+        ApiModule::Web3,
+        ApiModule::Teth,
+        ApiModule::Txpool,
+        ApiModule::Debug,
+        ApiModule::Admin,
+        ApiModule::Personal,
+        ApiModule::Evm,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiModule::Eth => "eth",
+            ApiModule::Net => "net",
+            ApiModule::Web3 => "web3",
+            ApiModule::Teth => "teth",
+            ApiModule::Txpool => "txpool",
+            ApiModule::Debug => "debug",
+            ApiModule::Admin => "admin",
+            ApiModule::Personal => "personal",
+            ApiModule::Evm => "evm",
+        }
+    }
+
+    /// The module of the method with the given name: the one of its prefix, except for the methods in
+    /// `MODULE_EXCEPTIONS`. `None` if the prefix isn't a module.
+    pub fn of(method: &str) -> Option<ApiModule> {
+        if let Some((_, module)) = MODULE_EXCEPTIONS.iter().find(|(name, _)| *name == method) {
+            return Some(*module);
+        }
+        let prefix = method.split('_').next()?;
+        prefix.parse().ok()
+    }
+}
+
+/// Methods that keep their usual names, but belong to another module than their prefix says:
+/// `eth_sendTransaction` signs with the node's keys, so it's only served with `personal`, and the dev
+/// methods `teth_set*` modify the state, so they're only served with `evm`.
+const MODULE_EXCEPTIONS: [(&str, ApiModule); 5] = [
+    ("eth_sendTransaction", ApiModule::Personal),
+    ("teth_setBalance", ApiModule::Evm),
+    ("teth_setCode", ApiModule::Evm),
+    ("teth_setStorageAt", ApiModule::Evm),
+    ("teth_setNonce", ApiModule::Evm),
+];
+
+impl FromStr for ApiModule {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .cloned()
+            .find(|module| module.name() == s)
+            .ok_or_else(|| format!("unknown API module: {}", s))
+    }
+}
+
+/// Configuration of the RPC servers.
+#[derive(Clone)]
+pub struct ServerConfig {
+    /// Whether transactions without replay protection (EIP-155) are accepted.
+    pub allow_unprotected_txs: bool,
+    /// Seconds between blocks, 0 for a block per transaction.
+    pub block_time: u64,
+    /// Address of the WebSocket server.
+    pub ws_address: SocketAddr,
+    /// Address of the HTTP server, which is only started if set.
+    pub http_address: Option<SocketAddr>,
+    /// Origins allowed to make cross-origin HTTP requests (CORS), `*` for any.
+    pub http_cors_domains: Vec<String>,
+    /// Accepted values of the `Host` header of HTTP requests (without port), `*` for any.
+    pub http_vhosts: Vec<String>,
+    /// Path of the Unix socket of the IPC server, which is only started if set.
+    pub ipc_path: Option<PathBuf>,
+    /// Modules served over the network (WebSocket and HTTP).
+    pub rpc_apis: Vec<ApiModule>,
+    /// Modules served over IPC.
+    pub ipc_apis: Vec<ApiModule>,
+    /// Faucet paying out `teth_faucet` requests, which fail without it.
+    pub faucet: Option<Faucet>,
+    /// Whether the dev methods (`evm_*`, `teth_set*`) are served.
+    pub dev: bool,
+    /// Whether the WebSocket and HTTP servers are only reached through a reverse proxy, whose
+    /// `X-Forwarded-For` header is trusted for the client IP.
+    pub behind_proxy: bool,
+}
+
+/// Handler for the RPC methods of the given modules. Every transport gets its own handler, but they
+/// share the node, subscriptions and filters of `rpc`.
+/// Transports without sessions (HTTP) pass metadata without one, which makes `eth_subscribe` fail.
+fn io_handler(rpc: &RpcImpl, apis: &[ApiModule]) -> MetaIoHandler<RequestMeta> {
+    let mut methods: HashMap<String, RemoteProcedure<RequestMeta>> =
+        Rpc::to_delegate(rpc.clone()).into();
+    let admin_methods: HashMap<_, _> = AdminRpc::to_delegate(rpc.clone()).into();
+    methods.extend(admin_methods);
+    if rpc.dev {
+        let dev_methods: HashMap<_, _> = DevRpc::to_delegate(rpc.clone()).into();
+        methods.extend(dev_methods);
+    }
+    methods.retain(|name, _| ApiModule::of(name).is_some_and(|module| apis.contains(&module)));
+    let mut io = PubSubHandler::new(MetaIoHandler::default());
+    io.extend_with(methods);
+    io.into()
+}
+
+/// Forwards the events of the node to the subscriptions and filters of `rpc`.
+fn dispatch_events(rpc: &RpcImpl, events: Receiver<NodeEvent>) {
+    let node = rpc.node.clone();
+    let subscriptions = rpc.subscriptions.clone();
+    let filters = rpc.filters.clone();
+    thread::spawn(move || {
+        for event in events {
+            let node = node.lock().unwrap();
+            let pending = subscriptions.lock().unwrap().pending(&node, &event);
+            filters.lock().unwrap().record(&event);
+            drop(node);
+
+            for notifications in pending {
+                let id = notifications.id.clone();
+                if !notifications.send() {
+                    subscriptions.lock().unwrap().remove(&id);
+                }
+            }
+        }
+    });
+}
+
+/// Serves `io` on a Unix socket at `path`, with a session per connection for subscriptions.
+fn start_ipc_server(
+    path: &Path,
+    io: MetaIoHandler<RequestMeta>,
+) -> std::io::Result<jsonrpc_ipc_server::Server> {
+    remove_stale_socket(path);
+    let extractor = |context: &jsonrpc_ipc_server::RequestContext| {
+        RequestMeta::with_session(Session::new(context.sender.clone()), None)
+    };
+    jsonrpc_ipc_server::ServerBuilder::with_meta_extractor(io, extractor)
+        .start(&path.to_string_lossy())
+}
+
+/// Removes the socket of a previous run, which would make binding fail. Other files are left alone.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) {
+    use std::os::unix::fs::FileTypeExt;
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_stale_socket(_path: &Path) {}
+
+fn cors_domains(origins: &[String]) -> DomainsValidation<AccessControlAllowOrigin> {
+    DomainsValidation::AllowOnly(origins.iter().map(AccessControlAllowOrigin::from).collect())
+}
+
+/// Allows the given hosts on any port.
+fn allowed_hosts(hosts: &[String]) -> DomainsValidation<Host> {
+    if hosts.iter().any(|host| host == "*") {
+        return DomainsValidation::Disabled;
+    }
+    let hosts = hosts
+        .iter()
+        .flat_map(|host| vec![Host::from(host), Host::from(format!("{}:*", host))])
+        .collect();
+    DomainsValidation::AllowOnly(hosts)
+}
+
+/// Starts the JSON-RPC servers (WebSocket and, if configured, HTTP and IPC). Unless `block_time` is zero,
+/// pending transactions are sealed into a new block every `block_time` seconds; otherwise, each
+/// transaction gets its own block right away. No blocks are sealed while the node imports blocks.
+pub fn start_servers(node: Arc<Mutex<Node>>, config: &ServerConfig) {
+    let events = node.lock().unwrap().subscribe();
+    let block_time = config.block_time;
+    if block_time > 0 {
+        let node = node.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(block_time));
+            let mut node = node.lock().unwrap();
+            if node.pool.pending_count() > 0 && node.sync.is_none() {
+                let timestamp = node.block_timestamp();
+                if let Err(e) = node.seal_block(timestamp) {
+                    eprintln!("Error: could not seal block: {}", e);
+                }
+            }
+        });
+    }
+
+    let mut rpc = RpcImpl::new(node, config.allow_unprotected_txs, block_time == 0);
+    rpc.faucet = config.faucet.clone().map(Mutex::new).map(Arc::new);
+    rpc.dev = config.dev;
+    dispatch_events(&rpc, events);
+
+    let behind_proxy = config.behind_proxy;
+    let _http_server = config.http_address.map(|address| {
+        let io = io_handler(&rpc, &config.rpc_apis);
+        let extractor =
+            move |request: &Request<Body>| RequestMeta::from_http_request(request, behind_proxy);
+        jsonrpc_http_server::ServerBuilder::with_meta_extractor(io, extractor)
+            .cors(cors_domains(&config.http_cors_domains))
+            .allowed_hosts(allowed_hosts(&config.http_vhosts))
+            .start_http(&address)
+            .expect("HTTP server must start with no issues")
+    });
+    let _ipc_server = config.ipc_path.as_ref().map(|path| {
+        start_ipc_server(path, io_handler(&rpc, &config.ipc_apis))
+            .expect("IPC server must start with no issues")
+    });
+
+    let extractor = |context: &RequestContext| {
+        let client_ip = HANDSHAKE_CLIENT_IP.with(Cell::take);
+        RequestMeta::with_session(Session::new(context.sender()), client_ip)
+    };
+    let io = io_handler(&rpc, &config.rpc_apis);
+    let mut builder = ServerBuilder::with_meta_extractor(io, extractor);
+    if behind_proxy {
+        builder = builder.request_middleware(|request: &ws::Request| {
+            let header = request.header("x-forwarded-for");
+            let client_ip = forwarded_ip(header.and_then(|value| std::str::from_utf8(value).ok()));
+            HANDSHAKE_CLIENT_IP.with(|ip| ip.set(client_ip));
+            None
+        });
+    }
+    let ws_server = builder
+        .start(&config.ws_address)
+        .expect("WebSocket server must start with no issues");
+
+    ws_server.wait().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{
+        dev_accounts, parse_secret_key, CallKind, FaucetConfig, Genesis, StateRetention,
+        SyncProgress, DEV_ACCOUNT_KEYS, DEV_CHAIN_ID, ONE_ETHER,
+    };
+    use jsonrpc_ws_server::jsonrpc_core::futures::sync::mpsc;
+
+    fn dev_rpc(instant_seal: bool) -> RpcImpl {
+        let genesis = Genesis::dev();
+        let mut node = Node::new(BlockChain::from_genesis(&genesis), genesis.state());
+        node.accounts = dev_accounts();
+        RpcImpl::new(Arc::new(Mutex::new(node)), false, instant_seal)
+    }
+
+    #[test]
+    fn test_send_raw_transaction() {
+        let rpc = dev_rpc(false);
+        let (_, key) = dev_accounts().into_iter().next().unwrap();
+        let mut transaction = Transaction::Legacy(LegacyTransaction {
+            gas_price: 2.into(),
+            gas_limit: 21000.into(),
+            to: Some(Address::zero()),
+            value: 1.into(),
+            ..LegacyTransaction::default()
+        });
+        transaction.sign(&key, None);
+        let error = rpc
+            .send_raw_transaction(Bytes(transaction.to_rlp()))
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(-32000));
+        assert_eq!(error.data, Some(json!({ "reason": "unprotected" })));
+
+        transaction.sign(&key, Some(DEV_CHAIN_ID));
+        let raw = Bytes(transaction.to_rlp());
+        assert_eq!(
+            rpc.send_raw_transaction(raw.clone()),
+            Ok(transaction.hash())
+        );
+        let error = rpc.send_raw_transaction(raw).unwrap_err();
+        assert_eq!(error.data, Some(json!({ "reason": "alreadyKnown" })));
+
+        transaction.set_gas_limit(20000.into());
+        transaction.sign(&key, Some(DEV_CHAIN_ID));
+        let error = rpc
+            .send_raw_transaction(Bytes(transaction.to_rlp()))
+            .unwrap_err();
+        assert_eq!(error.data.unwrap()["reason"], "intrinsicGasTooLow");
+
+        assert!(rpc.send_raw_transaction(Bytes(vec![0x12, 0x34])).is_err());
+    }
+
+    #[test]
+    fn test_send_transaction() {
+        let rpc = dev_rpc(true);
+        let from = rpc.accounts().unwrap()[0];
+        // not the zero address, which is the beneficiary of the dev node
+        let to = Address::from(0x1234);
+        let request = || TransactionRequest {
+            from,
+            to: Some(to),
+            value: Some(1000.into()),
+            ..TransactionRequest::default()
+        };
+        let hash = rpc.send_transaction(request()).unwrap();
+        let hash2 = rpc.send_transaction(request()).unwrap();
+
+        let node = rpc.node.lock().unwrap();
+        let block = node.block_chain.latest_block();
+        assert_eq!(block.header.number, 2);
+        assert_eq!(block.transactions[0].hash(), hash2);
+        assert_eq!(block.transactions[0].nonce(), 1.into());
+        assert_eq!(block.transactions[0].chain_id(), Some(DEV_CHAIN_ID));
+        assert_ne!(hash, hash2);
+        assert_eq!(node.state.accounts[&to].balance, 2000.into());
+        drop(node);
+
+        let unknown = TransactionRequest {
+            from: Address::zero(),
+            ..request()
+        };
+        assert!(rpc.send_transaction(unknown).is_err());
+    }
+
+    #[test]
+    fn test_transaction_lookups() {
+        let rpc = dev_rpc(true);
+        let from = rpc.accounts().unwrap()[0];
+        let create = TransactionRequest {
+            from,
+            value: Some(1000.into()),
+            ..TransactionRequest::default()
+        };
+        let hash = rpc.send_transaction(create).unwrap();
+
+        let transaction = rpc.get_transaction_by_hash(hash).unwrap().unwrap();
+        assert_eq!(transaction.block_number, Some(1));
+        assert_eq!(transaction.transaction_index, Some(0));
+        let block_hash = transaction.block_hash.unwrap();
+        let by_index = rpc
+            .get_transaction_by_block_hash_and_index(block_hash, 0.into())
+            .unwrap();
+        assert_eq!(by_index.unwrap().hash, hash);
+        let by_index = rpc
+            .get_transaction_by_block_number_and_index("0x1".to_string(), 1.into())
+            .unwrap();
+        assert!(by_index.is_none());
+
+        let receipt = rpc.get_transaction_receipt(hash).unwrap().unwrap();
+        assert_eq!(receipt.status, 1);
+        assert_eq!(receipt.gas_used, 53000.into());
+        assert_eq!(receipt.cumulative_gas_used, 53000.into());
+        assert_eq!(
+            receipt.contract_address,
+            Some(contract_address(&from, &0.into()))
+        );
+        assert_eq!(receipt.effective_gas_price, DEFAULT_PRIORITY_FEE.into());
+
+        assert_eq!(
+            rpc.get_block_transaction_count_by_hash(block_hash),
+            Ok(Some(1.into()))
+        );
+        assert_eq!(
+            rpc.get_block_transaction_count_by_number("0x2".to_string()),
+            Ok(None)
+        );
+        assert!(rpc.get_transaction_receipt(H256::zero()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_state_queries() {
+        let rpc = dev_rpc(true);
+        let from = rpc.accounts().unwrap()[0];
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let transfer = || TransactionRequest {
+            from,
+            to: Some(recipient),
+            value: Some(1000.into()),
+            ..TransactionRequest::default()
+        };
+        rpc.send_transaction(transfer()).unwrap();
+        assert_eq!(rpc.block_number(), Ok(1.into()));
+
+        let tag = |tag: &str| Some(BlockParameter::NumberOrTag(tag.to_string()));
+        assert_eq!(rpc.get_balance(recipient, None), Ok(1000.into()));
+        assert_eq!(rpc.get_balance(recipient, tag("0x0")), Ok(0.into()));
+        assert_eq!(
+            rpc.get_transaction_count(from, tag("earliest")),
+            Ok(0.into())
+        );
+        assert_eq!(rpc.get_transaction_count(from, tag("latest")), Ok(1.into()));
+        assert!(rpc.get_balance(recipient, tag("0x2")).is_err());
+
+        let genesis_hash = rpc.node.lock().unwrap().block_chain.canonical_blocks()[0].hash();
+        let by_hash: BlockParameter = serde_json::from_value(json!({
+            "blockHash": genesis_hash,
+            "requireCanonical": true
+        }))
+        .unwrap();
+        assert_eq!(rpc.get_balance(recipient, Some(by_hash)), Ok(0.into()));
+        let by_number: BlockParameter =
+            serde_json::from_value(json!({ "blockNumber": "0x1" })).unwrap();
+        assert_eq!(rpc.get_balance(recipient, Some(by_number)), Ok(1000.into()));
+        let by_hash = BlockParameter::Hash {
+            block_hash: H256::zero(),
+            require_canonical: false,
+        };
+        assert!(rpc.get_balance(recipient, Some(by_hash)).is_err());
+
+        assert_eq!(rpc.get_code(recipient, None), Ok(Bytes(vec![])));
+        assert_eq!(
+            rpc.get_storage_at(recipient, 0.into(), None),
+            Ok(H256::zero())
+        );
+
+        // states beyond the retention window are recomputed
+        rpc.node
+            .lock()
+            .unwrap()
+            .block_chain
+            .states
+            .set_retention(StateRetention::Recent(1));
+        for _ in 0..2 {
+            rpc.send_transaction(transfer()).unwrap();
+        }
+        assert_eq!(rpc.get_balance(recipient, tag("0x1")), Ok(1000.into()));
+        assert_eq!(rpc.get_balance(recipient, tag("0x2")), Ok(2000.into()));
+        assert_eq!(rpc.get_balance(recipient, None), Ok(3000.into()));
+    }
+
+    #[test]
+    fn test_call_and_estimate_gas() {
+        let rpc = dev_rpc(false);
+        let from = rpc.accounts().unwrap()[0];
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let transfer = || TransactionRequest {
+            from,
+            to: Some(recipient),
+            value: Some(1000.into()),
+            ..TransactionRequest::default()
+        };
+
+        assert_eq!(rpc.call(transfer(), None), Ok(Bytes(vec![])));
+        assert_eq!(rpc.get_balance(recipient, None), Ok(0.into()));
+        assert_eq!(rpc.node.lock().unwrap().pool.pending_count(), 0);
+        assert_eq!(rpc.estimate_gas(transfer(), None), Ok(21000.into()));
+
+        // the zero address has no balance, so it can't transfer any value...
+        let from_nobody = TransactionRequest {
+            from: Address::zero(),
+            ..transfer()
+        };
+        let error = rpc.call(from_nobody, None).unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(-32000));
+        assert_eq!(error.data.unwrap()["reason"], "insufficientFunds");
+        // ...but doesn't need any for a call without value and fees
+        let request: TransactionRequest =
+            serde_json::from_value(json!({ "to": recipient })).unwrap();
+        assert_eq!(rpc.call(request, None), Ok(Bytes(vec![])));
+
+        let creation = TransactionRequest {
+            from,
+            data: Some(Bytes(vec![1, 0])),
+            ..TransactionRequest::default()
+        };
+        assert_eq!(
+            rpc.estimate_gas(creation, None),
+            Ok((53000 + 68 + 4).into())
+        );
+        let too_little_gas = TransactionRequest {
+            gas: Some(20000.into()),
+            ..transfer()
+        };
+        assert!(rpc.estimate_gas(too_little_gas, None).is_err());
+        let unknown_block = Some(BlockParameter::NumberOrTag("0x1".to_string()));
+        assert!(rpc.call(transfer(), unknown_block).is_err());
+    }
+
+    #[test]
+    fn test_fee_history() {
+        let rpc = dev_rpc(false);
+        let from = rpc.accounts().unwrap()[0];
+        assert_eq!(
+            rpc.max_priority_fee_per_gas(),
+            Ok(DEFAULT_PRIORITY_FEE.into())
+        );
+        // before London, the whole gas price is the priority fee
+        for (nonce, gas_price) in [3u64, 1, 2].iter().enumerate() {
+            let transfer = TransactionRequest {
+                from,
+                to: Some(Address::from(0x1234)),
+                value: Some(1.into()),
+                gas: Some(21000.into()),
+                gas_price: Some((*gas_price).into()),
+                nonce: Some(nonce.into()),
+                ..TransactionRequest::default()
+            };
+            rpc.send_transaction(transfer).unwrap();
+        }
+        rpc.node.lock().unwrap().seal_block(now()).unwrap();
+
+        let history = rpc
+            .fee_history(2.into(), "latest".to_string(), Some(vec![0.0, 50.0, 100.0]))
+            .unwrap();
+        assert_eq!(history.oldest_block, 0);
+        assert_eq!(history.base_fee_per_gas.len(), 3);
+        let node = rpc.node.lock().unwrap();
+        let gas_limit = node.block_chain.latest_block().header.gas_limit;
+        drop(node);
+        assert_eq!(
+            history.gas_used_ratio,
+            vec![0.0, 63000.0 / gas_limit.low_u64() as f64]
+        );
+        let rewards = |fees: [u64; 3]| fees.iter().map(|fee| U256::from(*fee)).collect();
+        assert_eq!(
+            history.reward,
+            Some(vec![rewards([0, 0, 0]), rewards([1, 2, 3])])
+        );
+        let history = rpc.fee_history(1.into(), "0x0".to_string(), None).unwrap();
+        assert_eq!(history.oldest_block, 0);
+        assert_eq!(history.reward, None);
+        assert!(rpc
+            .fee_history(1.into(), "latest".to_string(), Some(vec![50.0, 10.0]))
+            .is_err());
+        assert_eq!(rpc.max_priority_fee_per_gas(), Ok(2.into()));
+
+        let genesis = Genesis {
+            gas_limit: 0.into(),
+            ..Genesis::dev()
+        };
+        let node = Node::new(BlockChain::from_genesis(&genesis), genesis.state());
+        let rpc = RpcImpl::new(Arc::new(Mutex::new(node)), false, false);
+        let history = rpc
+            .fee_history(1.into(), "latest".to_string(), None)
+            .unwrap();
+        assert_eq!(history.gas_used_ratio, vec![0.0]);
+        assert!(serde_json::to_string(&history).is_ok());
+    }
+
+    #[test]
+    fn test_block_queries() {
+        let rpc = dev_rpc(true);
+        let from = rpc.accounts().unwrap()[0];
+        let transfer = TransactionRequest {
+            from,
+            to: Some(Address::zero()),
+            value: Some(1.into()),
+            ..TransactionRequest::default()
+        };
+        let transaction_hash = rpc.send_transaction(transfer).unwrap();
+        let by_number = |number: &str| rpc.get_block_by_number(number.to_string(), false).unwrap();
+
+        let genesis = by_number("earliest").unwrap();
+        let latest = by_number("latest").unwrap();
+        assert_eq!(genesis.number, Some(0));
+        assert_eq!(genesis.total_difficulty, Some(genesis.difficulty));
+        assert_eq!(latest.number, Some(1));
+        assert_eq!(
+            latest.total_difficulty,
+            Some(genesis.difficulty + latest.difficulty)
+        );
+        let json = serde_json::to_value(&latest).unwrap();
+        assert_eq!(json["number"], "0x1");
+        assert_eq!(json["transactions"], json!([transaction_hash]));
+        for tag in &["safe", "finalized", "0x1"] {
+            assert_eq!(by_number(tag).unwrap().hash, latest.hash);
+        }
+        let pending = by_number("pending").unwrap();
+        assert_eq!((pending.number, pending.hash), (Some(2), None));
+        assert_eq!(pending.parent_hash, latest.hash.unwrap());
+        assert!(by_number("0x2").is_none());
+        assert!(rpc
+            .get_block_by_number("latest!".to_string(), false)
+            .is_err());
+
+        let latest_hash = latest.hash.unwrap();
+        let block = rpc.get_block_by_hash(latest_hash, true).unwrap().unwrap();
+        let transaction = &serde_json::to_value(&block).unwrap()["transactions"][0];
+        assert_eq!(transaction["hash"], json!(transaction_hash));
+        assert_eq!(transaction["blockNumber"], "0x1");
+        assert_eq!(transaction["transactionIndex"], "0x0");
+        assert_eq!(block.state_root, latest.state_root);
+        assert!(rpc
+            .get_block_by_hash(H256::zero(), false)
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            rpc.get_uncle_count_by_block_number("latest".to_string()),
+            Ok(Some(0.into()))
+        );
+        assert_eq!(
+            rpc.get_uncle_count_by_block_number("0x2".to_string()),
+            Ok(None)
+        );
+        // the uncles aren't validated beyond the ommers hash, so any header will do here
+        {
+            let mut node = rpc.node.lock().unwrap();
+            let mut block = node.block_chain.latest_block().clone();
+            block.ommers = vec![node.block_chain.canonical_blocks()[0].header.clone()];
+            node.block_chain.blocks.insert(latest_hash, block);
+        }
+        assert_eq!(
+            rpc.get_uncle_count_by_block_hash(latest_hash),
+            Ok(Some(1.into()))
+        );
+        let uncle = rpc
+            .get_uncle_by_block_hash_and_index(latest_hash, 0.into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(uncle.hash, genesis.hash);
+        assert_eq!(uncle.total_difficulty, None);
+        assert!(rpc
+            .get_uncle_by_block_number_and_index("0x1".to_string(), 1.into())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_txpool() {
+        let rpc = dev_rpc(false);
+        let from = rpc.accounts().unwrap()[0];
+        let request = |nonce: u64| TransactionRequest {
+            from,
+            to: Some(Address::zero()),
+            value: Some(1000.into()),
+            gas: Some(21000.into()),
+            gas_price: Some(2.into()),
+            nonce: Some(nonce.into()),
+            ..TransactionRequest::default()
+        };
+        let pending = rpc.send_transaction(request(0)).unwrap();
+        rpc.send_transaction(request(2)).unwrap();
+        assert_eq!(
+            rpc.txpool_status().unwrap(),
+            TxpoolStatus {
+                pending: 1,
+                queued: 1
+            }
+        );
+
+        let content = serde_json::to_value(rpc.txpool_content().unwrap()).unwrap();
+        let sender = to_hex(&from);
+        assert_eq!(content["pending"][&sender]["0"]["hash"], json!(pending));
+        assert_eq!(content["pending"][&sender]["0"]["blockHash"], json!(null));
+        assert_eq!(content["queued"][&sender]["2"]["nonce"], "0x2");
+        let summaries = serde_json::to_value(rpc.txpool_inspect().unwrap()).unwrap();
+        assert_eq!(
+            summaries["queued"][&sender]["2"],
+            format!("{}: 1000 wei + 21000 gas × 2 wei", to_hex(&Address::zero()))
+        );
+
+        // the pending block has the executable transactions only
+        let block = rpc
+            .get_block_by_number("pending".to_string(), false)
+            .unwrap()
+            .unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["number"], "0x1");
+        assert_eq!(json["hash"], json!(null));
+        assert_eq!(json["transactions"], json!([pending]));
+        assert_eq!(rpc.block_number().unwrap(), 0.into());
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let address = Address::from("0x3535353535353535353535353535353535353535");
+        let (topic1, topic2, topic3) = (H256::from(1), H256::from(2), H256::from(3));
+        let log = LogEntry {
+            address,
+            topics: vec![topic1, topic2],
+            data: vec![],
+        };
+        let filter = |value: Value| serde_json::from_value::<LogFilter>(value).unwrap();
+
+        assert!(LogFilter::default().matches(&log));
+        assert!(filter(json!({ "address": address })).matches(&log));
+        assert!(filter(json!({ "address": [] })).matches(&log));
+        assert!(!filter(json!({ "address": [Address::zero()] })).matches(&log));
+        assert!(filter(json!({ "topics": [null, topic2] })).matches(&log));
+        assert!(filter(json!({ "topics": [topic1, [topic3, topic2]] })).matches(&log));
+        assert!(!filter(json!({ "topics": [topic2] })).matches(&log));
+        assert!(!filter(json!({ "topics": [topic1, topic2, topic3] })).matches(&log));
+    }
+
+    #[test]
+    fn test_subscription_notifications() {
+        let rpc = dev_rpc(false);
+        let from = rpc.accounts().unwrap()[0];
+        let transfer = TransactionRequest {
+            from,
+            to: Some(Address::zero()),
+            value: Some(1.into()),
+            ..TransactionRequest::default()
+        };
+        let transaction_hash = rpc.send_transaction(transfer).unwrap();
+        let mut node = rpc.node.lock().unwrap();
+        let any = LogFilter::default();
+
+        let pending = NodeEvent::NewPendingTransaction(transaction_hash);
+        assert_eq!(
+            notifications(
+                &node,
+                &pending,
+                SubscriptionKind::NewPendingTransactions,
+                &any
+            ),
+            vec![json!(transaction_hash)]
+        );
+        assert!(notifications(&node, &pending, SubscriptionKind::NewHeads, &any).is_empty());
+
+        let block_hash = node.seal_block(now()).unwrap();
+        let new_head = NodeEvent::NewHead(block_hash);
+        let heads = notifications(&node, &new_head, SubscriptionKind::NewHeads, &any);
+        assert_eq!(heads.len(), 1);
+        assert_eq!(heads[0]["hash"], json!(block_hash));
+        assert_eq!(heads[0]["number"], "0x1");
+
+        // there's no EVM to create logs yet, so add one to the receipt
+        let log = LogEntry {
+            address: Address::zero(),
+            topics: vec![H256::from(1)],
+            data: vec![42],
+        };
+        node.block_chain.receipts.get_mut(&block_hash).unwrap()[0]
+            .logs
+            .push(log);
+        let logs = notifications(&node, &new_head, SubscriptionKind::Logs, &any);
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0]["transactionHash"], json!(transaction_hash));
+        assert_eq!(logs[0]["data"], "0x2a");
+        let other_topic: LogFilter =
+            serde_json::from_value(json!({ "topics": [H256::from(2)] })).unwrap();
+        assert!(notifications(&node, &new_head, SubscriptionKind::Logs, &other_topic).is_empty());
+    }
+
+    #[test]
+    fn test_subscription_session() {
+        let rpc = dev_rpc(false);
+        let (transport, _receiver) = mpsc::channel(1);
+        let meta = RequestMeta::with_session(Session::new(transport), None);
+        let (subscriber, id, _notifications) = Subscriber::new_test("eth_subscription");
+        rpc.subscribe(meta.clone(), subscriber, SubscriptionKind::NewHeads, None);
+        let id = id.wait().unwrap().unwrap();
+        assert!(rpc.subscriptions.lock().unwrap().active.contains_key(&id));
+
+        // the subscriptions of a session end with it
+        drop(meta);
+        assert!(rpc.subscriptions.lock().unwrap().active.is_empty());
+    }
+
+    #[test]
+    fn test_filters() {
+        let rpc = dev_rpc(true);
+        let events = rpc.node.lock().unwrap().subscribe();
+        let block_filter = rpc.new_block_filter().unwrap();
+        let pending_filter = rpc.new_pending_transaction_filter().unwrap();
+        let log_filter = rpc.new_filter(LogFilter::default()).unwrap();
+
+        let from = rpc.accounts().unwrap()[0];
+        let transfer = TransactionRequest {
+            from,
+            to: Some(Address::zero()),
+            value: Some(1.into()),
+            ..TransactionRequest::default()
+        };
+        let transaction_hash = rpc.send_transaction(transfer).unwrap();
+        for event in events.try_iter() {
+            rpc.filters.lock().unwrap().record(&event);
+        }
+        let block_hash = {
+            let mut node = rpc.node.lock().unwrap();
+            let block_hash = node.block_chain.latest_block_hash;
+            // there's no EVM to create logs yet, so add one to the receipt
+            let log = LogEntry {
+                address: Address::zero(),
+                topics: vec![],
+                data: vec![],
+            };
+            node.block_chain.receipts.get_mut(&block_hash).unwrap()[0]
+                .logs
+                .push(log);
+            block_hash
+        };
+        let changes = |id: U256| serde_json::to_value(rpc.get_filter_changes(id).unwrap()).unwrap();
+
+        assert_eq!(changes(block_filter), json!([block_hash]));
+        assert_eq!(changes(block_filter), json!([]));
+        assert_eq!(changes(pending_filter), json!([transaction_hash]));
+        assert_eq!(changes(pending_filter), json!([]));
+        let logs = changes(log_filter);
+        assert_eq!(logs.as_array().unwrap().len(), 1);
+        assert_eq!(logs[0]["blockHash"], json!(block_hash));
+        assert_eq!(changes(log_filter), json!([]));
+        assert_eq!(rpc.get_filter_logs(log_filter).unwrap().len(), 1);
+
+        // as if the chain had been longer when the filters were last polled
+        for filter in rpc.filters.lock().unwrap().installed.values_mut() {
+            filter.last_block = 5;
+        }
+        assert_eq!(changes(block_filter), json!([]));
+        assert_eq!(changes(log_filter), json!([]));
+        assert!(rpc.get_filter_logs(block_filter).is_err());
+
+        let filter = |value: Value| serde_json::from_value::<LogFilter>(value).unwrap();
+        let all_blocks = filter(json!({ "fromBlock": "earliest", "toBlock": "0x100" }));
+        assert_eq!(rpc.get_logs(all_blocks).unwrap().len(), 1);
+        assert_eq!(
+            rpc.get_logs(filter(json!({ "blockHash": block_hash })))
+                .unwrap()
+                .len(),
+            1
+        );
+        let hash_and_range = filter(json!({ "blockHash": block_hash, "fromBlock": "0x0" }));
+        assert!(rpc.get_logs(hash_and_range).is_err());
+        // the bloom of the block doesn't include the added log, so the block is skipped
+        let by_address = filter(json!({ "fromBlock": "earliest", "address": Address::zero() }));
+        assert!(rpc.get_logs(by_address).unwrap().is_empty());
+
+        assert_eq!(rpc.uninstall_filter(block_filter), Ok(true));
+        assert_eq!(rpc.uninstall_filter(block_filter), Ok(false));
+        assert!(rpc.get_filter_changes(block_filter).is_err());
+        rpc.filters.lock().unwrap().timeout = Duration::from_secs(0);
+        assert!(rpc.get_filter_changes(log_filter).is_err());
+    }
+
+    #[test]
+    fn test_node_info() {
+        let rpc = dev_rpc(false);
+        assert_eq!(rpc.client_version().unwrap(), CLIENT_VERSION);
+        assert_eq!(
+            rpc.sha3(Bytes(vec![])).unwrap(),
+            H256::from("0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+        assert_eq!(rpc.net_version().unwrap(), DEV_CHAIN_ID.to_string());
+        assert_eq!(rpc.net_peer_count().unwrap(), 0.into());
+        assert_eq!(rpc.protocol_version().unwrap(), PROTOCOL_VERSION.into());
+        assert_eq!(rpc.coinbase().unwrap(), Address::zero());
+        // no London fork on the dev chain and no transactions yet
+        assert_eq!(rpc.gas_price().unwrap(), DEFAULT_PRIORITY_FEE.into());
+
+        assert!(rpc.mining().unwrap());
+        assert_eq!(
+            serde_json::to_value(rpc.syncing().unwrap()).unwrap(),
+            json!(false)
+        );
+        rpc.node.lock().unwrap().sync = Some(SyncProgress {
+            starting_block: 0,
+            highest_block: 10,
+        });
+        assert!(!rpc.mining().unwrap());
+        assert_eq!(
+            serde_json::to_value(rpc.syncing().unwrap()).unwrap(),
+            json!({ "startingBlock": "0x0", "currentBlock": "0x0", "highestBlock": "0xa" })
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let rpc = dev_rpc(false);
+        let accounts = rpc.accounts().unwrap();
+        let (from, to) = (accounts[0], accounts[1]);
+        let send = |value: u64| {
+            let request = TransactionRequest {
+                from,
+                to: Some(to),
+                value: Some(value.into()),
+                ..TransactionRequest::default()
+            };
+            rpc.send_transaction(request).unwrap()
+        };
+        let hashes = [send(1), send(2)];
+        let timestamp = rpc.node.lock().unwrap().block_timestamp();
+        rpc.node.lock().unwrap().seal_block(timestamp).unwrap();
+
+        let options = |options: Value| Some(serde_json::from_value(options).unwrap());
+        let call_tracer = options(json!({ "tracer": "callTracer" }));
+        match rpc.trace_transaction(hashes[1], call_tracer).unwrap() {
+            TraceResult::Calls(call) => {
+                assert_eq!((call.from, call.to, call.value), (from, to, 2.into()));
+                assert_eq!((call.gas, call.gas_used), (21000.into(), 21000.into()));
+                assert!(call.calls.is_empty());
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        let result = rpc.trace_transaction(hashes[0], None).unwrap();
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({ "gas": 21000, "failed": false, "returnValue": "", "structLogs": [] })
+        );
+        let unknown_tracer = options(json!({ "tracer": "prestateTracer" }));
+        assert!(rpc.trace_transaction(hashes[0], unknown_tracer).is_err());
+        assert!(rpc.trace_transaction(H256::zero(), None).is_err());
+
+        let request = TransactionRequest {
+            from,
+            data: Some(Bytes(vec![0x60, 0x00])),
+            ..TransactionRequest::default()
+        };
+        let call_tracer = options(json!({ "tracer": "callTracer", "disableStack": true }));
+        match rpc.trace_call(request, None, call_tracer).unwrap() {
+            TraceResult::Calls(call) => {
+                assert_eq!(call.kind, CallKind::Create);
+                assert_eq!(call.to, contract_address(&from, &2.into()));
+                assert_eq!(call.input, vec![0x60, 0x00]);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_api_modules() {
+        assert_eq!("txpool".parse(), Ok(ApiModule::Txpool));
+        assert!("eth_".parse::<ApiModule>().is_err());
+        assert_eq!(ApiModule::of("eth_getBalance"), Some(ApiModule::Eth));
+        assert_eq!(ApiModule::of("debug_getRawBlock"), Some(ApiModule::Debug));
+        let send_transaction = ApiModule::of("eth_sendTransaction");
+        assert_eq!(send_transaction, Some(ApiModule::Personal));
+        assert_eq!(ApiModule::of("teth_setBalance"), Some(ApiModule::Evm));
+        assert_eq!(ApiModule::of("teth_topAccounts"), Some(ApiModule::Teth));
+        assert_eq!(ApiModule::of("unknown_method"), None);
+        for module in &ApiModule::ALL {
+            assert_eq!(module.name().parse(), Ok(*module));
+        }
+    }
+
+    #[test]
+    fn test_io_handler() {
+        let io = io_handler(&dev_rpc(false), &[ApiModule::Eth, ApiModule::Teth]);
+        let request = |request: Value| -> Value {
+            let meta = RequestMeta::default();
+            let response = io.handle_request_sync(&request.to_string(), meta).unwrap();
+            serde_json::from_str(&response).unwrap()
+        };
+
+        let responses = request(json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": [] },
+            { "jsonrpc": "2.0", "id": 2, "method": "eth_getBlockByNumber", "params": ["0x0", false] },
+        ]));
+        assert_eq!(
+            responses[0],
+            json!({ "jsonrpc": "2.0", "id": 1, "result": "0x0" })
+        );
+        assert_eq!(responses[1]["result"]["number"], json!("0x0"));
+
+        // no session without a WebSocket connection
+        let response = request(json!({
+            "jsonrpc": "2.0", "id": 3, "method": "eth_subscribe", "params": ["newHeads"]
+        }));
+        assert!(response["error"].is_object());
+        // methods of other modules don't exist
+        for method in &["admin_datadir", "net_version", "eth_sendTransaction"] {
+            let response = request(json!({
+                "jsonrpc": "2.0", "id": 4, "method": method, "params": []
+            }));
+            assert_eq!(response["error"]["code"], json!(-32601));
+        }
+
+        assert_eq!(
+            allowed_hosts(&["*".to_string()]),
+            DomainsValidation::Disabled
+        );
+        assert_eq!(
+            allowed_hosts(&["localhost".to_string()]),
+            DomainsValidation::AllowOnly(vec!["localhost".into(), "localhost:*".into()])
+        );
+    }
+
+    #[test]
+    fn test_top_accounts() {
+        let rpc = dev_rpc(true);
+        let from = rpc.accounts().unwrap()[0];
+        // the beneficiary of the dev node
+        let to = Address::zero();
+        let request = TransactionRequest {
+            from,
+            to: Some(to),
+            value: Some(1000.into()),
+            ..TransactionRequest::default()
+        };
+        rpc.send_transaction(request).unwrap();
+
+        let all = rpc.top_accounts(Some(MAX_PAGE_SIZE), None, None).unwrap();
+        assert_eq!(all.total, all.accounts.len());
+        let entry = all.accounts.iter().find(|entry| entry.address == to);
+        let entry = entry.unwrap();
+        assert_eq!(entry.transaction_count, 1);
+        assert_eq!(entry.first_seen_block, 1);
+        let sender = all.accounts.iter().find(|entry| entry.address == from);
+        assert_eq!(sender.unwrap().first_seen_block, 0);
+
+        let page = rpc.top_accounts(Some(1), Some(1), None).unwrap();
+        assert_eq!(page.total, all.total);
+        assert_eq!(page.accounts[0], all.accounts[1]);
+        assert_eq!(page.accounts[0].rank, 2);
+        let page = rpc.top_accounts(None, Some(all.total + 1), None).unwrap();
+        assert!(page.accounts.is_empty());
+        let too_many = rpc.top_accounts(Some(MAX_PAGE_SIZE + 1), None, None);
+        assert!(too_many.is_err());
+
+        let players = rpc
+            .top_accounts(Some(MAX_PAGE_SIZE), None, Some(true))
+            .unwrap();
+        assert_eq!(players.total, all.total - 1);
+        assert!(players.accounts.iter().all(|entry| entry.address != to));
+    }
+
+    #[test]
+    fn test_explorer() {
+        let rpc = dev_rpc(true);
+        let accounts = rpc.accounts().unwrap();
+        let (from, to) = (accounts[0], accounts[1]);
+        let send = |from: Address, to: Address| {
+            let request = TransactionRequest {
+                from,
+                to: Some(to),
+                value: Some(1000.into()),
+                ..TransactionRequest::default()
+            };
+            rpc.send_transaction(request).unwrap()
+        };
+        let hashes = [send(from, to), send(to, from), send(from, Address::zero())];
+
+        let history = rpc.get_account_transactions(to, None).unwrap();
+        assert_eq!(history.total, 2);
+        let transactions = &history.transactions;
+        assert_eq!(transactions[0].transaction.hash, hashes[1]);
+        assert_eq!(transactions[0].transaction.block_number, Some(2));
+        assert_eq!(transactions[0].status, 1);
+        assert_eq!(transactions[1].transaction.hash, hashes[0]);
+        let json = serde_json::to_value(&transactions[1]).unwrap();
+        assert_eq!(json["from"], json!(from));
+        assert_eq!(json["blockNumber"], json!("0x1"));
+        assert!(json["timestamp"].is_string());
+        let next_page = rpc.get_account_transactions(to, Some(1)).unwrap();
+        assert_eq!(next_page.total, 2);
+        assert!(next_page.transactions.is_empty());
+
+        let balances = rpc.get_account_balance_history(to).unwrap();
+        assert_eq!(balances.len(), 3);
+        assert_eq!(balances[0].block_number, 0);
+        assert_eq!(balances[2].block_number, 2);
+        assert_eq!(balances[2].balance, rpc.get_balance(to, None).unwrap());
+        let unknown = rpc.get_account_balance_history(Address::from(0x1234));
+        assert!(unknown.unwrap().is_empty());
+
+        let blocks = rpc.get_recent_blocks(2).unwrap();
+        let numbers: Vec<u64> = blocks.iter().map(|block| block.number).collect();
+        assert_eq!(numbers, vec![3, 2]);
+        assert_eq!(blocks[0].transaction_count, 1);
+        assert_eq!(rpc.get_recent_blocks(10).unwrap().len(), 4);
+        assert!(rpc.get_recent_blocks(MAX_RECENT_BLOCKS + 1).is_err());
+    }
+
+    #[test]
+    fn test_faucet() {
+        let mut rpc = dev_rpc(false);
+        let address = Address::from(0x1234);
+        let meta = RequestMeta::default();
+        let error = rpc.faucet(meta.clone(), address, None).unwrap_err();
+        assert_eq!(error.data, Some(json!({ "reason": "disabled" })));
+
+        let key = parse_secret_key(DEV_ACCOUNT_KEYS[1]).unwrap();
+        let faucet = Faucet::new(key, FaucetConfig::default());
+        let faucet_address = faucet.address;
+        rpc.faucet = Some(Arc::new(Mutex::new(faucet)));
+        let hash = rpc.faucet(meta.clone(), address, None).unwrap();
+        let node = rpc.node.lock().unwrap();
+        let transaction = node.pool.get(&hash).unwrap();
+        assert_eq!(transaction.sender(), Some(faucet_address));
+        assert_eq!(transaction.to(), Some(address));
+        assert_eq!(transaction.value(), *ONE_ETHER);
+        drop(node);
+
+        let error = rpc.faucet(meta, address, None).unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(-32000));
+        assert_eq!(error.data.unwrap()["reason"], "addressCooldown");
+
+        assert_eq!(forwarded_ip(None), None);
+        assert_eq!(forwarded_ip(Some("garbage")), None);
+        // the proxy appends the address it got the request from to the one sent by the client
+        let request = Request::builder()
+            .header("x-forwarded-for", "10.0.0.1, 10.0.0.3")
+            .body(Body::empty())
+            .unwrap();
+        let client_ip = RequestMeta::from_http_request(&request, true).client_ip;
+        assert_eq!(client_ip, Some("10.0.0.3".parse().unwrap()));
+        let untrusted = RequestMeta::from_http_request(&request, false);
+        assert_eq!(untrusted.client_ip, None);
+    }
+
+    #[test]
+    fn test_dev_methods() {
+        let mut rpc = dev_rpc(false);
+        let address = Address::from(0x1234);
+        let snapshot = rpc.snapshot().unwrap();
+        let block_filter = rpc.new_block_filter().unwrap();
+        let new_blocks = |rpc: &RpcImpl| match rpc.get_filter_changes(block_filter).unwrap() {
+            FilterChanges::Hashes(hashes) => hashes,
+            FilterChanges::Logs(_) => panic!("not a log filter"),
+        };
+
+        assert_eq!(rpc.set_balance(address, 1000.into()), Ok(true));
+        rpc.set_nonce(address, 5.into()).unwrap();
+        rpc.set_code(address, Bytes(vec![0x60, 0x00])).unwrap();
+        let stored = rpc.set_storage_at(address, 1.into(), H256::from(2));
+        assert_eq!(stored, Ok(true));
+        assert_eq!(rpc.get_balance(address, None), Ok(1000.into()));
+        assert_eq!(rpc.get_transaction_count(address, None), Ok(5.into()));
+        assert_eq!(rpc.get_code(address, None), Ok(Bytes(vec![0x60, 0x00])));
+        let value = rpc.get_storage_at(address, 1.into(), None);
+        assert_eq!(value, Ok(H256::from(2)));
+
+        let latest = rpc.node.lock().unwrap().block_chain.latest_block().clone();
+        let genesis_timestamp = latest.header.timestamp;
+        let too_early = rpc.set_next_block_timestamp(U64(genesis_timestamp));
+        assert!(too_early.is_err());
+        let timestamp = genesis_timestamp + 1000;
+        assert_eq!(rpc.set_next_block_timestamp(U64(timestamp)), Ok(true));
+        assert_eq!(rpc.mine(None), Ok("0x0".to_string()));
+        {
+            let node = rpc.node.lock().unwrap();
+            let header = &node.block_chain.latest_block().header;
+            assert_eq!((header.number, header.timestamp), (1, timestamp));
+            // the changes are part of the state of the block
+            assert_eq!(header.state_root, node.state.state_root());
+            assert_eq!(node.next_timestamp, None);
+        }
+        assert!(rpc.increase_time(U64(3600)).unwrap() >= 3600);
+        rpc.mine(Some(U64(timestamp + 5000))).unwrap();
+        assert_eq!(rpc.block_number(), Ok(2.into()));
+        assert_eq!(new_blocks(&rpc).len(), 2);
+
+        assert_eq!(rpc.revert(snapshot), Ok(true));
+        assert_eq!(rpc.block_number(), Ok(0.into()));
+        assert_eq!(rpc.get_balance(address, None), Ok(0.into()));
+        assert_eq!(rpc.revert(snapshot), Ok(false));
+        // filters report the blocks sealed after going back
+        assert!(new_blocks(&rpc).is_empty());
+        rpc.mine(None).unwrap();
+        let latest_hash = rpc.node.lock().unwrap().block_chain.latest_block_hash;
+        assert_eq!(new_blocks(&rpc), vec![latest_hash]);
+
+        // only served in dev mode
+        let mine = json!({ "jsonrpc": "2.0", "id": 1, "method": "evm_mine", "params": [] });
+        let response = |rpc: &RpcImpl, module: ApiModule, request: &Value| {
+            let io = io_handler(rpc, &[module]);
+            let response = io.handle_request_sync(&request.to_string(), RequestMeta::default());
+            serde_json::from_str::<Value>(&response.unwrap()).unwrap()
+        };
+        let not_found = response(&rpc, ApiModule::Evm, &mine);
+        assert_eq!(not_found["error"]["code"], json!(-32601));
+        rpc.dev = true;
+        let mined = response(&rpc, ApiModule::Evm, &mine);
+        assert_eq!(mined["result"], json!("0x0"));
+        // `teth_set*` belong to `evm`
+        let set_balance = json!({
+            "jsonrpc": "2.0", "id": 2, "method": "teth_setBalance", "params": [address, "0x1"]
+        });
+        let unknown = response(&rpc, ApiModule::Teth, &set_balance);
+        assert_eq!(unknown["error"]["code"], json!(-32601));
+        let result = response(&rpc, ApiModule::Evm, &set_balance);
+        assert_eq!(result["result"], json!(true));
+
+        // the chain file of a data directory couldn't be imported with a modified state
+        rpc.node.lock().unwrap().datadir = Some(std::env::temp_dir().join("teth-test-dev"));
+        let error = rpc.set_nonce(address, 1.into()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::ServerError(-32000));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ipc_server() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        fn read_message(reader: &mut BufReader<UnixStream>) -> Value {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        let rpc = dev_rpc(false);
+        let events = rpc.node.lock().unwrap().subscribe();
+        dispatch_events(&rpc, events);
+        let path = std::env::temp_dir().join(format!("teth-test-{}.ipc", std::process::id()));
+        let _server = start_ipc_server(&path, io_handler(&rpc, &ApiModule::ALL)).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = |method: &str, params: Value| {
+            let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+            writeln!(stream, "{}", request).unwrap();
+        };
+
+        request("admin_datadir", json!([]));
+        assert_eq!(read_message(&mut reader)["result"], Value::Null);
+        request("eth_subscribe", json!(["newHeads"]));
+        let subscription = read_message(&mut reader)["result"].clone();
+        assert!(subscription.is_string());
+
+        let block_hash = rpc.node.lock().unwrap().seal_block(now()).unwrap();
+        let notification = read_message(&mut reader);
+        assert_eq!(notification["method"], "eth_subscription");
+        assert_eq!(notification["params"]["subscription"], subscription);
+        assert_eq!(notification["params"]["result"]["hash"], json!(block_hash));
+        fs::remove_file(&path).ok();
+    }
 }
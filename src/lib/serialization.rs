@@ -0,0 +1,188 @@
+//! Serialization of RPC types as specified by the
+//! [JSON-RPC API](https://github.com/ethereum/wiki/wiki/JSON-RPC#hex-value-encoding):
+//! QUANTITY values are `0x`-prefixed hex numbers without leading zeroes, DATA values `0x`-prefixed hex
+//! strings with two digits per byte. `U256` and the hash types (`H256`, `Address`...) already serialize
+//! that way; the modules here are for plain integers and byte vectors, to be used with
+//! `#[serde(with = "...")]`.
+
+use std::fmt;
+
+use ethereum_types::U256;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::lib::{from_hex, to_hex};
+
+/// Integers (`u8`, `u64`, `usize`) as QUANTITY.
+pub mod quantity {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<U256>,
+        S: Serializer,
+    {
+        let value: U256 = (*value).into();
+        value.serialize(serializer)
+    }
+}
+
+/// Optional integers as QUANTITY or `null`.
+pub mod option_quantity {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<U256>,
+        S: Serializer,
+    {
+        let value: Option<U256> = value.map(Into::into);
+        value.serialize(serializer)
+    }
+}
+
+/// Byte vectors as DATA.
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(value))
+    }
+}
+
+/// Optional byte vectors as DATA or `null`.
+pub mod option_bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => bytes::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Byte vector that (de)serializes as DATA, for parameters and results of RPC methods.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        bytes::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a 0x-prefixed hex string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Bytes, E> {
+                if !value.starts_with("0x") {
+                    return Err(E::custom(format!("missing 0x prefix: {}", value)));
+                }
+                from_hex(value).map(Bytes).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(BytesVisitor)
+    }
+}
+
+/// Integer parameter given as QUANTITY or as JSON number, as dev tools (Hardhat, Ganache) send both.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct U64(pub u64);
+
+impl<'de> Deserialize<'de> for U64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct U64Visitor;
+
+        impl<'de> Visitor<'de> for U64Visitor {
+            type Value = U64;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "an integer or a 0x-prefixed hex string")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<U64, E> {
+                Ok(U64(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<U64, E> {
+                if !value.starts_with("0x") {
+                    return Err(E::custom(format!("missing 0x prefix: {}", value)));
+                }
+                u64::from_str_radix(&value[2..], 16)
+                    .map(U64)
+                    .map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(U64Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Example {
+        #[serde(with = "quantity")]
+        number: u64,
+        #[serde(with = "quantity")]
+        index: usize,
+        #[serde(with = "option_quantity")]
+        missing: Option<u64>,
+        #[serde(with = "bytes")]
+        data: Vec<u8>,
+        #[serde(with = "option_bytes")]
+        input: Option<Vec<u8>>,
+    }
+
+    #[test]
+    fn test_serialization() {
+        let example = Example {
+            number: 1024,
+            index: 0,
+            missing: None,
+            data: vec![],
+            input: Some(vec![0, 1, 255]),
+        };
+        assert_eq!(
+            serde_json::to_value(example).unwrap(),
+            json!({
+                "number": "0x400",
+                "index": "0x0",
+                "missing": null,
+                "data": "0x",
+                "input": "0x0001ff"
+            })
+        );
+
+        let bytes: Bytes = serde_json::from_value(json!("0x0001ff")).unwrap();
+        assert_eq!(bytes, Bytes(vec![0, 1, 255]));
+        assert_eq!(serde_json::to_value(bytes).unwrap(), json!("0x0001ff"));
+        assert!(serde_json::from_value::<Bytes>(json!("0001ff")).is_err());
+
+        let integers: Vec<U64> = serde_json::from_value(json!([3600, "0xe10"])).unwrap();
+        assert_eq!(integers, vec![U64(3600), U64(3600)]);
+        assert!(serde_json::from_value::<U64>(json!(-1)).is_err());
+    }
+}
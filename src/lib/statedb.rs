@@ -0,0 +1,133 @@
+//! Storage of world states, addressable by their state root. Only the states of the most recent
+//! blocks are retained (unless in archive mode); older ones can be recomputed from the blocks, see
+//! `BlockChain::state_at`.
+//!
+//! The states are kept in memory only. On startup, the chain stored in the data directory is
+//! executed again, which recreates them.
+
+use std::collections::{HashMap, VecDeque};
+
+use ethereum_types::H256;
+
+use crate::lib::WorldState;
+
+/// Number of recent states kept by default, as in geth.
+pub const DEFAULT_STATE_HISTORY: usize = 128;
+
+/// Which states a `StateDb` keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateRetention {
+    /// The given number of most recently added states, plus the first one (the genesis state).
+    Recent(usize),
+    /// All states (archive node).
+    Archive,
+}
+
+impl Default for StateRetention {
+    fn default() -> Self {
+        StateRetention::Recent(DEFAULT_STATE_HISTORY)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StateDb {
+    states: HashMap<H256, WorldState>,
+    /// State roots in the order they were added, except the first one and pinned ones, which are
+    /// never pruned.
+    history: VecDeque<H256>,
+    /// The root of the first state.
+    base_root: Option<H256>,
+    retention: StateRetention,
+}
+
+impl StateDb {
+    /// Adds a state with the given root, pruning the oldest state if more than allowed by the
+    /// retention are stored.
+    pub fn insert(&mut self, state_root: H256, state: WorldState) {
+        if self.states.insert(state_root, state).is_some() {
+            return;
+        }
+        if self.base_root.is_none() {
+            self.base_root = Some(state_root);
+            return;
+        }
+        self.history.push_back(state_root);
+        self.prune();
+    }
+
+    /// Adds a state that's kept regardless of the retention, because it can't be recomputed from the
+    /// blocks (see `Node::modify_state`).
+    pub fn pin(&mut self, state_root: H256, state: WorldState) {
+        self.history.retain(|root| *root != state_root);
+        self.states.insert(state_root, state);
+    }
+
+    pub fn set_retention(&mut self, retention: StateRetention) {
+        self.retention = retention;
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        if let StateRetention::Recent(n) = self.retention {
+            while self.history.len() > n {
+                if let Some(root) = self.history.pop_front() {
+                    self.states.remove(&root);
+                }
+            }
+        }
+    }
+
+    /// StateAt(root) - the state with the given root, if it's retained.
+    pub fn get(&self, state_root: &H256) -> Option<&WorldState> {
+        self.states.get(state_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::AccountState;
+    use ethereum_types::Address;
+
+    fn state(balance: u64) -> (H256, WorldState) {
+        let mut state = WorldState::new();
+        state
+            .accounts
+            .insert(Address::zero(), AccountState::new(balance.into()));
+        (state.state_root(), state)
+    }
+
+    fn state_db(retention: StateRetention) -> StateDb {
+        StateDb {
+            retention,
+            ..StateDb::default()
+        }
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut db = state_db(StateRetention::Recent(2));
+        let roots: Vec<H256> = (0..5)
+            .map(|i| {
+                let (root, state) = state(i);
+                db.insert(root, state);
+                root
+            })
+            .collect();
+        assert_eq!(db.states.len(), 3);
+        assert!(db.get(&roots[0]).is_some());
+        assert!(db.get(&roots[1]).is_none());
+        assert!(db.get(&roots[2]).is_none());
+        assert_eq!(db.get(&roots[4]).unwrap().state_root(), roots[4]);
+
+        let mut archive = state_db(StateRetention::Archive);
+        for i in 0..5 {
+            let (root, state) = state(i);
+            archive.insert(root, state);
+        }
+        assert_eq!(archive.states.len(), 5);
+        archive.set_retention(StateRetention::Recent(0));
+        assert_eq!(archive.states.len(), 1);
+        assert!(archive.get(&roots[0]).is_some());
+    }
+}
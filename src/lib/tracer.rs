@@ -0,0 +1,475 @@
+//! Tracing of transaction execution for `debug_traceTransaction` and `debug_traceCall`. Execution
+//! reports the calls it enters and leaves and every step of the EVM to a `Tracer`, which turns them
+//! into its output - the struct logs of geth's default tracer (`StructLogger`) or a call tree
+//! (`CallTracer`).
+
+use std::collections::BTreeMap;
+
+use ethereum_types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::lib::serialization::bytes;
+use crate::lib::{to_hex, Wei};
+
+/// Kind of a call frame, named like the opcodes that start them.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CallKind {
+    Call,
+    Create,
+}
+
+/// A step of the EVM, reported before the operation is executed.
+#[derive(Debug)]
+pub struct Step<'a> {
+    /// Program counter.
+    pub pc: usize,
+    /// The opcode of the operation.
+    pub op: u8,
+    /// Gas available before the operation.
+    pub gas: u64,
+    /// Gas the operation costs.
+    pub gas_cost: u64,
+    /// Depth of the call frame, 1 for the transaction itself.
+    pub depth: usize,
+    /// The stack, bottom first.
+    pub stack: &'a [U256],
+    /// The memory of the call frame.
+    pub memory: &'a [u8],
+    /// The storage key and new value written by the operation (SSTORE).
+    pub storage_change: Option<(H256, H256)>,
+}
+
+/// Receives the events of an execution. Calls are properly nested: each `enter` is followed by the
+/// steps and nested calls of the frame and then by its `exit`. There's no EVM yet, so only the
+/// frame of the transaction itself is reported and there are no steps.
+pub trait Tracer {
+    /// Start of a message call or contract creation from `from` to `to` (the new account for
+    /// creations) with the given gas limit.
+    fn enter(
+        &mut self,
+        _kind: CallKind,
+        _from: Address,
+        _to: Address,
+        _value: Wei,
+        _gas: U256,
+        _input: &[u8],
+    ) {
+    }
+
+    /// A step of the EVM in the innermost call.
+    fn step(&mut self, _step: &Step) {}
+
+    /// End of the innermost call, with the error if it failed.
+    fn exit(&mut self, _gas_used: U256, _output: &[u8], _error: Option<&str>) {}
+}
+
+/// Tracer for untraced execution, which ignores all events.
+pub struct NoopTracer;
+
+impl Tracer for NoopTracer {}
+
+/// What the struct logs include, as in geth (all but the memory by default).
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StructLoggerConfig {
+    pub disable_stack: bool,
+    pub disable_storage: bool,
+    pub enable_memory: bool,
+}
+
+/// A logged step, see `Step`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: usize,
+    /// Name of the opcode.
+    pub op: String,
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub depth: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<U256>>,
+    /// The memory in words of 32 bytes (hex without `0x`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<Vec<String>>,
+    /// The storage written so far in the call frame (hex without `0x`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<BTreeMap<String, String>>,
+}
+
+/// Output of the `StructLogger` in the format of geth.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionResult {
+    /// Gas used by the transaction.
+    pub gas: u64,
+    pub failed: bool,
+    /// Output of the transaction (hex without `0x`).
+    pub return_value: String,
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// geth's default tracer: logs every step along with the stack, memory and storage.
+#[derive(Debug, Default)]
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<StructLog>,
+    /// Storage written by each call frame in progress, innermost last.
+    storage: Vec<BTreeMap<H256, H256>>,
+    result: ExecutionResult,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        StructLogger {
+            config,
+            ..StructLogger::default()
+        }
+    }
+
+    pub fn into_result(self) -> ExecutionResult {
+        ExecutionResult {
+            struct_logs: self.logs,
+            ..self.result
+        }
+    }
+}
+
+impl Tracer for StructLogger {
+    fn enter(&mut self, _: CallKind, _: Address, _: Address, _: Wei, _: U256, _: &[u8]) {
+        self.storage.push(BTreeMap::new());
+    }
+
+    fn step(&mut self, step: &Step) {
+        let storage = self.storage.last_mut();
+        if let (Some(storage), Some((key, value))) = (storage, step.storage_change) {
+            storage.insert(key, value);
+        }
+        let hex = |bytes: &[u8]| to_hex(bytes)[2..].to_string();
+        let config = self.config;
+        let stack = Some(step.stack).filter(|_| !config.disable_stack);
+        let memory = Some(step.memory).filter(|_| config.enable_memory);
+        let storage = self.storage.last().filter(|_| !config.disable_storage);
+        self.logs.push(StructLog {
+            pc: step.pc,
+            op: opcode_name(step.op).map_or_else(
+                || format!("opcode {:#04x} not defined", step.op),
+                str::to_string,
+            ),
+            gas: step.gas,
+            gas_cost: step.gas_cost,
+            depth: step.depth,
+            stack: stack.map(<[U256]>::to_vec),
+            memory: memory.map(|memory| memory.chunks(32).map(hex).collect()),
+            storage: storage.map(|storage| {
+                storage
+                    .iter()
+                    .map(|(key, value)| (hex(&key[..]), hex(&value[..])))
+                    .collect()
+            }),
+        });
+    }
+
+    fn exit(&mut self, gas_used: U256, output: &[u8], error: Option<&str>) {
+        self.storage.pop();
+        if self.storage.is_empty() {
+            self.result = ExecutionResult {
+                gas: gas_used.low_u64(),
+                failed: error.is_some(),
+                return_value: to_hex(output)[2..].to_string(),
+                struct_logs: vec![],
+            };
+        }
+    }
+}
+
+/// A call of the call tree, as returned by geth's `callTracer`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    #[serde(rename = "type")]
+    pub kind: CallKind,
+    pub from: Address,
+    pub to: Address,
+    pub value: Wei,
+    pub gas: U256,
+    pub gas_used: U256,
+    #[serde(with = "bytes")]
+    pub input: Vec<u8>,
+    #[serde(with = "bytes")]
+    pub output: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Nested calls, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+/// Builds the tree of calls (`callTracer`), ignoring the steps.
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    /// Calls in progress, innermost last.
+    frames: Vec<CallFrame>,
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    /// The frame of the transaction, `None` if nothing was executed.
+    pub fn into_result(self) -> Option<CallFrame> {
+        self.root
+    }
+}
+
+impl Tracer for CallTracer {
+    fn enter(
+        &mut self,
+        kind: CallKind,
+        from: Address,
+        to: Address,
+        value: Wei,
+        gas: U256,
+        input: &[u8],
+    ) {
+        self.frames.push(CallFrame {
+            kind,
+            from,
+            to,
+            value,
+            gas,
+            gas_used: U256::zero(),
+            input: input.to_vec(),
+            output: vec![],
+            error: None,
+            calls: vec![],
+        });
+    }
+
+    fn exit(&mut self, gas_used: U256, output: &[u8], error: Option<&str>) {
+        let mut frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        frame.gas_used = gas_used;
+        frame.output = output.to_vec();
+        frame.error = error.map(str::to_string);
+        match self.frames.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+/// Appendix H.2 - the mnemonic of an opcode, `None` if it's not defined.
+pub fn opcode_name(op: u8) -> Option<&'static str> {
+    let name = match op {
+        0x00 => "STOP",
+        0x01 => "ADD",
+        0x02 => "MUL",
+        0x03 => "SUB",
+        0x04 => "DIV",
+        0x05 => "SDIV",
+        0x06 => "MOD",
+        0x07 => "SMOD",
+        0x08 => "ADDMOD",
+        0x09 => "MULMOD",
+        0x0a => "EXP",
+        0x0b => "SIGNEXTEND",
+        0x10 => "LT",
+        0x11 => "GT",
+        0x12 => "SLT",
+        0x13 => "SGT",
+        0x14 => "EQ",
+        0x15 => "ISZERO",
+        0x16 => "AND",
+        0x17 => "OR",
+        0x18 => "XOR",
+        0x19 => "NOT",
+        0x1a => "BYTE",
+        0x1b => "SHL",
+        0x1c => "SHR",
+        0x1d => "SAR",
+        0x20 => "SHA3",
+        0x30 => "ADDRESS",
+        0x31 => "BALANCE",
+        0x32 => "ORIGIN",
+        0x33 => "CALLER",
+        0x34 => "CALLVALUE",
+        0x35 => "CALLDATALOAD",
+        0x36 => "CALLDATASIZE",
+        0x37 => "CALLDATACOPY",
+        0x38 => "CODESIZE",
+        0x39 => "CODECOPY",
+        0x3a => "GASPRICE",
+        0x3b => "EXTCODESIZE",
+        0x3c => "EXTCODECOPY",
+        0x3d => "RETURNDATASIZE",
+        0x3e => "RETURNDATACOPY",
+        0x3f => "EXTCODEHASH",
+        0x40 => "BLOCKHASH",
+        0x41 => "COINBASE",
+        0x42 => "TIMESTAMP",
+        0x43 => "NUMBER",
+        0x44 => "DIFFICULTY",
+        0x45 => "GASLIMIT",
+        0x46 => "CHAINID",
+        0x47 => "SELFBALANCE",
+        0x48 => "BASEFEE",
+        0x50 => "POP",
+        0x51 => "MLOAD",
+        0x52 => "MSTORE",
+        0x53 => "MSTORE8",
+        0x54 => "SLOAD",
+        0x55 => "SSTORE",
+        0x56 => "JUMP",
+        0x57 => "JUMPI",
+        0x58 => "PC",
+        0x59 => "MSIZE",
+        0x5a => "GAS",
+        0x5b => "JUMPDEST",
+        0x60..=0x7f => PUSH[(op - 0x60) as usize],
+        0x80..=0x8f => DUP[(op - 0x80) as usize],
+        0x90..=0x9f => SWAP[(op - 0x90) as usize],
+        0xa0..=0xa4 => LOG[(op - 0xa0) as usize],
+        0xf0 => "CREATE",
+        0xf1 => "CALL",
+        0xf2 => "CALLCODE",
+        0xf3 => "RETURN",
+        0xf4 => "DELEGATECALL",
+        0xf5 => "CREATE2",
+        0xfa => "STATICCALL",
+        0xfd => "REVERT",
+        0xfe => "INVALID",
+        0xff => "SELFDESTRUCT",
+        _ => return None,
+    };
+    Some(name)
+}
+
+const PUSH: [&str; 32] = [
+    "PUSH1", "PUSH2", "PUSH3", "PUSH4", "PUSH5", "PUSH6", "PUSH7", "PUSH8", "PUSH9", "PUSH10",
+    "PUSH11", "PUSH12", "PUSH13", "PUSH14", "PUSH15", "PUSH16", "PUSH17", "PUSH18", "PUSH19",
+    "PUSH20", "PUSH21", "PUSH22", "PUSH23", "PUSH24", "PUSH25", "PUSH26", "PUSH27", "PUSH28",
+    "PUSH29", "PUSH30", "PUSH31", "PUSH32",
+];
+
+const DUP: [&str; 16] = [
+    "DUP1", "DUP2", "DUP3", "DUP4", "DUP5", "DUP6", "DUP7", "DUP8", "DUP9", "DUP10", "DUP11",
+    "DUP12", "DUP13", "DUP14", "DUP15", "DUP16",
+];
+
+const SWAP: [&str; 16] = [
+    "SWAP1", "SWAP2", "SWAP3", "SWAP4", "SWAP5", "SWAP6", "SWAP7", "SWAP8", "SWAP9", "SWAP10",
+    "SWAP11", "SWAP12", "SWAP13", "SWAP14", "SWAP15", "SWAP16",
+];
+
+const LOG: [&str; 5] = ["LOG0", "LOG1", "LOG2", "LOG3", "LOG4"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(pc: usize, op: u8, stack: &[U256], storage_change: Option<(H256, H256)>) -> Step<'_> {
+        Step {
+            pc,
+            op,
+            gas: 100 - pc as u64,
+            gas_cost: 3,
+            depth: 1,
+            stack,
+            memory: &[0xff; 40],
+            storage_change,
+        }
+    }
+
+    #[test]
+    fn test_struct_logger() {
+        let mut logger = StructLogger::new(StructLoggerConfig::default());
+        let stack = [U256::from(1), U256::from(2)];
+        logger.enter(
+            CallKind::Call,
+            Address::from(1),
+            Address::from(2),
+            0.into(),
+            100.into(),
+            &[],
+        );
+        logger.step(&step(0, 0x60, &stack[..1], None));
+        logger.step(&step(2, 0x55, &stack, Some((H256::from(2), H256::from(1)))));
+        logger.step(&step(3, 0x0c, &[], None));
+        logger.exit(21003.into(), &[0xab], None);
+        let result = logger.into_result();
+        assert_eq!((result.gas, result.failed), (21003, false));
+        assert_eq!(result.return_value, "ab");
+
+        let logs = &result.struct_logs;
+        let ops: Vec<&str> = logs.iter().map(|log| log.op.as_str()).collect();
+        assert_eq!(ops, vec!["PUSH1", "SSTORE", "opcode 0x0c not defined"]);
+        assert_eq!(logs[1].stack, Some(stack.to_vec()));
+        assert_eq!(logs[1].memory, None);
+        assert_eq!(logs[0].storage, Some(BTreeMap::new()));
+        let storage = logs[2].storage.as_ref().unwrap();
+        assert_eq!(storage.len(), 1);
+        assert!(storage[&format!("{:0>64}", 2)].ends_with("01"));
+
+        let config = StructLoggerConfig {
+            disable_stack: true,
+            disable_storage: true,
+            enable_memory: true,
+        };
+        let mut logger = StructLogger::new(config);
+        logger.enter(
+            CallKind::Create,
+            Address::from(1),
+            Address::from(2),
+            0.into(),
+            100.into(),
+            &[],
+        );
+        logger.step(&step(0, 0x00, &stack, None));
+        logger.exit(100.into(), &[], Some("out of gas"));
+        let result = logger.into_result();
+        assert!(result.failed);
+        let log = &result.struct_logs[0];
+        assert_eq!((log.stack.as_ref(), log.storage.as_ref()), (None, None));
+        let memory = log.memory.as_ref().unwrap();
+        assert_eq!((memory.len(), memory[1].as_str()), (2, "ffffffffffffffff"));
+    }
+
+    #[test]
+    fn test_call_tracer() {
+        let mut tracer = CallTracer::default();
+        let (a, b, c) = (Address::from(1), Address::from(2), Address::from(3));
+        tracer.enter(CallKind::Call, a, b, 5.into(), 50000.into(), &[1, 2]);
+        tracer.step(&step(0, 0xf1, &[], None));
+        tracer.enter(CallKind::Call, b, c, 0.into(), 1000.into(), &[]);
+        tracer.exit(1000.into(), &[], Some("out of gas"));
+        tracer.enter(CallKind::Create, b, c, 1.into(), 2000.into(), &[0x60]);
+        tracer.exit(500.into(), &[0x00], None);
+        tracer.exit(30000.into(), &[0x01], None);
+        let root = tracer.into_result().unwrap();
+        assert_eq!((root.from, root.to, root.gas_used), (a, b, 30000.into()));
+        assert_eq!((root.input, root.output), (vec![1, 2], vec![1]));
+        let kinds: Vec<CallKind> = root.calls.iter().map(|call| call.kind).collect();
+        assert_eq!(kinds, vec![CallKind::Call, CallKind::Create]);
+        assert_eq!(root.calls[0].error, Some("out of gas".to_string()));
+        assert!(root.calls[1].calls.is_empty());
+
+        let json = serde_json::to_value(&root.calls[1]).unwrap();
+        assert_eq!(json["type"], "CREATE");
+        assert_eq!(json["gasUsed"], "0x1f4");
+        assert_eq!(json["input"], "0x60");
+        assert!(json.get("error").is_none() && json.get("calls").is_none());
+    }
+
+    #[test]
+    fn test_opcode_name() {
+        assert_eq!(opcode_name(0x60), Some("PUSH1"));
+        assert_eq!(opcode_name(0x7f), Some("PUSH32"));
+        assert_eq!(opcode_name(0x8f), Some("DUP16"));
+        assert_eq!(opcode_name(0x9f), Some("SWAP16"));
+        assert_eq!(opcode_name(0xa4), Some("LOG4"));
+        assert_eq!(opcode_name(0xfa), Some("STATICCALL"));
+        assert_eq!(opcode_name(0xa5), None);
+    }
+}
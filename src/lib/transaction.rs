@@ -1,15 +1,23 @@
+use std::fmt;
+
 use ethereum_types::{Address, H256, U256};
 use lazy_static::lazy_static;
-use rlp::{encode, Encodable, RlpStream};
+use rlp::{encode, Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use secp256k1::{Message, PublicKey, RecoverableSignature, RecoveryId, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
 use tiny_keccak::keccak256;
 
-use crate::lib::{AccountState, Wei, WorldState, FEES};
+use crate::lib::{
+    AccountState, BlockHeader, CallKind, ChainConfig, LogEntry, NoopTracer, Tracer, Wei,
+    WorldState, FEES,
+};
 
 /// ECDSA signature
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Signature {
-    /// T<sub>w</sub>
-    pub v: u8,
+    /// T<sub>w</sub> - 27 or 28 for legacy signatures, chainId * 2 + 35 or 36 with
+    /// replay protection (EIP-155), the y-parity 0 or 1 for typed transactions (T<sub>y</sub>).
+    pub v: u64,
     /// T<sub>r</sub>
     pub r: U256,
     /// T<sub>s</sub>
@@ -25,26 +33,236 @@ lazy_static! {
 
 #[allow(dead_code)]
 impl Signature {
-    pub fn new(v: u8, r: U256, s: U256) -> Self {
+    pub fn new(v: u64, r: U256, s: U256) -> Self {
         Self { v, r, s }
     }
 
+    /// The chain ID the signature is bound to (EIP-155), `None` for legacy signatures.
+    pub fn chain_id(&self) -> Option<u64> {
+        if self.v >= 35 {
+            Some((self.v - 35) / 2)
+        } else {
+            None
+        }
+    }
+
+    /// The recovery id (0 or 1) encoded in T<sub>w</sub>.
+    pub fn recovery_id(&self) -> Option<u8> {
+        match self.v {
+            0 | 1 => Some(self.v as u8),
+            27 | 28 => Some((self.v - 27) as u8),
+            v if v >= 35 => Some(((v - 35) % 2) as u8),
+            _ => None,
+        }
+    }
+
     /// We declare that an ECDSA signature is invalid unless all the following conditions are true:  
     ///     0 < r < secp256k1n  
     ///     0 < s < secp256k1n÷2+1  
     ///     v ∈ {27, 28}  
     /// where:  
     ///     secp256k1n = 115792089237316195423570985008687907852837564279074904382605163141518161494337
+    ///
+    /// With EIP-155, v ∈ {chainId * 2 + 35, chainId * 2 + 36} is valid as well, and typed transactions
+    /// use v ∈ {0, 1}.
     pub fn is_valid(&self) -> bool {
         self.r > U256::zero()
             && self.r < *SECP256K1N
             && self.s > U256::zero()
             && self.s < *SECP256K1N / 2 + 1
-            && self.v == 27
-            || self.v == 28
+            && self.recovery_id().is_some()
+    }
+}
+
+/// Reasons for a transaction to be invalid (Section 6 and Equation 58).
+///
+/// Serialized as `{"reason": "nonceTooLow", "details": {"expected": .., "got": ..}}`, e.g. for
+/// the data of JSON-RPC errors.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", content = "details", rename_all = "camelCase")]
+pub enum TransactionError {
+    /// S(T) = ∅
+    InvalidSignature,
+    /// T<sub>n</sub> < σ[S(T)]<sub>n</sub>
+    NonceTooLow { expected: U256, got: U256 },
+    /// T<sub>n</sub> > σ[S(T)]<sub>n</sub>
+    NonceTooHigh { expected: U256, got: U256 },
+    /// g<sub>0</sub> > T<sub>g</sub>
+    IntrinsicGasTooLow { required: u64, got: U256 },
+    /// v<sub>0</sub> > σ[S(T)]<sub>b</sub>
+    InsufficientFunds { required: U256, balance: U256 },
+    /// T<sub>g</sub> > B<sub>H<sub>l</sub></sub> − l(B<sub>R</sub>)<sub>u</sub>
+    BlockGasLimitExceeded { available: U256, got: U256 },
+    /// The transaction is signed for another chain, or EIP-155 isn't active yet.
+    InvalidChainId(u64),
+    /// T<sub>x</sub> is unknown or the fork introducing it isn't active yet.
+    UnsupportedType(u8),
+    /// T<sub>f</sub> > T<sub>m</sub>
+    PriorityFeeAboveMaxFee { max_priority_fee: Wei, max_fee: Wei },
+    /// T<sub>m</sub> (or T<sub>p</sub>) < H<sub>f</sub>
+    MaxFeeBelowBaseFee { max_fee: Wei, base_fee: Wei },
+    /// The transaction isn't replay-protected (EIP-155) and the node doesn't accept those.
+    Unprotected,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::TransactionError::*;
+        match self {
+            InvalidSignature => write!(f, "invalid signature"),
+            NonceTooLow { expected, got } => {
+                write!(f, "nonce too low (expected {}, got {})", expected, got)
+            }
+            NonceTooHigh { expected, got } => {
+                write!(f, "nonce too high (expected {}, got {})", expected, got)
+            }
+            IntrinsicGasTooLow { required, got } => write!(
+                f,
+                "intrinsic gas too low (required {}, got {})",
+                required, got
+            ),
+            InsufficientFunds { required, balance } => write!(
+                f,
+                "insufficient funds (required {}, balance {})",
+                required, balance
+            ),
+            BlockGasLimitExceeded { available, got } => write!(
+                f,
+                "exceeds block gas limit (available {}, got {})",
+                available, got
+            ),
+            InvalidChainId(chain_id) => write!(f, "invalid chain id {}", chain_id),
+            UnsupportedType(transaction_type) => {
+                write!(f, "transaction type {} not supported", transaction_type)
+            }
+            PriorityFeeAboveMaxFee {
+                max_priority_fee,
+                max_fee,
+            } => write!(
+                f,
+                "max priority fee per gas higher than max fee per gas ({} > {})",
+                max_priority_fee, max_fee
+            ),
+            MaxFeeBelowBaseFee { max_fee, base_fee } => write!(
+                f,
+                "max fee per gas less than block base fee ({} < {})",
+                max_fee, base_fee
+            ),
+            Unprotected => write!(
+                f,
+                "only replay-protected (EIP-155) transactions are allowed"
+            ),
+        }
+    }
+}
+
+/// The result of executing a transaction, Υ<sup>g</sup> and Υ<sup>l</sup> (Section 6).
+#[derive(Debug, Default)]
+pub struct TransactionOutcome {
+    /// The total gas used by the transaction.
+    pub gas_used: U256,
+    /// The logs created during execution.
+    pub logs: Vec<LogEntry>,
+    /// o - the output data of a message call (always empty, there's no EVM yet).
+    pub output: Vec<u8>,
+}
+
+/// An entry of an access list (EIP-2930): an address and the storage keys the transaction plans
+/// to access, which are then charged for up-front.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
+impl Encodable for AccessListItem {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.address);
+        s.append_list(&self.storage_keys);
     }
 }
 
+impl Decodable for AccessListItem {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            address: rlp.val_at(0)?,
+            storage_keys: rlp.list_at(1)?,
+        })
+    }
+}
+
+/// A legacy transaction (type 0), the original transaction format.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LegacyTransaction {
+    /// T<sub>n</sub>
+    pub nonce: U256,
+    /// T<sub>p</sub>
+    pub gas_price: Wei,
+    /// T<sub>g</sub>
+    pub gas_limit: U256,
+    /// T<sub>t</sub>
+    pub to: Option<Address>,
+    /// T<sub>v</sub>
+    pub value: Wei,
+    /// T<sub>i</sub> for contract creations, T<sub>d</sub> for message calls.
+    pub data: Vec<u8>,
+    /// T<sub>w</sub>, T<sub>r</sub> and T<sub>s</sub>
+    pub signature: Signature,
+}
+
+/// A transaction with an access list (type 1, EIP-2930).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AccessListTransaction {
+    /// T<sub>c</sub>
+    pub chain_id: u64,
+    /// T<sub>n</sub>
+    pub nonce: U256,
+    /// T<sub>p</sub>
+    pub gas_price: Wei,
+    /// T<sub>g</sub>
+    pub gas_limit: U256,
+    /// T<sub>t</sub>
+    pub to: Option<Address>,
+    /// T<sub>v</sub>
+    pub value: Wei,
+    /// T<sub>i</sub> for contract creations, T<sub>d</sub> for message calls.
+    pub data: Vec<u8>,
+    /// T<sub>A</sub>
+    pub access_list: Vec<AccessListItem>,
+    /// T<sub>y</sub>, T<sub>r</sub> and T<sub>s</sub>
+    pub signature: Signature,
+}
+
+/// A transaction with a max fee and a priority fee per gas instead of a gas price (type 2, EIP-1559).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DynamicFeeTransaction {
+    /// T<sub>c</sub>
+    pub chain_id: u64,
+    /// T<sub>n</sub>
+    pub nonce: U256,
+    /// T<sub>f</sub>
+    pub max_priority_fee_per_gas: Wei,
+    /// T<sub>m</sub>
+    pub max_fee_per_gas: Wei,
+    /// T<sub>g</sub>
+    pub gas_limit: U256,
+    /// T<sub>t</sub>
+    pub to: Option<Address>,
+    /// T<sub>v</sub>
+    pub value: Wei,
+    /// T<sub>i</sub> for contract creations, T<sub>d</sub> for message calls.
+    pub data: Vec<u8>,
+    /// T<sub>A</sub>
+    pub access_list: Vec<AccessListItem>,
+    /// T<sub>y</sub>, T<sub>r</sub> and T<sub>s</sub>
+    pub signature: Signature,
+}
+
 /// T
 ///
 /// A transaction (formally, T) is a single cryptographically-signed instruction constructed by an
@@ -53,56 +271,255 @@ impl Signature {
 /// There are two types of transactions: those which result in message calls and those which
 /// result in the creation of new accounts with associated code (known informally as ‘contract
 /// creation’).
+///
+/// Each variant is one transaction type T<sub>x</sub> (EIP-2718) with the fields of that type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transaction {
+    /// Type 0
+    Legacy(LegacyTransaction),
+    /// Type 1 (EIP-2930)
+    AccessList(AccessListTransaction),
+    /// Type 2 (EIP-1559)
+    DynamicFee(DynamicFeeTransaction),
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Transaction::Legacy(LegacyTransaction::default())
+    }
+}
+
+impl From<LegacyTransaction> for Transaction {
+    fn from(transaction: LegacyTransaction) -> Self {
+        Transaction::Legacy(transaction)
+    }
+}
+
+impl From<AccessListTransaction> for Transaction {
+    fn from(transaction: AccessListTransaction) -> Self {
+        Transaction::AccessList(transaction)
+    }
+}
+
+impl From<DynamicFeeTransaction> for Transaction {
+    fn from(transaction: DynamicFeeTransaction) -> Self {
+        Transaction::DynamicFee(transaction)
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Debug, Default)]
-pub struct Transaction {
+impl Transaction {
+    /// T<sub>x</sub> - the type byte, which prefixes the RLP of typed transactions and their receipts.
+    pub fn transaction_type(&self) -> u8 {
+        match self {
+            Transaction::Legacy(_) => 0,
+            Transaction::AccessList(_) => 1,
+            Transaction::DynamicFee(_) => 2,
+        }
+    }
+
     /// A scalar value equal to the number of transactions sent by the sender; formally T<sub>n</sub>.
-    pub nonce: U256,
+    pub fn nonce(&self) -> U256 {
+        match self {
+            Transaction::Legacy(t) => t.nonce,
+            Transaction::AccessList(t) => t.nonce,
+            Transaction::DynamicFee(t) => t.nonce,
+        }
+    }
+
     /// A scalar value equal to the number of Wei to be paid per unit of _gas_ for all computation costs incurred
     /// as a result of the execution of this transaction; formally T<sub>p</sub>.
-    pub gas_price: Wei,
+    /// For EIP-1559 transactions, this is the max fee per gas T<sub>m</sub>.
+    pub fn gas_price(&self) -> Wei {
+        match self {
+            Transaction::Legacy(t) => t.gas_price,
+            Transaction::AccessList(t) => t.gas_price,
+            Transaction::DynamicFee(t) => t.max_fee_per_gas,
+        }
+    }
+
     /// A scalar value equal to the maximum amount of gas that should be used in executing this transaction.
     /// This is paid up-front, before any computation is done and may not be increased later; formally T<sub>g</sub>.
-    pub gas_limit: U256,
+    pub fn gas_limit(&self) -> U256 {
+        match self {
+            Transaction::Legacy(t) => t.gas_limit,
+            Transaction::AccessList(t) => t.gas_limit,
+            Transaction::DynamicFee(t) => t.gas_limit,
+        }
+    }
+
+    /// Sets T<sub>g</sub>, e.g. while estimating the gas of an unsigned transaction.
+    pub fn set_gas_limit(&mut self, gas_limit: U256) {
+        match self {
+            Transaction::Legacy(t) => t.gas_limit = gas_limit,
+            Transaction::AccessList(t) => t.gas_limit = gas_limit,
+            Transaction::DynamicFee(t) => t.gas_limit = gas_limit,
+        }
+    }
+
     /// The 160-bit address of the message call’s recipient or, for a contract creation transaction, ∅,
     /// used here to denote the only member of B<sub>0</sub>; formally T<sub>t</sub>.
-    pub to: Option<Address>,
+    pub fn to(&self) -> Option<Address> {
+        match self {
+            Transaction::Legacy(t) => t.to,
+            Transaction::AccessList(t) => t.to,
+            Transaction::DynamicFee(t) => t.to,
+        }
+    }
+
     /// A scalar value equal to the number of Wei to be transferred to the message call’s recipient or,
     /// in the case of contract creation, as an endowment to the newly created account; formally T<sub>v</sub>.
-    pub value: Wei,
-
-    /// Values corresponding to the signature of the transaction and used to determine the sender of the transaction;
-    /// formally T<sub>w</sub>, T<sub>r</sub> and T<sub>s</sub>. This is expanded in Appendix F.
-    pub signature: Signature,
+    pub fn value(&self) -> Wei {
+        match self {
+            Transaction::Legacy(t) => t.value,
+            Transaction::AccessList(t) => t.value,
+            Transaction::DynamicFee(t) => t.value,
+        }
+    }
 
-    /// An unlimited size byte array specifying the EVM-code for the account initialisation procedure, formally
-    /// T<sub>i</sub>.  
+    /// For contract creations, an unlimited size byte array specifying the EVM-code for the account
+    /// initialisation procedure, formally T<sub>i</sub>.
     /// `init` is an EVM-code fragment; it returns the `body`, a second fragment of code that executes each time the
     /// account receives a message call (either through a transaction or due to the internal execution of code).
-    /// `init` is executed only once at account creation and gets discarded immediately thereafter.  
-    /// Empty for message calls.
-    pub init: Option<Vec<u8>>,
+    /// `init` is executed only once at account creation and gets discarded immediately thereafter.
+    ///
+    /// For message calls, an unlimited size byte array specifying the input data of the message call,
+    /// formally T<sub>d</sub>.
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Transaction::Legacy(t) => &t.data,
+            Transaction::AccessList(t) => &t.data,
+            Transaction::DynamicFee(t) => &t.data,
+        }
+    }
 
-    /// An unlimited size byte array specifying the input data of the message call, formally T<sub>d</sub>.  
-    /// Empty for contract creation transactions.
-    pub data: Option<Vec<u8>>,
-}
+    /// T<sub>A</sub> - empty for legacy transactions.
+    pub fn access_list(&self) -> &[AccessListItem] {
+        match self {
+            Transaction::Legacy(_) => &[],
+            Transaction::AccessList(t) => &t.access_list,
+            Transaction::DynamicFee(t) => &t.access_list,
+        }
+    }
+
+    /// Values corresponding to the signature of the transaction and used to determine the sender of the transaction;
+    /// formally T<sub>w</sub> (T<sub>y</sub> for typed transactions), T<sub>r</sub> and T<sub>s</sub>.
+    /// This is expanded in Appendix F.
+    pub fn signature(&self) -> &Signature {
+        match self {
+            Transaction::Legacy(t) => &t.signature,
+            Transaction::AccessList(t) => &t.signature,
+            Transaction::DynamicFee(t) => &t.signature,
+        }
+    }
+
+    fn signature_mut(&mut self) -> &mut Signature {
+        match self {
+            Transaction::Legacy(t) => &mut t.signature,
+            Transaction::AccessList(t) => &mut t.signature,
+            Transaction::DynamicFee(t) => &mut t.signature,
+        }
+    }
 
-#[allow(dead_code)]
-impl Transaction {
     /// S
     ///
     /// Appendix F specifies the function, S, which maps transactions to the sender, and happens through
     /// the ECDSA of the SECP-256k1 curve, using the hash of the transaction (excepting the latter three
     /// signature fields) as the datum to sign. For the present we simply assert that the sender of a
     /// given transaction T can be represented with S(T).
-    pub fn sender(&self) -> Address {
-        // unimplemented!() // TODO!!!!
-        Address::random()
+    ///
+    /// `None` if the signature is invalid (S(T) = ∅).
+    pub fn sender(&self) -> Option<Address> {
+        // typed transactions have the y-parity as v, legacy ones 27/28 or the EIP-155 value
+        let signature = self.signature();
+        let is_typed = self.transaction_type() != 0;
+        if !signature.is_valid() || is_typed != (signature.v <= 1) {
+            return None;
+        }
+        let recovery_id = RecoveryId::from_i32(i32::from(signature.recovery_id()?)).ok()?;
+        let mut compact = [0u8; 64];
+        signature.r.to_big_endian(&mut compact[..32]);
+        signature.s.to_big_endian(&mut compact[32..]);
+        let signature = RecoverableSignature::from_compact(&compact, recovery_id).ok()?;
+        let message = Message::from_slice(&self.signing_hash(self.chain_id())).ok()?;
+        let public_key = Secp256k1::verification_only()
+            .recover(&message, &signature)
+            .ok()?;
+        Some(public_key_to_address(&public_key))
+    }
+
+    /// The chain ID the transaction is bound to - T<sub>c</sub> for typed transactions, from the
+    /// signature for legacy ones (EIP-155). `None` if it isn't replay-protected.
+    pub fn chain_id(&self) -> Option<u64> {
+        match self {
+            Transaction::Legacy(t) => t.signature.chain_id(),
+            Transaction::AccessList(t) => Some(t.chain_id),
+            Transaction::DynamicFee(t) => Some(t.chain_id),
+        }
+    }
+
+    /// h(T) - the hash of the transaction without its signature (Equation 283 ff.)
+    ///
+    /// For legacy transactions with a chain ID, the hash additionally covers (chainId, 0, 0) (EIP-155).
+    /// Typed transactions always hash T<sub>x</sub> · RLP(fields) with their own chain ID, `chain_id`
+    /// is ignored for them.
+    pub fn signing_hash(&self, chain_id: Option<u64>) -> H256 {
+        let mut s = RlpStream::new();
+        match (self, chain_id) {
+            (Transaction::Legacy(_), Some(chain_id)) => {
+                s.begin_list(9);
+                self.append_unsigned_fields(&mut s);
+                s.append(&chain_id);
+                s.append_empty_data();
+                s.append_empty_data();
+            }
+            _ => {
+                s.begin_list(self.unsigned_field_count());
+                self.append_unsigned_fields(&mut s);
+            }
+        }
+        keccak256(&self.with_type_prefix(s.out())).into()
     }
 
+    /// Signs the transaction with the given private key. Legacy transactions are replay-protected
+    /// for the given chain (EIP-155) unless `chain_id` is `None`; typed transactions are signed
+    /// for their own chain ID.
+    pub fn sign(&mut self, secret_key: &SecretKey, chain_id: Option<u64>) {
+        let message = Message::from_slice(&self.signing_hash(chain_id)).expect("hash is 32 bytes");
+        let (recovery_id, compact) = Secp256k1::signing_only()
+            .sign_recoverable(&message, secret_key)
+            .serialize_compact();
+        let recovery_id = recovery_id.to_i32() as u64;
+        let v = match (&self, chain_id) {
+            (Transaction::Legacy(_), Some(chain_id)) => chain_id * 2 + 35 + recovery_id,
+            (Transaction::Legacy(_), None) => 27 + recovery_id,
+            _ => recovery_id,
+        };
+        *self.signature_mut() = Signature::new(
+            v,
+            U256::from_big_endian(&compact[..32]),
+            U256::from_big_endian(&compact[32..]),
+        );
+    }
+
+    /// The canonical encoding: RLP(T) for legacy transactions, T<sub>x</sub> · RLP(T) for typed
+    /// ones (EIP-2718). This is what gets hashed, put into the transactions trie and sent around.
     pub fn to_rlp(&self) -> Vec<u8> {
-        encode(self)
+        if let Transaction::Legacy(_) = self {
+            return encode(self);
+        }
+        let mut s = RlpStream::new_list(self.unsigned_field_count() + 3);
+        self.append_unsigned_fields(&mut s);
+        self.append_signature(&mut s);
+        self.with_type_prefix(s.out())
+    }
+
+    /// Decodes the canonical encoding, see `to_rlp`.
+    pub fn from_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        match data.first() {
+            Some(&first) if first >= 0xc0 => rlp::decode(data),
+            _ => Self::decode_typed(data),
+        }
     }
 
     /// Keccak 256-bit hash
@@ -110,40 +527,149 @@ impl Transaction {
         keccak256(&self.to_rlp()).into()
     }
 
-    /// Section 6 (beginning)
-    pub fn validate(&self) {
-        // TODO!!:
-        // (1) The transaction is well-formed RLP, with no additional trailing bytes;
-        // (2) the transaction signature is valid;
-        // (3) the transaction nonce is valid (equivalent to the sender account’s current nonce);
-        // (4) the gas limit is no smaller than the intrinsic gas, g0, used by the transaction; and
-        // (5) the sender account balance contains at least the cost, v0, required in up-front payment.
+    /// Section 6 (beginning):
+    /// (1) The transaction is well-formed RLP, with no additional trailing bytes;
+    /// (2) the transaction signature is valid;
+    /// (3) the transaction nonce is valid (equivalent to the sender account’s current nonce);
+    /// (4) the gas limit is no smaller than the intrinsic gas, g0, used by the transaction; and
+    /// (5) the sender account balance contains at least the cost, v0, required in up-front payment.
+    ///
+    /// (1) is ensured by decoding. See also Equation 58 - `gas_available` is B<sub>H<sub>l</sub></sub> − l(B<sub>R</sub>)<sub>u</sub>,
+    /// i.e. the block's gas limit minus the gas utilised in this block prior.
+    ///
+    /// `header` is the header of the block the transaction is included in, which determines the active
+    /// rules of the chain `config`. Legacy transactions without replay protection are valid either way.
+    pub fn validate(
+        &self,
+        state: &WorldState,
+        gas_available: U256,
+        header: &BlockHeader,
+        config: &ChainConfig,
+    ) -> Result<(), TransactionError> {
+        let sender = self.validate_stateless(header, config)?;
+        self.validate_against_state(sender, state, gas_available)
+    }
 
-        // See also Equation 58:
-        // S(T) != ∅ ∧
-        // σ[S(T)] != ∅ ∧
-        // Tn = σ[S(T)]n ∧
-        // g0 <= Tg ∧
-        // v0 <= σ[S(T)]b ∧
-        // Tg <= B_Hl−l(B_R)u
-        //  -> he sum of the transaction’s gas limit, Tg, and the gas utilised in this block prior,
-        //     given by l(BR)u, must be no greater than the block’s gasLimit, BHl
-        unimplemented!()
+    /// Like `validate`, but with the given sender instead of checking the signature (2). Used to
+    /// simulate unsigned transactions (`eth_call`, `eth_estimateGas`).
+    pub fn validate_as(
+        &self,
+        sender: Address,
+        state: &WorldState,
+        gas_available: U256,
+        header: &BlockHeader,
+        config: &ChainConfig,
+    ) -> Result<(), TransactionError> {
+        self.validate_rules(header, config)?;
+        self.validate_against_state(sender, state, gas_available)
+    }
+
+    /// The checks of `validate` that depend on the state: (3), (5) and the gas available in the block.
+    fn validate_against_state(
+        &self,
+        sender: Address,
+        state: &WorldState,
+        gas_available: U256,
+    ) -> Result<(), TransactionError> {
+        let account = state.accounts.get(&sender).cloned().unwrap_or_default();
+
+        if self.nonce() < account.nonce {
+            return Err(TransactionError::NonceTooLow {
+                expected: account.nonce,
+                got: self.nonce(),
+            });
+        }
+        if self.nonce() > account.nonce {
+            return Err(TransactionError::NonceTooHigh {
+                expected: account.nonce,
+                got: self.nonce(),
+            });
+        }
+
+        let (v0, overflow) = self.up_front_cost();
+        if overflow || v0 > account.balance {
+            return Err(TransactionError::InsufficientFunds {
+                required: v0,
+                balance: account.balance,
+            });
+        }
+
+        if self.gas_limit() > gas_available {
+            return Err(TransactionError::BlockGasLimitExceeded {
+                available: gas_available,
+                got: self.gas_limit(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The checks of `validate` that don't depend on the state: the signature (2), the intrinsic gas (4)
+    /// and the rules of the chain at the block with the given header. Returns the sender.
+    pub fn validate_stateless(
+        &self,
+        header: &BlockHeader,
+        config: &ChainConfig,
+    ) -> Result<Address, TransactionError> {
+        let sender = self.sender().ok_or(TransactionError::InvalidSignature)?;
+        self.validate_rules(header, config)?;
+        Ok(sender)
+    }
+
+    /// The checks of `validate_stateless` except the signature.
+    fn validate_rules(
+        &self,
+        header: &BlockHeader,
+        config: &ChainConfig,
+    ) -> Result<(), TransactionError> {
+        let fork_block = match self {
+            Transaction::Legacy(_) => Some(0),
+            Transaction::AccessList(_) => config.berlin_block,
+            Transaction::DynamicFee(_) => config.london_block,
+        };
+        if !ChainConfig::is_active(fork_block, header.number) {
+            return Err(TransactionError::UnsupportedType(self.transaction_type()));
+        }
+        if let Some(tx_chain_id) = self.chain_id() {
+            if config.eip155_chain_id(header.number) != Some(tx_chain_id) {
+                return Err(TransactionError::InvalidChainId(tx_chain_id));
+            }
+        }
+        if let Transaction::DynamicFee(t) = self {
+            if t.max_priority_fee_per_gas > t.max_fee_per_gas {
+                return Err(TransactionError::PriorityFeeAboveMaxFee {
+                    max_priority_fee: t.max_priority_fee_per_gas,
+                    max_fee: t.max_fee_per_gas,
+                });
+            }
+        }
+        if let Some(base_fee) = header.base_fee_per_gas {
+            if self.gas_price() < base_fee {
+                return Err(TransactionError::MaxFeeBelowBaseFee {
+                    max_fee: self.gas_price(),
+                    base_fee,
+                });
+            }
+        }
+
+        let g0 = self.intrinsic_gas();
+        if U256::from(g0) > self.gas_limit() {
+            return Err(TransactionError::IntrinsicGasTooLow {
+                required: g0,
+                got: self.gas_limit(),
+            });
+        }
+
+        Ok(())
     }
 
     /// Section 6.2: We define intrinsic gas g<sub>0</sub>, the amount of gas this transaction requires to be paid prior
     /// to execution, as follows:
     pub fn intrinsic_gas(&self) -> u64 {
         let mut g0: u64 = 0;
-        let data_or_code = if let Some(init) = &self.init {
-            init
-        } else if let Some(data) = &self.data {
-            data
-        } else {
-            unreachable!()
-        };
 
-        g0 += data_or_code
+        g0 += self
+            .data()
             .iter()
             .map(|i| {
                 if *i == 0 {
@@ -154,90 +680,347 @@ impl Transaction {
             })
             .sum::<u64>();
 
-        if self.to.is_none() {
+        if self.to().is_none() {
             g0 += FEES.tx_create;
         }
 
         g0 += FEES.transaction;
 
+        // EIP-2930
+        for item in self.access_list() {
+            g0 += FEES.access_list_address;
+            g0 += FEES.access_list_storage_key * item.storage_keys.len() as u64;
+        }
+
         g0
     }
 
-    /// v<sub>0</sub> (Equation 57)
-    pub fn up_front_cost(&self) -> U256 {
-        self.gas_limit * self.gas_price + self.value
+    /// T<sub>f</sub> for EIP-1559 transactions, T<sub>p</sub> otherwise.
+    pub fn max_priority_fee_per_gas(&self) -> Wei {
+        match self {
+            Transaction::DynamicFee(t) => t.max_priority_fee_per_gas,
+            _ => self.gas_price(),
+        }
+    }
+
+    /// p - the price actually paid per unit of gas, given the base fee of the block.
+    /// EIP-1559 transactions pay at most T<sub>m</sub>, and at most T<sub>f</sub> above the base fee.
+    pub fn effective_gas_price(&self, base_fee: U256) -> U256 {
+        self.gas_price()
+            .min(base_fee.saturating_add(self.max_priority_fee_per_gas()))
+    }
+
+    /// v<sub>0</sub> (Equation 57). The second value is `true` on overflow.
+    pub fn up_front_cost(&self) -> (U256, bool) {
+        let (gas_cost, overflow1) = self.gas_limit().overflowing_mul(self.gas_price());
+        let (cost, overflow2) = gas_cost.overflowing_add(self.value());
+        (cost, overflow1 || overflow2)
     }
 
     /// Section 6.2
-    pub fn execute(&self, state: &mut WorldState) {
-        let mut sender_account = state.accounts[&self.sender()].clone();
+    ///
+    /// Executes the transaction on `state` as part of the block with the given header, paying the fees to
+    /// its beneficiary. After London, the beneficiary only gets the priority fee - the base fee is burnt.
+    /// The transaction must have been validated against `state`.
+    pub fn execute(
+        &self,
+        state: &mut WorldState,
+        header: &BlockHeader,
+    ) -> Result<TransactionOutcome, TransactionError> {
+        let sender = self.sender().ok_or(TransactionError::InvalidSignature)?;
+        Ok(self.execute_as(sender, state, header))
+    }
+
+    /// `execute` with the given sender instead of the one recovered from the signature.
+    /// The transaction must have been validated with `validate_as`.
+    pub fn execute_as(
+        &self,
+        sender: Address,
+        state: &mut WorldState,
+        header: &BlockHeader,
+    ) -> TransactionOutcome {
+        self.execute_traced(sender, state, header, &mut NoopTracer)
+    }
+
+    /// `execute_as`, reporting the execution to `tracer`.
+    pub fn execute_traced(
+        &self,
+        sender: Address,
+        state: &mut WorldState,
+        header: &BlockHeader,
+        tracer: &mut dyn Tracer,
+    ) -> TransactionOutcome {
+        let beneficiary = header.beneficiary;
+        let base_fee = header.base_fee();
+        let gas_price = self.effective_gas_price(base_fee);
+        let gas_limit = self.gas_limit();
+        let mut sender_account = state.accounts.get(&sender).cloned().unwrap_or_default();
         // Equation 60
-        sender_account.balance -= self.gas_limit * self.gas_price;
+        sender_account.balance -= gas_limit * gas_price;
         // Equationn 61
         sender_account.nonce += 1.into();
+        state.accounts.insert(sender, sender_account);
 
         // => checkpoint state σ0
 
         // gas available for the proceeding computation (Equation 63)
-        let _g = self.gas_limit - self.intrinsic_gas();
+        let g = gas_limit - self.intrinsic_gas();
 
-        if self.to.is_some() {
-            self.execute_message_call(state)
+        let (kind, to) = match self.to() {
+            Some(to) => (CallKind::Call, to),
+            None => (CallKind::Create, contract_address(&sender, &self.nonce())),
+        };
+        tracer.enter(kind, sender, to, self.value(), gas_limit, self.data());
+        let logs = if self.to().is_some() {
+            self.execute_message_call(state, sender)
         } else {
-            self.execute_contract_creation()
-        }
+            self.execute_contract_creation(state, sender)
+        };
 
-        // TODO!!?
-    }
+        // There's no EVM yet, so none of the remaining gas g' is used and nothing is refunded.
+        let g_remaining = g;
+        // TODO!: refund counter, self destructed accounts... equation 64
+        let refund = U256::zero();
+        // g* (Equation 65)
+        let g_star = g_remaining + refund.min((gas_limit - g_remaining) / 2);
+        let gas_used = gas_limit - g_star;
 
-    // TODO!: refund counter, self destructed accounts... equation 64
-    // TODO!: g* ... Equation 65
+        // Equations 66-68: unused gas is refunded to the sender, the used gas is paid to the beneficiary
+        state.accounts.entry(sender).or_default().balance += g_star * gas_price;
+        state.accounts.entry(beneficiary).or_default().balance += gas_used * (gas_price - base_fee);
+
+        let output = vec![];
+        tracer.exit(gas_used, &output, None);
+        TransactionOutcome {
+            gas_used,
+            logs,
+            output,
+        }
+    }
 
     /// Section 7
-    pub fn execute_contract_creation(&self) {
-        unimplemented!() // TODO!!
+    ///
+    /// Creates the new account at a ≡ B<sub>96..255</sub>(KEC(RLP((s, σ[s]<sub>n</sub> − 1)))) (Equation 77)
+    /// with the endowment. There's no EVM yet, so `init` is not executed and the account has no code.
+    pub fn execute_contract_creation(
+        &self,
+        state: &mut WorldState,
+        sender: Address,
+    ) -> Vec<LogEntry> {
+        let sender_nonce = state.accounts[&sender].nonce - U256::from(1);
+        let address = contract_address(&sender, &sender_nonce);
+
+        let mut sender_account = state.accounts[&sender].clone();
+        let mut new_account = state.accounts.get(&address).cloned().unwrap_or_default();
+        sender_account.balance -= self.value();
+        new_account.balance += self.value();
+        state.accounts.insert(sender, sender_account);
+        state.accounts.insert(address, new_account);
+
+        vec![]
     }
 
     /// Section 8
-    pub fn execute_message_call(&self, state: &mut WorldState) {
+    pub fn execute_message_call(&self, state: &mut WorldState, sender: Address) -> Vec<LogEntry> {
         // We define σ1, the first transitional state as the original state but with the value
         // transferred from sender to recipient (Equation 99)
 
-        let sender_address = self.sender();
-        let mut sender_account = state.accounts[&sender_address].clone();
-        let to_address = self.to.expect("message call needs a recipient!");
+        let mut sender_account = state.accounts[&sender].clone();
+        sender_account.balance -= self.value();
+        state.accounts.insert(sender, sender_account);
+
+        let to_address = self.to().expect("message call needs a recipient!");
         let mut recipient_account = if let Some(acc) = state.accounts.get(&to_address) {
             acc.clone()
         } else {
             AccountState::default()
         };
+        recipient_account.balance += self.value();
+        state.accounts.insert(to_address, recipient_account);
 
-        sender_account.balance -= self.value;
-        recipient_account.balance += self.value;
+        // TODO!: execute code of the recipient (EVM)
+        vec![]
+    }
 
-        state.accounts.insert(sender_address, sender_account);
-        state.accounts.insert(to_address, recipient_account);
+    /// Number of fields without the signature.
+    fn unsigned_field_count(&self) -> usize {
+        match self {
+            Transaction::Legacy(_) => 6,
+            Transaction::AccessList(_) => 8,
+            Transaction::DynamicFee(_) => 9,
+        }
+    }
 
-        // TODO:!?
+    /// (T<sub>c</sub>), T<sub>n</sub>, (T<sub>f</sub>), T<sub>p</sub>/T<sub>m</sub>, T<sub>g</sub>,
+    /// T<sub>t</sub>, T<sub>v</sub>, T<sub>i</sub>/T<sub>d</sub>, (T<sub>A</sub>)
+    fn append_unsigned_fields(&self, s: &mut RlpStream) {
+        match self {
+            Transaction::Legacy(t) => {
+                s.append(&t.nonce);
+                s.append(&t.gas_price);
+            }
+            Transaction::AccessList(t) => {
+                s.append(&t.chain_id);
+                s.append(&t.nonce);
+                s.append(&t.gas_price);
+            }
+            Transaction::DynamicFee(t) => {
+                s.append(&t.chain_id);
+                s.append(&t.nonce);
+                s.append(&t.max_priority_fee_per_gas);
+                s.append(&t.max_fee_per_gas);
+            }
+        }
+        s.append(&self.gas_limit());
+        match self.to() {
+            Some(to) => s.append(&to),
+            None => s.append_empty_data(),
+        };
+        s.append(&self.value());
+        s.append(&self.data());
+        if self.transaction_type() != 0 {
+            s.append_list(self.access_list());
+        }
+    }
+
+    /// T<sub>w</sub>/T<sub>y</sub>, T<sub>r</sub>, T<sub>s</sub>
+    fn append_signature(&self, s: &mut RlpStream) {
+        let signature = self.signature();
+        s.append(&signature.v);
+        s.append(&signature.r);
+        s.append(&signature.s);
+    }
+
+    /// Prepends T<sub>x</sub> for typed transactions.
+    fn with_type_prefix(&self, payload: Vec<u8>) -> Vec<u8> {
+        if let Transaction::Legacy(_) = self {
+            return payload;
+        }
+        let mut out = Vec::with_capacity(payload.len() + 1);
+        out.push(self.transaction_type());
+        out.extend(payload);
+        out
+    }
+
+    /// Decodes T<sub>x</sub> · RLP(T) of a typed transaction.
+    fn decode_typed(data: &[u8]) -> Result<Self, DecoderError> {
+        let (&transaction_type, payload) = data.split_first().ok_or(DecoderError::RlpIsTooShort)?;
+        let rlp = Rlp::new(payload);
+        match transaction_type {
+            1 => {
+                if rlp.item_count()? != 11 {
+                    return Err(DecoderError::RlpIncorrectListLen);
+                }
+                Ok(Transaction::AccessList(AccessListTransaction {
+                    chain_id: rlp.val_at(0)?,
+                    nonce: rlp.val_at(1)?,
+                    gas_price: rlp.val_at(2)?,
+                    gas_limit: rlp.val_at(3)?,
+                    to: decode_to(&rlp.at(4)?)?,
+                    value: rlp.val_at(5)?,
+                    data: rlp.val_at(6)?,
+                    access_list: rlp.list_at(7)?,
+                    signature: decode_signature(&rlp, 8)?,
+                }))
+            }
+            2 => {
+                if rlp.item_count()? != 12 {
+                    return Err(DecoderError::RlpIncorrectListLen);
+                }
+                Ok(Transaction::DynamicFee(DynamicFeeTransaction {
+                    chain_id: rlp.val_at(0)?,
+                    nonce: rlp.val_at(1)?,
+                    max_priority_fee_per_gas: rlp.val_at(2)?,
+                    max_fee_per_gas: rlp.val_at(3)?,
+                    gas_limit: rlp.val_at(4)?,
+                    to: decode_to(&rlp.at(5)?)?,
+                    value: rlp.val_at(6)?,
+                    data: rlp.val_at(7)?,
+                    access_list: rlp.list_at(8)?,
+                    signature: decode_signature(&rlp, 9)?,
+                }))
+            }
+            _ => Err(DecoderError::Custom("unknown transaction type")),
+        }
     }
 }
 
+/// T<sub>t</sub> - empty for contract creations.
+fn decode_to(rlp: &Rlp) -> Result<Option<Address>, DecoderError> {
+    if rlp.is_empty() {
+        Ok(None)
+    } else {
+        rlp.as_val().map(Some)
+    }
+}
+
+/// The three signature fields, starting at `index`.
+fn decode_signature(rlp: &Rlp, index: usize) -> Result<Signature, DecoderError> {
+    Ok(Signature::new(
+        rlp.val_at(index)?,
+        rlp.val_at(index + 1)?,
+        rlp.val_at(index + 2)?,
+    ))
+}
+
+/// The address belonging to a public key: the rightmost 160 bits of its Keccak hash (Equation 287).
+pub fn public_key_to_address(public_key: &PublicKey) -> Address {
+    let mut address = Address::zero();
+    address.copy_from_slice(&keccak256(&public_key.serialize_uncompressed()[1..])[12..]);
+    address
+}
+
+/// The address belonging to a private key.
+pub fn secret_key_to_address(secret_key: &SecretKey) -> Address {
+    public_key_to_address(&PublicKey::from_secret_key(
+        &Secp256k1::signing_only(),
+        secret_key,
+    ))
+}
+
+/// The address of a contract created by `sender` with the given nonce (Equation 77).
+pub fn contract_address(sender: &Address, nonce: &U256) -> Address {
+    let mut s = RlpStream::new_list(2);
+    s.append(sender);
+    s.append(nonce);
+    let mut address = Address::zero();
+    address.copy_from_slice(&keccak256(&s.out())[12..]);
+    address
+}
+
 /// Equation 15
+///
+/// Legacy transactions are encoded as a list, typed transactions as a byte array containing
+/// T<sub>x</sub> · RLP(T), so that they can be told apart in a block's transaction list (EIP-2718).
 impl Encodable for Transaction {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.append(&self.nonce);
-        s.append(&self.gas_price);
-        s.append(&self.gas_limit);
-        s.append(&self.to);
-        s.append(&self.value);
-        if self.to.is_none() {
-            s.append(&self.init);
+        if let Transaction::Legacy(_) = self {
+            s.begin_list(9);
+            self.append_unsigned_fields(s);
+            self.append_signature(s);
         } else {
-            s.append(&self.data);
+            s.append(&self.to_rlp());
         }
-        s.append(&self.signature.v);
-        s.append(&self.signature.r);
-        s.append(&self.signature.s);
+    }
+}
+
+impl Decodable for Transaction {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if !rlp.is_list() {
+            return Self::decode_typed(rlp.data()?);
+        }
+        if rlp.item_count()? != 9 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Transaction::Legacy(LegacyTransaction {
+            nonce: rlp.val_at(0)?,
+            gas_price: rlp.val_at(1)?,
+            gas_limit: rlp.val_at(2)?,
+            to: decode_to(&rlp.at(3)?)?,
+            value: rlp.val_at(4)?,
+            data: rlp.val_at(5)?,
+            signature: decode_signature(rlp, 6)?,
+        }))
     }
 }
 
@@ -245,11 +1028,374 @@ impl Encodable for Transaction {
 mod tests {
     use super::*;
 
+    fn test_key() -> SecretKey {
+        SecretKey::from_slice(&[0x46; 32]).unwrap()
+    }
+
+    fn chain_config(chain_id: u64) -> ChainConfig {
+        ChainConfig {
+            chain_id,
+            ..ChainConfig::default()
+        }
+    }
+
+    /// The transaction of the EIP-155 example, unsigned.
+    fn eip155_example() -> LegacyTransaction {
+        LegacyTransaction {
+            nonce: 9.into(),
+            gas_price: 20_000_000_000u64.into(),
+            gas_limit: 21000.into(),
+            to: Some(Address::from("0x3535353535353535353535353535353535353535")),
+            value: *crate::lib::ONE_ETHER,
+            ..LegacyTransaction::default()
+        }
+    }
+
     #[test]
-    #[ignore] // TODO!!: missing implementation...
     fn test_transaction() {
         let t = Transaction::default();
-        assert_eq!(t.sender(), Address::zero());
+        assert_eq!(t.sender(), None);
+        assert_eq!(t.transaction_type(), 0);
+    }
+
+    #[test]
+    fn test_sign_and_recover_sender() {
+        let mut t = Transaction::Legacy(eip155_example());
+        t.sign(&test_key(), None);
+        assert!(t.signature().is_valid());
+        assert_eq!(
+            t.sender(),
+            Some(Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"))
+        );
+
+        if let Transaction::Legacy(legacy) = &mut t {
+            legacy.value += 1.into();
+        }
+        assert_ne!(
+            t.sender(),
+            Some(Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"))
+        );
+    }
+
+    #[test]
+    fn test_eip155() {
+        // example from https://eips.ethereum.org/EIPS/eip-155
+        let mut t = Transaction::Legacy(eip155_example());
+        assert_eq!(
+            t.signing_hash(Some(1)),
+            H256::from("0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53")
+        );
+        t.sign(&test_key(), Some(1));
+        assert_eq!(t.signature().v, 37);
+        assert_eq!(t.chain_id(), Some(1));
+        assert_eq!(
+            crate::lib::to_hex(&t.to_rlp()),
+            concat!(
+                "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7",
+                "6400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067",
+                "cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+            )
+        );
+        assert_eq!(
+            t.sender(),
+            Some(Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"))
+        );
+
+        let decoded: Transaction = rlp::decode(&t.to_rlp()).unwrap();
+        assert_eq!(decoded.chain_id(), Some(1));
+
+        let mut state = WorldState::new();
+        state.accounts.insert(
+            t.sender().unwrap(),
+            AccountState {
+                nonce: 9.into(),
+                ..AccountState::new(*crate::lib::ONE_ETHER * U256::from(2))
+            },
+        );
+        let header = BlockHeader::default();
+        assert_eq!(
+            t.validate(&state, 21000.into(), &header, &chain_config(1)),
+            Ok(())
+        );
+        assert_eq!(
+            t.validate(&state, 21000.into(), &header, &ChainConfig::default()),
+            Err(TransactionError::InvalidChainId(1))
+        );
+        let pre_eip155 = ChainConfig {
+            eip155_block: None,
+            ..chain_config(1)
+        };
+        assert_eq!(
+            t.validate(&state, 21000.into(), &header, &pre_eip155),
+            Err(TransactionError::InvalidChainId(1))
+        );
+
+        // legacy signatures are still valid with EIP-155
+        t.sign(&test_key(), None);
+        assert_eq!(t.chain_id(), None);
+        assert!(t.signature().v == 27 || t.signature().v == 28);
+        assert_eq!(
+            t.validate(&state, 21000.into(), &header, &chain_config(1)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_typed_transactions() {
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let access_list = vec![AccessListItem {
+            address: recipient,
+            storage_keys: vec![H256::from(U256::from(1))],
+        }];
+        let mut t = Transaction::AccessList(AccessListTransaction {
+            chain_id: 85,
+            nonce: 0.into(),
+            gas_price: 2.into(),
+            gas_limit: 30_000.into(),
+            to: Some(recipient),
+            value: 1000.into(),
+            access_list: access_list.clone(),
+            ..AccessListTransaction::default()
+        });
+        assert_eq!(
+            t.signing_hash(None),
+            H256::from("0x26142cc2e5b427787e35bbb448b838cb09b53efaf80a0a929ed4f9d2a1442f6b")
+        );
+        assert_eq!(t.intrinsic_gas(), 21000 + 2400 + 1900);
+
+        t.sign(&test_key(), None);
+        assert!(t.signature().v <= 1);
+        assert_eq!(t.chain_id(), Some(85));
+        assert_eq!(
+            t.sender(),
+            Some(Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"))
+        );
+        let rlp = t.to_rlp();
+        assert_eq!(rlp[0], 1);
+        assert_eq!(Transaction::from_rlp(&rlp).unwrap(), t);
+        // inside a block, the envelope is wrapped in a byte array
+        let decoded: Transaction = rlp::decode(&rlp::encode(&t)).unwrap();
+        assert_eq!(decoded, t);
+
+        let mut t = Transaction::DynamicFee(DynamicFeeTransaction {
+            chain_id: 85,
+            nonce: 0.into(),
+            max_priority_fee_per_gas: 1.into(),
+            max_fee_per_gas: 3.into(),
+            gas_limit: 30_000.into(),
+            to: Some(recipient),
+            value: 1000.into(),
+            access_list,
+            ..DynamicFeeTransaction::default()
+        });
+        assert_eq!(
+            t.signing_hash(None),
+            H256::from("0x0928bebf7e670fddd81ac702cd50663cf06eb55ac160694b85e79e973fd67270")
+        );
+        t.sign(&test_key(), None);
+        let rlp = t.to_rlp();
+        assert_eq!(rlp[0], 2);
+        assert_eq!(Transaction::from_rlp(&rlp).unwrap(), t);
+        assert_eq!(t.gas_price(), 3.into());
+        assert_eq!(t.effective_gas_price(0.into()), 1.into());
+        assert_eq!(t.effective_gas_price(1.into()), 2.into());
+        assert_eq!(t.effective_gas_price(10.into()), 3.into());
+
+        let mut state = WorldState::new();
+        state
+            .accounts
+            .insert(t.sender().unwrap(), AccountState::new(1_000_000.into()));
+        let header = BlockHeader::default();
+        assert_eq!(
+            t.validate(&state, 30_000.into(), &header, &ChainConfig::default()),
+            Err(TransactionError::UnsupportedType(2))
+        );
+        let london = ChainConfig {
+            london_block: Some(0),
+            ..ChainConfig::default()
+        };
+        assert_eq!(t.validate(&state, 30_000.into(), &header, &london), Ok(()));
+
+        let header = BlockHeader {
+            base_fee_per_gas: Some(2.into()),
+            ..BlockHeader::default()
+        };
+        assert_eq!(t.validate(&state, 30_000.into(), &header, &london), Ok(()));
+        let sender = t.sender().unwrap();
+        let outcome = t.execute(&mut state, &header).unwrap();
+        assert_eq!(outcome.gas_used, 25300.into());
+        // effective gas price 3, of which 2 are burnt
+        assert_eq!(
+            state.accounts[&sender].balance,
+            (1_000_000 - 1000 - 25300 * 3).into()
+        );
+        assert_eq!(state.accounts[&header.beneficiary].balance, 25300.into());
+        if let Transaction::DynamicFee(dynamic_fee) = &mut t {
+            dynamic_fee.nonce = 1.into();
+        }
+        t.sign(&test_key(), None);
+        let header = BlockHeader {
+            base_fee_per_gas: Some(4.into()),
+            ..BlockHeader::default()
+        };
+        assert_eq!(
+            t.validate(&state, 30_000.into(), &header, &london),
+            Err(TransactionError::MaxFeeBelowBaseFee {
+                max_fee: 3.into(),
+                base_fee: 4.into()
+            })
+        );
+
+        // a typed transaction with a legacy v
+        t.signature_mut().v += 27;
+        assert_eq!(t.sender(), None);
+
+        assert!(Transaction::from_rlp(&[3, 0xc0]).is_err());
+    }
+
+    #[test]
+    fn test_transaction_rlp_roundtrip() {
+        let mut t = Transaction::Legacy(LegacyTransaction {
+            nonce: 1.into(),
+            gas_price: 2.into(),
+            gas_limit: 84_000.into(),
+            to: None,
+            value: 42_000.into(),
+            data: vec![0x60, 0x00],
+            ..LegacyTransaction::default()
+        });
+        t.sign(&test_key(), None);
+        let decoded: Transaction = rlp::decode(&t.to_rlp()).unwrap();
+        assert_eq!(decoded, t);
+        assert_eq!(decoded.data(), &[0x60, 0x00]);
+
+        if let Transaction::Legacy(legacy) = &mut t {
+            legacy.to = Some(Address::random());
+            legacy.data = vec![1, 2, 3];
+        }
+        let decoded: Transaction = rlp::decode(&t.to_rlp()).unwrap();
+        assert_eq!(decoded, t);
+    }
+
+    #[test]
+    fn test_validate_and_execute() {
+        let sender = Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+        let beneficiary = Address::from("0x0000000000000000000000000000000000000001");
+        let header = BlockHeader {
+            beneficiary,
+            ..BlockHeader::default()
+        };
+        let config = ChainConfig::default();
+        let recipient = Address::from("0x3535353535353535353535353535353535353535");
+        let mut state = WorldState::new();
+        state
+            .accounts
+            .insert(sender, AccountState::new(1_000_000.into()));
+
+        let mut t = Transaction::Legacy(LegacyTransaction {
+            nonce: 0.into(),
+            gas_price: 2.into(),
+            gas_limit: 30_000.into(),
+            to: Some(recipient),
+            value: 1000.into(),
+            ..LegacyTransaction::default()
+        });
+        t.sign(&test_key(), None);
+        assert_eq!(t.validate(&state, 30_000.into(), &header, &config), Ok(()));
+        assert_eq!(
+            t.validate(&state, 29_999.into(), &header, &config),
+            Err(TransactionError::BlockGasLimitExceeded {
+                available: 29_999.into(),
+                got: 30_000.into()
+            })
+        );
+
+        let outcome = t.execute(&mut state, &header).unwrap();
+        assert_eq!(outcome.gas_used, 21000.into());
+        assert_eq!(state.accounts[&sender].nonce, 1.into());
+        assert_eq!(
+            state.accounts[&sender].balance,
+            (1_000_000 - 1000 - 21000 * 2).into()
+        );
+        assert_eq!(state.accounts[&recipient].balance, 1000.into());
+        assert_eq!(state.accounts[&beneficiary].balance, (21000 * 2).into());
+
+        // replay
+        assert_eq!(
+            t.validate(&state, 30_000.into(), &header, &config),
+            Err(TransactionError::NonceTooLow {
+                expected: 1.into(),
+                got: 0.into()
+            })
+        );
+
+        let mut t = Transaction::Legacy(LegacyTransaction {
+            nonce: 1.into(),
+            gas_price: 2.into(),
+            gas_limit: 20_000.into(),
+            to: Some(recipient),
+            ..LegacyTransaction::default()
+        });
+        t.sign(&test_key(), None);
+        assert_eq!(
+            t.validate(&state, 30_000.into(), &header, &config),
+            Err(TransactionError::IntrinsicGasTooLow {
+                required: 21000,
+                got: 20_000.into()
+            })
+        );
+
+        if let Transaction::Legacy(legacy) = &mut t {
+            legacy.gas_limit = 21000.into();
+            legacy.value = *crate::lib::ONE_ETHER;
+        }
+        t.sign(&test_key(), None);
+        assert_eq!(
+            t.validate(&state, 30_000.into(), &header, &config),
+            Err(TransactionError::InsufficientFunds {
+                required: *crate::lib::ONE_ETHER + U256::from(21000 * 2),
+                balance: (1_000_000 - 1000 - 21000 * 2).into()
+            })
+        );
+
+        // unsigned, with the sender given explicitly
+        let unsigned = Transaction::Legacy(LegacyTransaction {
+            nonce: 1.into(),
+            gas_limit: 21000.into(),
+            to: Some(recipient),
+            value: 500.into(),
+            ..LegacyTransaction::default()
+        });
+        assert_eq!(
+            unsigned.validate(&state, 30_000.into(), &header, &config),
+            Err(TransactionError::InvalidSignature)
+        );
+        assert_eq!(
+            unsigned.validate_as(sender, &state, 30_000.into(), &header, &config),
+            Ok(())
+        );
+        let outcome = unsigned.execute_as(sender, &mut state, &header);
+        assert_eq!(outcome.gas_used, 21000.into());
+        assert!(outcome.output.is_empty());
+        assert_eq!(state.accounts[&recipient].balance, 1500.into());
+    }
+
+    #[test]
+    fn test_secret_key_to_address() {
+        assert_eq!(
+            secret_key_to_address(&test_key()),
+            Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F")
+        );
+    }
+
+    #[test]
+    fn test_contract_address() {
+        // first contract created by 0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0
+        let sender = Address::from("0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+        assert_eq!(
+            contract_address(&sender, &0.into()),
+            Address::from("0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d")
+        );
     }
 
     #[test]
@@ -259,7 +1405,14 @@ mod tests {
         assert!(!s.is_valid());
 
         let s1 = sig(27, 1.into(), 1.into());
-        assert!(s1.is_valid())
+        assert!(s1.is_valid());
+
+        assert!(!sig(29, 1.into(), 1.into()).is_valid());
+        assert!(!sig(34, 1.into(), 1.into()).is_valid());
+        let s2 = sig(85 * 2 + 36, 1.into(), 1.into());
+        assert!(s2.is_valid());
+        assert_eq!(s2.chain_id(), Some(85));
+        assert_eq!(s2.recovery_id(), Some(1));
     }
 
     #[test]
@@ -267,18 +1420,21 @@ mod tests {
         // run with `cargo test -- --nocapture`
         println!("Transaction sizes:");
         println!(
-            "struct:                   {:>3} bytes",
+            "enum:                     {:>3} bytes",
             std::mem::size_of::<Transaction>()
         );
-        let mut transaction = Transaction::default();
+        let transaction = Transaction::default();
         let rlp: Vec<u8> = rlp::encode(&transaction);
         println!("rlp default:              {:>3} bytes", rlp.len());
 
-        transaction.nonce = 1234.into();
-        transaction.gas_price = 21000.into();
-        transaction.gas_limit = 3141592.into();
-        transaction.to = Some(Address::random());
-        transaction.value = Wei::from_dec_str("100000000000000000000").unwrap(); // 100 eth
+        let transaction = Transaction::Legacy(LegacyTransaction {
+            nonce: 1234.into(),
+            gas_price: 21000.into(),
+            gas_limit: 3141592.into(),
+            to: Some(Address::random()),
+            value: Wei::from_dec_str("100000000000000000000").unwrap(), // 100 eth
+            ..LegacyTransaction::default()
+        });
         let rlp: Vec<u8> = rlp::encode(&transaction);
         println!("rlp with some values:     {:>3} bytes", rlp.len());
     }
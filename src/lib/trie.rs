@@ -0,0 +1,200 @@
+use ethereum_types::H256;
+use lazy_static::lazy_static;
+use rlp::{RlpStream, NULL_RLP};
+use tiny_keccak::keccak256;
+
+lazy_static! {
+    /// The root hash of an empty trie, KEC(RLP(())).
+    pub static ref EMPTY_TRIE_ROOT: H256 = keccak256(&NULL_RLP).into();
+}
+
+/// TRIE(I) (Appendix D, Equation 195)
+///
+/// The root hash of the modified Merkle Patricia tree containing the given set of key/value pairs,
+/// where keys and values are arbitrary byte sequences. Only the root is computed, the nodes are not
+/// persisted anywhere.
+pub fn trie_root<I, K, V>(input: I) -> H256
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut items: Vec<(Vec<u8>, Vec<u8>)> = input
+        .into_iter()
+        .map(|(k, v)| (nibbles(k.as_ref()), v.as_ref().to_vec()))
+        .collect();
+    items.sort();
+    items.dedup_by(|a, b| a.0 == b.0);
+
+    keccak256(&node_rlp(&items, 0)).into()
+}
+
+/// Root of a trie keyed by the RLP-encoded index of each value, as used for the
+/// transactions (H<sub>t</sub>) and receipts (H<sub>e</sub>) roots.
+pub fn ordered_trie_root<I, V>(input: I) -> H256
+where
+    I: IntoIterator<Item = V>,
+    V: AsRef<[u8]>,
+{
+    trie_root(
+        input
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (rlp::encode(&i), v)),
+    )
+}
+
+/// Splits bytes into 4-bit nibbles (most significant first).
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| vec![b >> 4, b & 0x0f]).collect()
+}
+
+/// HP(x, t) - hex-prefix encoding (Appendix C)
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    out.extend(rest.chunks(2).map(|c| (c[0] << 4) | c[1]));
+    out
+}
+
+/// RLP(c(I, i)) - the structural composition function (Equation 197).
+/// `items` must be sorted by key.
+fn node_rlp(items: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Vec<u8> {
+    let mut s = RlpStream::new();
+    if items.is_empty() {
+        s.append_empty_data();
+        return s.out();
+    }
+
+    if items.len() == 1 {
+        let (key, value) = &items[0];
+        s.begin_list(2);
+        s.append(&hex_prefix(&key[depth..], true));
+        s.append(value);
+        return s.out();
+    }
+
+    // length of the longest common prefix of all keys, starting at `depth`
+    let first = &items[0].0;
+    let last = &items[items.len() - 1].0;
+    let mut prefix_end = depth;
+    while prefix_end < first.len()
+        && prefix_end < last.len()
+        && first[prefix_end] == last[prefix_end]
+    {
+        prefix_end += 1;
+    }
+
+    if prefix_end > depth {
+        // extension node
+        s.begin_list(2);
+        s.append(&hex_prefix(&first[depth..prefix_end], false));
+        append_node_ref(&mut s, items, prefix_end);
+        return s.out();
+    }
+
+    // branch node
+    s.begin_list(17);
+    let mut value = None;
+    let mut start = 0;
+    if first.len() == depth {
+        value = Some(&items[0].1);
+        start = 1;
+    }
+    for nibble in 0..16 {
+        let end = start
+            + items[start..]
+                .iter()
+                .take_while(|(k, _)| k[depth] == nibble)
+                .count();
+        if end == start {
+            s.append_empty_data();
+        } else {
+            append_node_ref(&mut s, &items[start..end], depth + 1);
+        }
+        start = end;
+    }
+    match value {
+        Some(value) => s.append(value),
+        None => s.append_empty_data(),
+    };
+    s.out()
+}
+
+/// n(I, i) - nodes whose RLP is shorter than 32 bytes are stored inline, otherwise by hash (Equation 196).
+fn append_node_ref(s: &mut RlpStream, items: &[(Vec<u8>, Vec<u8>)], depth: usize) {
+    let node = node_rlp(items, depth);
+    if node.len() < 32 {
+        s.append_raw(&node, 1);
+    } else {
+        s.append(&H256::from(keccak256(&node)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_trie_root() {
+        assert_eq!(
+            trie_root(Vec::<(Vec<u8>, Vec<u8>)>::new()),
+            H256::from("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+        );
+        assert_eq!(
+            trie_root(Vec::<(Vec<u8>, Vec<u8>)>::new()),
+            *EMPTY_TRIE_ROOT
+        );
+    }
+
+    #[test]
+    fn test_trie_root() {
+        // from ethereum/tests TrieTests/trietest.json ("puppy")
+        let root = trie_root(vec![
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ]);
+        assert_eq!(
+            root,
+            H256::from("0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+        );
+    }
+
+    #[test]
+    fn test_trie_root_branches() {
+        // from ethereum/tests TrieTests/trietest.json ("insert-middle-leaf")
+        let root = trie_root(vec![
+            ("key1aa", "0123456789012345678901234567890123456789xxx"),
+            ("key1", "0123456789012345678901234567890123456789Very_Long"),
+            ("key2bb", "aval3"),
+            ("key2", "short"),
+            ("key3cc", "aval3"),
+            ("key3", "1234567890123456789012345678901"),
+        ]);
+        assert_eq!(
+            root,
+            H256::from("0xcb65032e2f76c48b82b5c24b3db8f670ce73982869d38cd39a624f23d62a9e89")
+        );
+    }
+
+    #[test]
+    fn test_hex_prefix() {
+        assert_eq!(hex_prefix(&[1, 2, 3, 4, 5], false), vec![0x11, 0x23, 0x45]);
+        assert_eq!(
+            hex_prefix(&[0, 1, 2, 3, 4, 5], false),
+            vec![0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix(&[0xf, 1, 0xc, 0xb, 8], true),
+            vec![0x3f, 0x1c, 0xb8]
+        );
+    }
+}
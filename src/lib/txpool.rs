@@ -0,0 +1,502 @@
+//! The transaction pool: transactions submitted to the node that are not part of a block yet.
+
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+
+use ethereum_types::{Address, H256, U256};
+
+use crate::lib::{BlockHeader, ChainConfig, Transaction, TransactionError, WorldState};
+
+/// A transaction replacing one with the same sender and nonce must raise the gas price (and
+/// priority fee) by at least 1/10.
+const PRICE_BUMP_DIVISOR: u64 = 10;
+
+/// Limits on the size of the pool, so that it can't be flooded with transactions that are never
+/// included in a block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolLimits {
+    /// Maximum number of transactions in the pool.
+    pub max_transactions: usize,
+    /// Maximum number of transactions of a sender.
+    pub max_per_sender: usize,
+    /// How far the nonce of a transaction may be ahead of the nonce of its sender's account.
+    pub max_nonce_gap: u64,
+}
+
+impl Default for PoolLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: 4096,
+            max_per_sender: 64,
+            max_nonce_gap: 64,
+        }
+    }
+}
+
+/// Reasons for a transaction not to be accepted into the pool.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolError {
+    /// The transaction is already in the pool.
+    AlreadyKnown,
+    /// There's a transaction with the same sender and nonce that pays as much or only slightly less.
+    ReplacementUnderpriced,
+    /// The pool holds `PoolLimits::max_transactions` already.
+    PoolFull,
+    /// The sender has `PoolLimits::max_per_sender` transactions in the pool already.
+    SenderLimitExceeded,
+    /// The nonce is more than `PoolLimits::max_nonce_gap` ahead of the nonce of the sender's account.
+    NonceTooFarAhead {
+        max: U256,
+        got: U256,
+    },
+    Invalid(TransactionError),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolError::AlreadyKnown => write!(f, "already known"),
+            PoolError::ReplacementUnderpriced => write!(f, "replacement transaction underpriced"),
+            PoolError::PoolFull => write!(f, "txpool is full"),
+            PoolError::SenderLimitExceeded => write!(f, "account limit exceeded"),
+            PoolError::NonceTooFarAhead { max, got } => {
+                write!(f, "nonce too far ahead: max {}, got {}", max, got)
+            }
+            PoolError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<TransactionError> for PoolError {
+    fn from(e: TransactionError) -> Self {
+        PoolError::Invalid(e)
+    }
+}
+
+/// Transactions by sender and nonce.
+pub type TransactionsBySender = BTreeMap<Address, BTreeMap<U256, Transaction>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionPool {
+    /// Transactions that can be executed on top of the latest state, i.e. whose nonces follow the
+    /// sender's nonce without gaps.
+    pub pending: TransactionsBySender,
+    /// Transactions that have to wait for transactions with lower nonces.
+    pub queued: TransactionsBySender,
+    pub limits: PoolLimits,
+}
+
+impl TransactionPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validates the transaction against the latest state and the header of the next block and adds it
+    /// to the pool, replacing a transaction with the same sender and nonce if it pays enough more.
+    /// The sender must be able to pay for all of its transactions in the pool, and the `limits` must
+    /// not be exceeded. Returns the hash of the transaction.
+    pub fn add(
+        &mut self,
+        transaction: Transaction,
+        state: &WorldState,
+        header: &BlockHeader,
+        config: &ChainConfig,
+    ) -> Result<H256, PoolError> {
+        let hash = transaction.hash();
+        if self.contains(&hash) {
+            return Err(PoolError::AlreadyKnown);
+        }
+
+        let sender = transaction.validate_stateless(header, config)?;
+        let account = state.accounts.get(&sender).cloned().unwrap_or_default();
+        if transaction.nonce() < account.nonce {
+            return Err(TransactionError::NonceTooLow {
+                expected: account.nonce,
+                got: transaction.nonce(),
+            }
+            .into());
+        }
+        let max_nonce = account.nonce + U256::from(self.limits.max_nonce_gap);
+        if transaction.nonce() > max_nonce {
+            return Err(PoolError::NonceTooFarAhead {
+                max: max_nonce,
+                got: transaction.nonce(),
+            });
+        }
+        // the up-front costs of the other transactions of the sender have to be paid as well
+        let others = self
+            .pending
+            .get(&sender)
+            .into_iter()
+            .chain(self.queued.get(&sender))
+            .flat_map(BTreeMap::values)
+            .filter(|other| other.nonce() != transaction.nonce());
+        let (mut v0, mut overflow) = transaction.up_front_cost();
+        let mut sender_count = 1;
+        for other in others {
+            let (cost, cost_overflow) = other.up_front_cost();
+            let (sum, sum_overflow) = v0.overflowing_add(cost);
+            v0 = sum;
+            overflow |= cost_overflow || sum_overflow;
+            sender_count += 1;
+        }
+        if overflow || v0 > account.balance {
+            return Err(TransactionError::InsufficientFunds {
+                required: v0,
+                balance: account.balance,
+            }
+            .into());
+        }
+        if transaction.gas_limit() > header.gas_limit {
+            return Err(TransactionError::BlockGasLimitExceeded {
+                available: header.gas_limit,
+                got: transaction.gas_limit(),
+            }
+            .into());
+        }
+
+        let existing = self
+            .pending
+            .get(&sender)
+            .and_then(|transactions| transactions.get(&transaction.nonce()))
+            .or_else(|| {
+                self.queued
+                    .get(&sender)
+                    .and_then(|transactions| transactions.get(&transaction.nonce()))
+            });
+        if let Some(existing) = existing {
+            let bumped = |new: U256, old: U256| new >= old + old / PRICE_BUMP_DIVISOR;
+            if !bumped(transaction.gas_price(), existing.gas_price())
+                || !bumped(
+                    transaction.max_priority_fee_per_gas(),
+                    existing.max_priority_fee_per_gas(),
+                )
+            {
+                return Err(PoolError::ReplacementUnderpriced);
+            }
+        } else if sender_count > self.limits.max_per_sender {
+            return Err(PoolError::SenderLimitExceeded);
+        } else if self.pending_count() + self.queued_count() >= self.limits.max_transactions {
+            return Err(PoolError::PoolFull);
+        }
+
+        if let Some(transactions) = self.pending.get_mut(&sender) {
+            if let btree_map::Entry::Occupied(mut entry) = transactions.entry(transaction.nonce()) {
+                entry.insert(transaction);
+                return Ok(hash);
+            }
+        }
+        self.queued
+            .entry(sender)
+            .or_default()
+            .insert(transaction.nonce(), transaction);
+        self.promote(sender, account.nonce);
+        Ok(hash)
+    }
+
+    /// Moves the queued transactions of `sender` that directly follow the pending ones (or
+    /// `account_nonce` if there are none) to the pending ones.
+    fn promote(&mut self, sender: Address, account_nonce: U256) {
+        let mut next_nonce = self.next_pending_nonce(&sender).unwrap_or(account_nonce);
+        while let Some(transaction) = self
+            .queued
+            .get_mut(&sender)
+            .and_then(|transactions| transactions.remove(&next_nonce))
+        {
+            self.pending
+                .entry(sender)
+                .or_default()
+                .insert(next_nonce, transaction);
+            next_nonce += 1.into();
+        }
+        if self.queued.get(&sender).is_some_and(BTreeMap::is_empty) {
+            self.queued.remove(&sender);
+        }
+    }
+
+    fn next_pending_nonce(&self, address: &Address) -> Option<U256> {
+        self.pending
+            .get(address)
+            .and_then(|transactions| transactions.keys().next_back())
+            .map(|nonce| *nonce + U256::from(1))
+    }
+
+    /// The nonce the next transaction of `address` should have, taking pending transactions into account.
+    pub fn next_nonce(&self, address: &Address, state: &WorldState) -> U256 {
+        self.next_pending_nonce(address).unwrap_or_else(|| {
+            state
+                .accounts
+                .get(address)
+                .map_or_else(U256::zero, |account| account.nonce)
+        })
+    }
+
+    /// All transactions, pending ones first.
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.pending
+            .values()
+            .chain(self.queued.values())
+            .flat_map(BTreeMap::values)
+    }
+
+    pub fn get(&self, hash: &H256) -> Option<&Transaction> {
+        self.transactions()
+            .find(|transaction| transaction.hash() == *hash)
+    }
+
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.get(hash).is_some()
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn queued_count(&self) -> usize {
+        self.queued.values().map(BTreeMap::len).sum()
+    }
+
+    /// The pending transactions in the order they should be included in a block with the given base fee:
+    /// the one paying the highest price per gas first, while keeping the transactions of each sender
+    /// in nonce order.
+    pub fn ordered(&self, base_fee: U256) -> Vec<Transaction> {
+        let mut by_sender: Vec<Vec<&Transaction>> = self
+            .pending
+            .values()
+            .map(|transactions| transactions.values().rev().collect())
+            .collect();
+        let mut ordered = Vec::with_capacity(self.pending_count());
+        loop {
+            let best = by_sender
+                .iter_mut()
+                .filter(|transactions| !transactions.is_empty())
+                .max_by_key(|transactions| {
+                    transactions
+                        .last()
+                        .map(|transaction| transaction.effective_gas_price(base_fee))
+                });
+            match best.and_then(Vec::pop) {
+                Some(transaction) => ordered.push(transaction.clone()),
+                None => return ordered,
+            }
+        }
+    }
+
+    pub fn remove(&mut self, hash: &H256) -> Option<Transaction> {
+        for transactions_by_sender in [&mut self.pending, &mut self.queued].iter_mut() {
+            for transactions in transactions_by_sender.values_mut() {
+                let nonce = transactions
+                    .iter()
+                    .find(|(_, transaction)| transaction.hash() == *hash)
+                    .map(|(nonce, _)| *nonce);
+                if let Some(nonce) = nonce {
+                    return transactions.remove(&nonce);
+                }
+            }
+        }
+        None
+    }
+
+    /// Re-sorts the transactions after the state changed, e.g. because a block was added: transactions
+    /// whose nonce was used already are dropped, and pending transactions whose predecessor got removed
+    /// become queued again.
+    pub fn update(&mut self, state: &WorldState) {
+        let mut transactions = std::mem::take(&mut self.queued);
+        for (sender, pending) in std::mem::take(&mut self.pending) {
+            transactions.entry(sender).or_default().extend(pending);
+        }
+        for (sender, mut by_nonce) in transactions {
+            let account_nonce = state
+                .accounts
+                .get(&sender)
+                .map_or_else(U256::zero, |account| account.nonce);
+            let by_nonce = by_nonce.split_off(&account_nonce);
+            if !by_nonce.is_empty() {
+                self.queued.insert(sender, by_nonce);
+                self.promote(sender, account_nonce);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::{AccountState, LegacyTransaction, DEV_CHAIN_ID, ONE_ETHER};
+    use secp256k1::SecretKey;
+
+    fn unsigned_transfer(nonce: u64, gas_price: u64) -> LegacyTransaction {
+        LegacyTransaction {
+            nonce: nonce.into(),
+            gas_price: gas_price.into(),
+            gas_limit: 21000.into(),
+            to: Some(Address::from("0x3535353535353535353535353535353535353535")),
+            value: 1000.into(),
+            ..LegacyTransaction::default()
+        }
+    }
+
+    fn signed(transaction: LegacyTransaction, key: &SecretKey) -> Transaction {
+        let mut transaction = Transaction::Legacy(transaction);
+        transaction.sign(key, Some(DEV_CHAIN_ID));
+        transaction
+    }
+
+    fn transfer(nonce: u64, gas_price: u64) -> Transaction {
+        signed(
+            unsigned_transfer(nonce, gas_price),
+            &SecretKey::from_slice(&[0x46; 32]).unwrap(),
+        )
+    }
+
+    fn setup() -> (TransactionPool, WorldState, BlockHeader, ChainConfig) {
+        let mut state = WorldState::new();
+        state.accounts.insert(
+            Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F"),
+            AccountState::new(*ONE_ETHER),
+        );
+        let header = BlockHeader {
+            number: 1,
+            gas_limit: 3_141_592.into(),
+            ..BlockHeader::default()
+        };
+        (
+            TransactionPool::new(),
+            state,
+            header,
+            ChainConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_add() {
+        let (mut pool, state, header, config) = setup();
+        let sender = Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+
+        let hash = pool.add(transfer(1, 10), &state, &header, &config).unwrap();
+        assert_eq!(pool.queued_count(), 1);
+        assert_eq!(pool.pending_count(), 0);
+        assert_eq!(pool.next_nonce(&sender, &state), 0.into());
+
+        pool.add(transfer(0, 10), &state, &header, &config).unwrap();
+        assert_eq!(pool.queued_count(), 0);
+        assert_eq!(pool.pending_count(), 2);
+        assert_eq!(pool.next_nonce(&sender, &state), 2.into());
+        assert_eq!(pool.get(&hash), Some(&transfer(1, 10)));
+
+        assert_eq!(
+            pool.add(transfer(0, 10), &state, &header, &config),
+            Err(PoolError::AlreadyKnown)
+        );
+        assert_eq!(
+            pool.add(transfer(0, 9), &state, &header, &config),
+            Err(PoolError::ReplacementUnderpriced)
+        );
+        let replacement = pool.add(transfer(0, 11), &state, &header, &config).unwrap();
+        assert_eq!(pool.pending_count(), 2);
+        assert!(pool.contains(&replacement));
+
+        let unfunded = signed(
+            LegacyTransaction {
+                value: *ONE_ETHER,
+                ..unsigned_transfer(2, 10)
+            },
+            &SecretKey::from_slice(&[0x46; 32]).unwrap(),
+        );
+        match pool.add(unfunded, &state, &header, &config) {
+            Err(PoolError::Invalid(TransactionError::InsufficientFunds { .. })) => {}
+            result => panic!("expected insufficient funds, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let (mut pool, mut state, header, config) = setup();
+        pool.limits = PoolLimits {
+            max_transactions: 3,
+            max_per_sender: 2,
+            max_nonce_gap: 4,
+        };
+        assert_eq!(
+            pool.add(transfer(5, 10), &state, &header, &config),
+            Err(PoolError::NonceTooFarAhead {
+                max: 4.into(),
+                got: 5.into()
+            })
+        );
+        pool.add(transfer(4, 10), &state, &header, &config).unwrap();
+        pool.add(transfer(0, 10), &state, &header, &config).unwrap();
+        assert_eq!(
+            pool.add(transfer(1, 10), &state, &header, &config),
+            Err(PoolError::SenderLimitExceeded)
+        );
+        // replacements don't count
+        pool.add(transfer(4, 11), &state, &header, &config).unwrap();
+
+        let other_key = SecretKey::from_slice(&[0x47; 32]).unwrap();
+        let other = crate::lib::secret_key_to_address(&other_key);
+        state.accounts.insert(other, AccountState::new(*ONE_ETHER));
+        let from_other = |nonce: u64| signed(unsigned_transfer(nonce, 10), &other_key);
+        pool.add(from_other(0), &state, &header, &config).unwrap();
+        assert_eq!(
+            pool.add(from_other(1), &state, &header, &config),
+            Err(PoolError::PoolFull)
+        );
+
+        // the balance has to cover all transactions of the sender
+        let (mut pool, state, header, config) = setup();
+        let half = |nonce: u64, gas_price: u64| {
+            signed(
+                LegacyTransaction {
+                    value: *ONE_ETHER / 2,
+                    ..unsigned_transfer(nonce, gas_price)
+                },
+                &SecretKey::from_slice(&[0x46; 32]).unwrap(),
+            )
+        };
+        pool.add(half(0, 10), &state, &header, &config).unwrap();
+        match pool.add(half(1, 10), &state, &header, &config) {
+            Err(PoolError::Invalid(TransactionError::InsufficientFunds { required, .. })) => {
+                assert_eq!(required, *ONE_ETHER + U256::from(2 * 21000 * 10));
+            }
+            result => panic!("expected insufficient funds, got {:?}", result),
+        }
+        // a replacement only has to be paid instead of the replaced transaction
+        pool.add(half(0, 11), &state, &header, &config).unwrap();
+    }
+
+    #[test]
+    fn test_ordered_and_update() {
+        let (mut pool, mut state, header, config) = setup();
+        let sender = Address::from("0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+        let other_key = SecretKey::from_slice(&[0x47; 32]).unwrap();
+        let other = crate::lib::secret_key_to_address(&other_key);
+        state.accounts.insert(other, AccountState::new(*ONE_ETHER));
+
+        let expensive = signed(unsigned_transfer(0, 20), &other_key);
+        for transaction in &[transfer(0, 10), transfer(1, 30), expensive.clone()] {
+            pool.add(transaction.clone(), &state, &header, &config)
+                .unwrap();
+        }
+        // the nonce order of a sender comes before the price
+        assert_eq!(
+            pool.ordered(0.into()),
+            vec![expensive.clone(), transfer(0, 10), transfer(1, 30)]
+        );
+
+        // the first transaction of `sender` and the one of `other` were included in a block
+        state.accounts.get_mut(&sender).unwrap().nonce = 1.into();
+        state.accounts.get_mut(&other).unwrap().nonce = 1.into();
+        pool.add(transfer(3, 10), &state, &header, &config).unwrap();
+        pool.update(&state);
+        assert_eq!(pool.pending_count(), 1);
+        assert_eq!(pool.queued_count(), 1);
+        assert!(!pool.contains(&expensive.hash()));
+        assert_eq!(pool.next_nonce(&other, &state), 1.into());
+
+        assert_eq!(pool.remove(&transfer(1, 30).hash()), Some(transfer(1, 30)));
+        pool.update(&state);
+        assert_eq!(pool.pending_count(), 0);
+        assert_eq!(pool.queued_count(), 1);
+    }
+}
@@ -8,3 +8,65 @@ lazy_static! {
     /// lazy_static for one Ether in Wei.
     pub static ref ONE_ETHER: Wei = Wei::from(10).pow(18.into());
 }
+
+/// Decodes a hex string (with or without `0x` prefix) into bytes.
+/// Odd-length input is treated as having an implicit leading zero nibble.
+pub fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim_start_matches("0x").trim_start_matches("0X");
+    let padded;
+    let s = if s.len() % 2 == 1 {
+        padded = format!("0{}", s);
+        &padded
+    } else {
+        s
+    };
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format!("invalid hex: {}", s)))
+        .collect()
+}
+
+/// Encodes bytes as a `0x`-prefixed lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + bytes.len() * 2);
+    s.push_str("0x");
+    for b in bytes {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Parses a scalar given either as `0x`-prefixed hex or as a decimal string.
+pub fn parse_u256(s: &str) -> Result<U256, String> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        let bytes = from_hex(s)?;
+        if bytes.len() > 32 {
+            return Err(format!("value too large: {}", s));
+        }
+        Ok(U256::from_big_endian(&bytes))
+    } else {
+        U256::from_dec_str(s).map_err(|_| format!("invalid number: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(from_hex("0x0102ff").unwrap(), vec![1, 2, 255]);
+        assert_eq!(from_hex("102").unwrap(), vec![1, 2]);
+        assert_eq!(from_hex("0x").unwrap(), Vec::<u8>::new());
+        assert!(from_hex("0xzz").is_err());
+        assert_eq!(to_hex(&[0, 171]), "0x00ab");
+    }
+
+    #[test]
+    fn test_parse_u256() {
+        assert_eq!(parse_u256("0x400").unwrap(), 1024.into());
+        assert_eq!(parse_u256("1024").unwrap(), 1024.into());
+        assert_eq!(parse_u256("1000000000000000000").unwrap(), *ONE_ETHER);
+        assert!(parse_u256("abc").is_err());
+    }
+}
@@ -1,38 +1,27 @@
 use std::collections::HashMap;
 
-use ethereum_types::Address;
-// use keccak_hasher::KeccakHasher;
-// use kvdb::DBValue;
-// use memorydb::MemoryDB;
-// use patricia_trie_ethereum::TrieDBMut;
-// use hash_db::HashDB;
+use ethereum_types::{Address, H256, U256};
+use tiny_keccak::keccak256;
 
 use crate::lib::accountstate::AccountState;
-use crate::lib::ONE_ETHER;
+use crate::lib::trie_root;
 
 /// σ
 ///
 /// The world state (_state_), is a mapping between addresses (160-bit identifiers) and account states.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct WorldState {
     pub accounts: HashMap<Address, AccountState>,
-    // pub account_db: TrieDBMut<'db>,
-    // db: HashDB<KeccakHasher, DBValue>,
+    /// The storage contents of each account. σ[a]<sub>s</sub> is the root of the trie built from these.
+    pub storage: HashMap<Address, HashMap<H256, U256>>,
+    /// EVM code fragments, keyed by their Keccak hash σ[a]<sub>c</sub>.
+    pub code: HashMap<H256, Vec<u8>>,
 }
 
 #[allow(dead_code)]
 impl WorldState {
     pub fn new() -> Self {
-        // let mut memdb = MemoryDB::<KeccakHasher, DBValue>::new();
-        // let mut root = H256::new();
-        // let mut t = TrieDBMut::new(&mut memdb, &mut root);
-
-        // let db = HashDB::<KeccakHasher, DBValue>new();
-        // let account_db =
-        WorldState {
-            accounts: HashMap::new(),
-            // account_db:: TrieDBMut::new(&mut memdb, &mut root);
-        }
+        Self::default()
     }
 
     /// An account is dead when its account state is non-existent or empty:
@@ -42,21 +31,63 @@ impl WorldState {
         account.is_empty() || !self.accounts.values().any(|x| x == account)
     }
 
-    pub fn genesis_state() -> Self {
-        let mut state = Self::new();
-        let account = AccountState::new(*ONE_ETHER * 42u32);
-        state.accounts.insert(
-            Address::from("0xe94C35a6a32c759865dc49807f0ad3056848C8BF"),
-            account,
-        );
+    /// TRIE(L<sub>S</sub>(σ)) - the state root (Equation 9).
+    ///
+    /// L<sub>S</sub>(σ) ≡ {p(a) : σ[a] ≠ ∅}, with p(a) ≡ (KEC(a), RLP((σ[a]<sub>n</sub>, σ[a]<sub>b</sub>, σ[a]<sub>s</sub>, σ[a]<sub>c</sub>)))
+    pub fn state_root(&self) -> H256 {
+        trie_root(
+            self.accounts
+                .iter()
+                .map(|(address, account)| (keccak256(address), rlp::encode(account))),
+        )
+    }
+
+    /// The root of the storage trie of the account at `address`. Keys are stored as the Keccak hash of the
+    /// 256-bit key, values as RLP-encoded 256-bit integers; zero values are not part of the trie.
+    pub fn storage_root(&self, address: &Address) -> H256 {
+        let empty = HashMap::new();
+        let storage = self.storage.get(address).unwrap_or(&empty);
+        trie_root(
+            storage
+                .iter()
+                .filter(|(_, value)| !value.is_zero())
+                .map(|(key, value)| (keccak256(key), rlp::encode(value))),
+        )
+    }
+
+    pub fn storage_at(&self, address: &Address, key: &H256) -> U256 {
+        self.storage
+            .get(address)
+            .and_then(|storage| storage.get(key))
+            .cloned()
+            .unwrap_or_else(U256::zero)
+    }
 
-        let account = AccountState::new(*ONE_ETHER * 420u32);
-        state.accounts.insert(Address::random(), account);
+    /// Sets a storage value and updates σ[a]<sub>s</sub> accordingly.
+    pub fn set_storage(&mut self, address: Address, key: H256, value: U256) {
+        let storage = self.storage.entry(address).or_default();
+        if value.is_zero() {
+            storage.remove(&key);
+        } else {
+            storage.insert(key, value);
+        }
+        let storage_root = self.storage_root(&address);
+        self.accounts.entry(address).or_default().storage_root = storage_root;
+    }
 
-        let account = AccountState::new(*ONE_ETHER * 4200u32);
-        state.accounts.insert(Address::random(), account);
+    pub fn code_at(&self, address: &Address) -> Vec<u8> {
+        self.accounts
+            .get(address)
+            .and_then(|account| self.code.get(&account.code_hash))
+            .cloned()
+            .unwrap_or_default()
+    }
 
-        state
+    /// Stores the code in the state database and sets σ[a]<sub>c</sub> to its hash.
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        let code_hash: H256 = keccak256(&code).into();
+        self.code.insert(code_hash, code);
+        self.accounts.entry(address).or_default().code_hash = code_hash;
     }
 }
 
@@ -82,6 +113,7 @@ mod tests {
             ]
             .into_iter()
             .collect(),
+            ..Default::default()
         };
         assert!(world.is_account_dead(&empty_acc));
         assert!(!world.is_account_dead(&non_empty_acc));
@@ -1,11 +1,19 @@
 //! Toy Ethereum implementation closely following the [Yellow Paper](https://github.com/ethereum/yellowpaper/) ([PDF](https://ethereum.github.io/yellowpaper/paper.pdf)).
 //! Structs, fields and methods are annotated with their formal definition where applicable.
-//! 
-//! Currently implements a small subset of the [JSON-RPC API](https://github.com/ethereum/wiki/wiki/JSON-RPC) 
+//!
+//! Currently implements a small subset of the [JSON-RPC API](https://github.com/ethereum/wiki/wiki/JSON-RPC)
 //! and a few custom methods. See the `rpc` module for details.
-//! 
+//!
 //! See [README.md](https://github.com/bwasty/teth/blob/master/README.md) for more info.
 
+use std::fs::{self, File};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
 use structopt::StructOpt;
 
 mod lib;
@@ -14,17 +22,166 @@ pub use lib::*;
 fn main() {
     let opt = Options::from_args();
     match opt.commands {
-        SubCommand::Node { bootstrap } => {
-            if bootstrap {
-                let state = WorldState::genesis_state();
-                let mut block_chain = BlockChain::new();
-                block_chain.add_block(Block::genesis_block());
-                block_chain.add_block(Block::exodus_block());
-                rpc::start_websocket_server(state, block_chain);
+        SubCommand::Node {
+            bootstrap,
+            genesis,
+            datadir,
+            allow_unprotected_txs,
+            block_time,
+            state_history,
+            archive,
+            ws_addr,
+            ws_port,
+            http,
+            http_addr,
+            http_port,
+            http_corsdomain,
+            http_vhosts,
+            ipc_path,
+            rpc_apis,
+            rpc_behind_proxy,
+            ipc_apis,
+            faucet_key_file,
+            faucet_signature,
+            faucet_address_cooldown,
+            faucet_ip_cooldown,
+            faucet_daily_limit,
+            faucet_ip_daily_limit,
+        } => {
+            // A genesis file or a data directory is only used by a node running its own chain
+            if bootstrap || genesis.is_some() || datadir.is_some() {
+                let dev_chain = genesis.is_none();
+                let retention = if archive {
+                    StateRetention::Archive
+                } else {
+                    StateRetention::Recent(state_history)
+                };
+                let (state, block_chain) = load_chain(genesis, None, retention);
+                let mut node = Node::new(block_chain, state);
+                if dev_chain {
+                    node.accounts = dev_accounts();
+                }
+                node.datadir = datadir.clone();
+                let node = Arc::new(Mutex::new(node));
+
+                let faucet_key = match faucet_key_file {
+                    Some(path) => Some(
+                        fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|key| parse_secret_key(&key))
+                            .unwrap_or_else(|e| exit_with_error(&path, e)),
+                    ),
+                    None if dev_chain => parse_secret_key(DEV_ACCOUNT_KEYS[1]).ok(),
+                    None => None,
+                };
+                let faucet_config = FaucetConfig {
+                    address_cooldown: Duration::from_secs(faucet_address_cooldown),
+                    ip_cooldown: Duration::from_secs(faucet_ip_cooldown),
+                    daily_limit: faucet_daily_limit,
+                    ip_daily_limit: faucet_ip_daily_limit,
+                    require_signature: faucet_signature,
+                    ..FaucetConfig::default()
+                };
+
+                // The stored chain is imported while the node is already serving (see `eth_syncing`)
+                let chain_file = datadir.map(|datadir| datadir.join(CHAIN_FILE));
+                if let Some(chain_file) = chain_file.filter(|chain_file| chain_file.exists()) {
+                    let blocks = File::open(&chain_file)
+                        .map_err(ImportError::Io)
+                        .and_then(|mut f| read_blocks(&mut f))
+                        .unwrap_or_else(|e| exit_with_error(&chain_file, e));
+                    let node = node.clone();
+                    thread::spawn(move || {
+                        let count = sync_blocks(&node, blocks)
+                            .unwrap_or_else(|e| exit_with_error(&chain_file, e));
+                        println!("Imported {} blocks from {}", count, chain_file.display());
+                    });
+                }
+
+                let config = rpc::ServerConfig {
+                    allow_unprotected_txs,
+                    block_time,
+                    ws_address: SocketAddr::new(ws_addr, ws_port),
+                    http_address: if http {
+                        Some(SocketAddr::new(http_addr, http_port))
+                    } else {
+                        None
+                    },
+                    http_cors_domains: http_corsdomain,
+                    http_vhosts,
+                    ipc_path,
+                    rpc_apis,
+                    ipc_apis,
+                    faucet: faucet_key.map(|key| Faucet::new(key, faucet_config)),
+                    dev: dev_chain,
+                    behind_proxy: rpc_behind_proxy,
+                };
+                rpc::start_servers(node, &config);
             } else {
                 // TODO!: connect to master node (teth.malkut.net / localhost -> arg...)
                 unimplemented!()
             }
         }
+        SubCommand::Export {
+            file,
+            genesis,
+            datadir,
+        } => {
+            let (_, block_chain) = load_chain(genesis, Some(&datadir), StateRetention::default());
+            let count = File::create(&file)
+                .and_then(|mut f| export_blocks(&block_chain, &mut f))
+                .unwrap_or_else(|e| exit_with_error(&file, e));
+            println!("Exported {} blocks to {}", count, file.display());
+        }
+        SubCommand::Import {
+            file,
+            genesis,
+            datadir,
+        } => {
+            let (mut state, mut block_chain) =
+                load_chain(genesis, Some(&datadir), StateRetention::default());
+            let count = File::open(&file)
+                .map_err(ImportError::Io)
+                .and_then(|mut f| import_blocks(&mut block_chain, &mut state, &mut f))
+                .unwrap_or_else(|e| exit_with_error(&file, e));
+
+            let chain_file = datadir.join(CHAIN_FILE);
+            fs::create_dir_all(&datadir)
+                .and_then(|_| File::create(&chain_file))
+                .and_then(|mut f| export_blocks(&block_chain, &mut f))
+                .unwrap_or_else(|e| exit_with_error(&chain_file, e));
+            println!("Imported {} blocks from {}", count, file.display());
+        }
     }
 }
+
+/// Creates the genesis state and block and imports the chain stored in the data directory (if any).
+fn load_chain(
+    genesis: Option<PathBuf>,
+    datadir: Option<&Path>,
+    retention: StateRetention,
+) -> (WorldState, BlockChain) {
+    let genesis = match genesis {
+        Some(path) => Genesis::from_file(&path).unwrap_or_else(|e| exit_with_error(&path, e)),
+        None => Genesis::dev(),
+    };
+    let mut state = genesis.state();
+    let mut block_chain = BlockChain::from_genesis(&genesis);
+    block_chain.states.set_retention(retention);
+
+    if let Some(datadir) = datadir {
+        let chain_file = datadir.join(CHAIN_FILE);
+        if chain_file.exists() {
+            File::open(&chain_file)
+                .map_err(ImportError::Io)
+                .and_then(|mut f| import_blocks(&mut block_chain, &mut state, &mut f))
+                .unwrap_or_else(|e| exit_with_error(&chain_file, e));
+        }
+    }
+    (state, block_chain)
+}
+
+fn exit_with_error<T>(path: &Path, error: impl std::fmt::Display) -> T {
+    eprintln!("Error ({}): {}", path.display(), error);
+    process::exit(1)
+}